@@ -1,4 +1,5 @@
 use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Parser, Debug, Default)]
 pub struct AgentOptions {
@@ -18,6 +19,11 @@ pub enum AgentCommands {
         #[command(subcommand)]
         command: NetworkSubCommands,
     },
+    #[command(about = "Export or import the DID and keyring of this node")]
+    Identity {
+        #[command(subcommand)]
+        command: IdentitySubCommands,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -34,4 +40,24 @@ pub enum NetworkSubCommands {
         #[arg(short, long)]
         key: String,
     },
+    #[command(about = "Unset a network configuration")]
+    Unset {
+        #[arg(short, long)]
+        key: String,
+    },
+}
+
+// NOTE: The password is read from NODEX_IDENTITY_PASSWORD so it does not show up in the process list.
+#[derive(Subcommand, Debug)]
+pub enum IdentitySubCommands {
+    #[command(about = "Write an encrypted backup of the DID and keyring")]
+    Export {
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    #[command(about = "Replace the DID and keyring with an encrypted backup")]
+    Import {
+        #[arg(short, long)]
+        input: PathBuf,
+    },
 }
@@ -1,9 +1,13 @@
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, Key, KeyInit, Nonce};
 use home_config::HomeConfig;
 use protocol::keyring::keypair::{
     K256KeyPair, KeyPair, KeyPairHex, KeyPairing, KeyPairingError, X25519KeyPair,
 };
+use protocol::rand_core::{OsRng, RngCore};
 use serde::Deserialize;
 use serde::Serialize;
+use std::collections::HashMap;
 use std::env;
 use std::io;
 use std::io::Write;
@@ -14,8 +18,11 @@ use std::{
 };
 use std::{fs::OpenOptions, sync::MutexGuard};
 use thiserror::Error;
+use url::Url;
+use zeroize::Zeroizing;
 
 use crate::nodex::utils::UnwrapLog;
+use crate::usecase::verify_policy::VerifyPolicy;
 
 #[derive(Clone, Deserialize, Serialize)]
 struct KeyPairsConfig {
@@ -64,10 +71,12 @@ pub struct DidCommConfig {
 #[serde(default)]
 pub struct ConfigRoot {
     did: Option<String>,
+    additional_dids: HashMap<String, String>,
     key_pairs: KeyPairsConfig,
     extensions: ExtensionsConfig,
     metrics: MetricsConfig,
     didcomm: DidCommConfig,
+    verify_policy: VerifyPolicy,
     is_initialized: bool,
     schema_version: u8,
 }
@@ -76,6 +85,7 @@ impl Default for ConfigRoot {
     fn default() -> Self {
         ConfigRoot {
             did: None,
+            additional_dids: HashMap::new(),
             key_pairs: KeyPairsConfig {
                 sign: None,
                 update: None,
@@ -91,16 +101,39 @@ impl Default for ConfigRoot {
                 collect_interval: 15,
                 send_interval: 60,
                 cache_capacity: 1 << 16,
+                per_interface: false,
             },
             didcomm: DidCommConfig {
                 http_body_size_limit: 3 * 1024 * 1024,
             },
+            verify_policy: VerifyPolicy::default(),
             is_initialized: false,
             schema_version: 1,
         }
     }
 }
 
+// NOTE: The DID stored in `did` is addressed by this alias.
+pub const DEFAULT_DID_ALIAS: &str = "default";
+
+impl ConfigRoot {
+    fn did_by_alias(&self, alias: &str) -> Option<String> {
+        if alias == DEFAULT_DID_ALIAS {
+            return self.did.clone();
+        }
+        self.additional_dids.get(alias).cloned()
+    }
+
+    fn set_did_with_alias(&mut self, alias: &str, did: &str) {
+        if alias == DEFAULT_DID_ALIAS {
+            self.did = Some(did.to_string());
+        } else {
+            self.additional_dids
+                .insert(alias.to_string(), did.to_string());
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct SingletonAppConfig {
     inner: Arc<Mutex<AppConfig>>,
@@ -139,8 +172,36 @@ pub struct AppConfig {
 pub enum AppConfigError<E: std::error::Error> {
     #[error("key decode failed")]
     DecodeFailed(E),
-    #[error("failed to write config file")]
-    WriteError(home_config::JsonError),
+    #[error("failed to write config file: {0}")]
+    WriteError(#[source] io::Error),
+}
+
+// NOTE: Writes to a temporary file in the same directory and renames it over `path`,
+// so an interrupted write never leaves a truncated config behind.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let mut file = OpenOptions::new()
+        .truncate(true)
+        .create(true)
+        .write(true)
+        .open(&tmp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&tmp_path, path)
+}
+
+pub(crate) fn write_json_atomically<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let contents = serde_json::to_vec_pretty(value)?;
+    write_atomically(path, &contents)
 }
 
 fn convert_to_key<U, V, T: KeyPair<U, V>>(
@@ -155,6 +216,114 @@ fn load_key_pair<U, V, T: KeyPair<U, V>>(kind: &Option<KeyPairHex>) -> Option<T>
         .and_then(|key| convert_to_key(key).map_err(|e| log::error!("{:?}", e)).ok())
 }
 
+#[derive(Error, Debug)]
+pub enum IdentityError {
+    #[error("not a NodeX identity export")]
+    InvalidFormat,
+    #[error("failed to derive key from password: {0}")]
+    Kdf(argon2::Error),
+    #[error("failed to encrypt identity")]
+    Encrypt,
+    #[error("wrong password or corrupted identity export")]
+    Decrypt,
+    #[error("identity export does not contain a DID and a complete keyring")]
+    Incomplete,
+    #[error("failed to serialize identity: {0}")]
+    Serialize(#[from] serde_json::Error),
+    #[error("failed to write config file: {0}")]
+    Write(#[source] io::Error),
+}
+
+// NOTE: Everything needed to act as this node on another host.
+#[derive(Deserialize, Serialize)]
+pub struct Identity {
+    did: Option<String>,
+    additional_dids: HashMap<String, String>,
+    key_pairs: KeyPairsConfig,
+}
+
+impl Identity {
+    fn is_complete(&self) -> bool {
+        let keys = &self.key_pairs;
+        self.did.is_some()
+            && load_key_pair::<_, _, K256KeyPair>(&keys.sign).is_some()
+            && load_key_pair::<_, _, K256KeyPair>(&keys.update).is_some()
+            && load_key_pair::<_, _, K256KeyPair>(&keys.recovery).is_some()
+            && load_key_pair::<_, _, X25519KeyPair>(&keys.encrypt).is_some()
+    }
+
+    pub fn keyring(&self) -> Result<KeyPairing, IdentityError> {
+        let keys = &self.key_pairs;
+        Ok(KeyPairing {
+            sign: load_key_pair(&keys.sign).ok_or(IdentityError::Incomplete)?,
+            update: load_key_pair(&keys.update).ok_or(IdentityError::Incomplete)?,
+            recovery: load_key_pair(&keys.recovery).ok_or(IdentityError::Incomplete)?,
+            encrypt: load_key_pair(&keys.encrypt).ok_or(IdentityError::Incomplete)?,
+        })
+    }
+}
+
+// Export layout: MAGIC || salt || nonce || AES-256-GCM(JSON identity), keyed by
+// Argon2id(password, salt). MAGIC is bound as associated data.
+const IDENTITY_MAGIC: &[u8] = b"NODEX-IDENTITY-V1";
+const IDENTITY_SALT_LEN: usize = 16;
+const IDENTITY_NONCE_LEN: usize = 12;
+
+fn identity_cipher(password: &str, salt: &[u8]) -> Result<Aes256Gcm, IdentityError> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    argon2::Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key[..])
+        .map_err(IdentityError::Kdf)?;
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key[..])))
+}
+
+fn seal_identity(identity: &Identity, password: &str) -> Result<Vec<u8>, IdentityError> {
+    let mut salt = [0u8; IDENTITY_SALT_LEN];
+    let mut nonce = [0u8; IDENTITY_NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+
+    let plaintext = Zeroizing::new(serde_json::to_vec(identity)?);
+    let ciphertext = identity_cipher(password, &salt)?
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &plaintext,
+                aad: IDENTITY_MAGIC,
+            },
+        )
+        .map_err(|_| IdentityError::Encrypt)?;
+
+    Ok([IDENTITY_MAGIC, &salt, &nonce, &ciphertext].concat())
+}
+
+pub fn open_identity(blob: &[u8], password: &str) -> Result<Identity, IdentityError> {
+    let rest = blob
+        .strip_prefix(IDENTITY_MAGIC)
+        .filter(|rest| rest.len() > IDENTITY_SALT_LEN + IDENTITY_NONCE_LEN)
+        .ok_or(IdentityError::InvalidFormat)?;
+    let (salt, rest) = rest.split_at(IDENTITY_SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(IDENTITY_NONCE_LEN);
+
+    let plaintext = Zeroizing::new(
+        identity_cipher(password, salt)?
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: IDENTITY_MAGIC,
+                },
+            )
+            .map_err(|_| IdentityError::Decrypt)?,
+    );
+    let identity: Identity =
+        serde_json::from_slice(&plaintext).map_err(|_| IdentityError::InvalidFormat)?;
+    if !identity.is_complete() {
+        return Err(IdentityError::Incomplete);
+    }
+    Ok(identity)
+}
+
 impl AppConfig {
     fn touch(path: &Path) -> io::Result<()> {
         let mut file = OpenOptions::new()
@@ -184,9 +353,29 @@ impl AppConfig {
     }
 
     pub fn write(&self) -> Result<(), AppConfigError<KeyPairingError>> {
-        self.config
-            .save_json(&self.root)
-            .map_err(AppConfigError::WriteError)
+        write_json_atomically(self.config.path(), &self.root).map_err(AppConfigError::WriteError)
+    }
+
+    // NOTE: Encrypts the DID and keyring with `password` so they can be moved to another host.
+    pub fn export_identity(&self, password: &str) -> Result<Vec<u8>, IdentityError> {
+        let identity = Identity {
+            did: self.root.did.clone(),
+            additional_dids: self.root.additional_dids.clone(),
+            key_pairs: self.root.key_pairs.clone(),
+        };
+        if !identity.is_complete() {
+            return Err(IdentityError::Incomplete);
+        }
+        seal_identity(&identity, password)
+    }
+
+    // NOTE: Replaces the DIDs with those from an `open_identity` result. The keyring is not
+    // touched here; callers store `identity.keyring()` through the configured keystore first.
+    pub fn import_identity(&mut self, identity: Identity) -> Result<(), IdentityError> {
+        self.root.did = identity.did;
+        self.root.additional_dids = identity.additional_dids;
+        self.root.is_initialized = true;
+        write_json_atomically(self.config.path(), &self.root).map_err(IdentityError::Write)
     }
 
     pub fn load_trng_read_sig(&self) -> Option<Extension> {
@@ -231,64 +420,79 @@ impl AppConfig {
         load_key_pair(&self.root.key_pairs.sign)
     }
 
-    pub fn load_keyring(&self) -> Option<KeyPairing> {
-        let sign = self.load_sign_key_pair()?;
-        let update = self.load_update_key_pair()?;
-        let recovery = self.load_recovery_key_pair()?;
-        let encrypt = self.load_encrypt_key_pair()?;
-        Some(KeyPairing {
-            sign,
-            update,
-            recovery,
-            encrypt,
-        })
-    }
-
-    pub fn save_sign_key_pair(&mut self, value: &K256KeyPair) {
+    pub fn save_sign_key_pair(
+        &mut self,
+        value: &K256KeyPair,
+    ) -> Result<(), AppConfigError<KeyPairingError>> {
         self.root.key_pairs.sign = Some(value.to_hex_key_pair());
-        self.write().unwrap();
+        self.write()
     }
 
     pub fn load_update_key_pair(&self) -> Option<K256KeyPair> {
         load_key_pair(&self.root.key_pairs.update)
     }
 
-    pub fn save_update_key_pair(&mut self, value: &K256KeyPair) {
+    pub fn save_update_key_pair(
+        &mut self,
+        value: &K256KeyPair,
+    ) -> Result<(), AppConfigError<KeyPairingError>> {
         self.root.key_pairs.update = Some(value.to_hex_key_pair());
-        self.write().unwrap();
+        self.write()
     }
 
     pub fn load_recovery_key_pair(&self) -> Option<K256KeyPair> {
         load_key_pair(&self.root.key_pairs.recovery)
     }
 
-    pub fn save_recovery_key_pair(&mut self, value: &K256KeyPair) {
+    pub fn save_recovery_key_pair(
+        &mut self,
+        value: &K256KeyPair,
+    ) -> Result<(), AppConfigError<KeyPairingError>> {
         self.root.key_pairs.recovery = Some(value.to_hex_key_pair());
-        self.write().unwrap();
+        self.write()
     }
 
     pub fn load_encrypt_key_pair(&self) -> Option<X25519KeyPair> {
         load_key_pair(&self.root.key_pairs.encrypt)
     }
 
-    pub fn save_encrypt_key_pair(&mut self, value: &X25519KeyPair) {
+    pub fn save_encrypt_key_pair(
+        &mut self,
+        value: &X25519KeyPair,
+    ) -> Result<(), AppConfigError<KeyPairingError>> {
         self.root.key_pairs.encrypt = Some(value.to_hex_key_pair());
-        self.write().unwrap();
+        self.write()
     }
 
     pub fn get_did(&self) -> Option<String> {
-        self.root.did.clone()
+        self.get_did_by_alias(DEFAULT_DID_ALIAS)
+    }
+
+    pub fn save_did(&mut self, value: &str) -> Result<(), AppConfigError<KeyPairingError>> {
+        self.save_did_with_alias(DEFAULT_DID_ALIAS, value)
+    }
+
+    pub fn get_did_by_alias(&self, alias: &str) -> Option<String> {
+        self.root.did_by_alias(alias)
     }
 
-    pub fn save_did(&mut self, value: &str) {
-        self.root.did = Some(value.to_string());
-        self.write().unwrap_log()
+    pub fn save_did_with_alias(
+        &mut self,
+        alias: &str,
+        did: &str,
+    ) -> Result<(), AppConfigError<KeyPairingError>> {
+        self.root.set_did_with_alias(alias, did);
+        self.write()
     }
 
     pub fn get_didcomm_body_size(&self) -> usize {
         self.root.didcomm.http_body_size_limit
     }
 
+    pub fn get_verify_policy(&self) -> VerifyPolicy {
+        self.root.verify_policy.clone()
+    }
+
     pub fn get_metric_collect_interval(&self) -> u64 {
         let collect_interval = self.root.metrics.clone().collect_interval;
         if !(5..=300).contains(&collect_interval) {
@@ -316,22 +520,77 @@ impl AppConfig {
         cache_capacity
     }
 
+    pub fn get_metric_per_interface(&self) -> bool {
+        self.root.metrics.per_interface
+    }
+
     #[allow(dead_code)]
     pub fn get_is_initialized(&self) -> bool {
         self.root.is_initialized
     }
 
-    pub fn save_is_initialized(&mut self, value: bool) {
+    pub fn save_is_initialized(
+        &mut self,
+        value: bool,
+    ) -> Result<(), AppConfigError<KeyPairingError>> {
         self.root.is_initialized = value;
-        self.write().unwrap_log()
+        self.write()
     }
 }
 
 #[derive(Debug)]
 pub struct ServerConfig {
-    did_http_endpoint: String,
-    did_attachment_link: String,
-    studio_http_endpoint: String,
+    did_http_endpoint: Url,
+    did_attachment_link: Url,
+    studio_http_endpoint: Url,
+    environment: Environment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    Dev,
+    Staging,
+    Prod,
+    Custom,
+}
+
+impl std::fmt::Display for Environment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Environment::Dev => write!(f, "dev"),
+            Environment::Staging => write!(f, "staging"),
+            Environment::Prod => write!(f, "prod"),
+            Environment::Custom => write!(f, "custom"),
+        }
+    }
+}
+
+impl std::str::FromStr for Environment {
+    type Err = ServerConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dev" => Ok(Environment::Dev),
+            "staging" => Ok(Environment::Staging),
+            "prod" => Ok(Environment::Prod),
+            "custom" => Ok(Environment::Custom),
+            other => Err(ServerConfigError::InvalidEnvironment(other.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ServerConfigError {
+    #[error("invalid url for {name}: {value}")]
+    InvalidUrl {
+        name: &'static str,
+        value: String,
+        #[source]
+        source: url::ParseError,
+    },
+    #[error("invalid NODEX_ENVIRONMENT: {0} (expected dev, staging, prod or custom)")]
+    InvalidEnvironment(String),
 }
 
 impl Default for ServerConfig {
@@ -342,27 +601,59 @@ impl Default for ServerConfig {
 
 impl ServerConfig {
     pub fn new() -> ServerConfig {
+        Self::try_new().unwrap_log()
+    }
+
+    pub fn try_new() -> Result<ServerConfig, ServerConfigError> {
         let did_endpoint =
             env::var("NODEX_DID_HTTP_ENDPOINT").unwrap_or("https://did.nodecross.io".to_string());
         let link =
             env::var("NODEX_DID_ATTACHMENT_LINK").unwrap_or("https://did.getnodex.io".to_string());
         let studio_endpoint = env::var("NODEX_STUDIO_HTTP_ENDPOINT")
             .unwrap_or("https://http.hub.nodecross.io".to_string());
-
-        ServerConfig {
-            did_http_endpoint: did_endpoint,
-            did_attachment_link: link,
-            studio_http_endpoint: studio_endpoint,
+        let environment = env::var("NODEX_ENVIRONMENT").unwrap_or("prod".to_string());
+
+        Self::from_values(&did_endpoint, &link, &studio_endpoint, &environment)
+    }
+
+    pub fn from_values(
+        did_http_endpoint: &str,
+        did_attachment_link: &str,
+        studio_http_endpoint: &str,
+        environment: &str,
+    ) -> Result<ServerConfig, ServerConfigError> {
+        fn parse(name: &'static str, value: &str) -> Result<Url, ServerConfigError> {
+            Url::parse(value).map_err(|source| ServerConfigError::InvalidUrl {
+                name,
+                value: value.to_string(),
+                source,
+            })
         }
+
+        Ok(ServerConfig {
+            did_http_endpoint: parse("NODEX_DID_HTTP_ENDPOINT", did_http_endpoint)?,
+            did_attachment_link: parse("NODEX_DID_ATTACHMENT_LINK", did_attachment_link)?,
+            studio_http_endpoint: parse("NODEX_STUDIO_HTTP_ENDPOINT", studio_http_endpoint)?,
+            environment: environment.parse()?,
+        })
+    }
+
+    pub fn did_http_endpoint_url(&self) -> &Url {
+        &self.did_http_endpoint
     }
-    pub fn did_http_endpoint(&self) -> String {
-        self.did_http_endpoint.clone()
+    pub fn studio_http_endpoint_url(&self) -> &Url {
+        &self.studio_http_endpoint
     }
+    // NOTE: The link is embedded in messages as configured, so `Url`'s trailing
+    // slash on a bare origin is trimmed.
     pub fn did_attachment_link(&self) -> String {
-        self.did_attachment_link.clone()
+        self.did_attachment_link
+            .as_str()
+            .trim_end_matches('/')
+            .to_string()
     }
-    pub fn studio_http_endpoint(&self) -> String {
-        self.studio_http_endpoint.clone()
+    pub fn environment(&self) -> Environment {
+        self.environment
     }
 }
 
@@ -375,4 +666,196 @@ struct MetricsConfig {
     collect_interval: u64,
     send_interval: u64,
     cache_capacity: usize,
+    #[serde(default)]
+    per_interface: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::keyring::keypair::KeyPairingHex;
+
+    #[test]
+    fn test_config_root_without_additional_dids() {
+        let root: ConfigRoot = serde_json::from_str(r#"{"did": "did:nodex:test:main"}"#).unwrap();
+        assert!(root.additional_dids.is_empty());
+        assert_eq!(
+            root.did_by_alias(DEFAULT_DID_ALIAS).as_deref(),
+            Some("did:nodex:test:main")
+        );
+        assert_eq!(root.did_by_alias("sensor"), None);
+    }
+
+    #[test]
+    fn test_config_root_did_aliases() {
+        let mut root = ConfigRoot::default();
+        root.set_did_with_alias(DEFAULT_DID_ALIAS, "did:nodex:test:main");
+        root.set_did_with_alias("sensor", "did:nodex:test:sensor");
+
+        assert_eq!(root.did.as_deref(), Some("did:nodex:test:main"));
+        assert_eq!(
+            root.did_by_alias("sensor").as_deref(),
+            Some("did:nodex:test:sensor")
+        );
+
+        let json = serde_json::to_value(&root).unwrap();
+        assert_eq!(json["additional_dids"]["sensor"], "did:nodex:test:sensor");
+    }
+
+    #[test]
+    fn test_write_atomically_keeps_previous_file_on_partial_write() {
+        let dir = std::env::temp_dir().join(format!("nodex-config-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+
+        write_json_atomically(&path, &serde_json::json!({"did": "did:nodex:test:1"})).unwrap();
+
+        // NOTE: Simulate a crash after part of the next write reached the temp file.
+        fs::write(dir.join(".config.json.tmp"), b"{\"did\": \"did:no").unwrap();
+        let contents: serde_json::Value =
+            serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(contents["did"], "did:nodex:test:1");
+
+        write_json_atomically(&path, &serde_json::json!({"did": "did:nodex:test:2"})).unwrap();
+        let contents: serde_json::Value =
+            serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(contents["did"], "did:nodex:test:2");
+        assert!(!dir.join(".config.json.tmp").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_server_config_typed_accessors() {
+        let config = ServerConfig::from_values(
+            "https://did.example.com",
+            "https://attachment.example.com",
+            "https://studio.example.com:8443",
+            "staging",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.did_http_endpoint_url().host_str(),
+            Some("did.example.com")
+        );
+        assert_eq!(config.studio_http_endpoint_url().port(), Some(8443));
+        assert_eq!(
+            config.did_attachment_link(),
+            "https://attachment.example.com"
+        );
+        assert_eq!(config.environment(), Environment::Staging);
+    }
+
+    #[test]
+    fn test_server_config_rejects_non_numeric_port() {
+        let result = ServerConfig::from_values(
+            "https://did.example.com",
+            "https://attachment.example.com",
+            "https://studio.example.com:foo",
+            "prod",
+        );
+
+        match result {
+            Err(ServerConfigError::InvalidUrl { name, source, .. }) => {
+                assert_eq!(name, "NODEX_STUDIO_HTTP_ENDPOINT");
+                assert_eq!(source, url::ParseError::InvalidPort);
+            }
+            _ => panic!("non-numeric port must be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_server_config_rejects_unknown_environment() {
+        let result = ServerConfig::from_values(
+            "https://did.example.com",
+            "https://attachment.example.com",
+            "https://studio.example.com",
+            "production",
+        );
+
+        match result {
+            Err(ServerConfigError::InvalidEnvironment(value)) => assert_eq!(value, "production"),
+            _ => panic!("unknown environment must be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_server_config_rejects_invalid_url() {
+        let result = ServerConfig::from_values(
+            "https://did.example.com",
+            "not a url",
+            "https://studio.example.com",
+            "prod",
+        );
+
+        match result {
+            Err(ServerConfigError::InvalidUrl { name, value, .. }) => {
+                assert_eq!(name, "NODEX_DID_ATTACHMENT_LINK");
+                assert_eq!(value, "not a url");
+            }
+            Ok(_) => panic!("invalid url must be rejected"),
+        }
+    }
+
+    fn test_identity() -> Identity {
+        let keyring = KeyPairingHex::from(&KeyPairing::create_keyring(OsRng));
+        Identity {
+            did: Some("did:nodex:test:main".to_string()),
+            additional_dids: HashMap::from([(
+                "sensor".to_string(),
+                "did:nodex:test:sensor".to_string(),
+            )]),
+            key_pairs: KeyPairsConfig {
+                sign: Some(keyring.sign.clone()),
+                update: Some(keyring.update.clone()),
+                recovery: Some(keyring.recovery.clone()),
+                encrypt: Some(keyring.encrypt.clone()),
+            },
+        }
+    }
+
+    #[test]
+    fn test_identity_round_trip() {
+        let identity = test_identity();
+        let blob = seal_identity(&identity, "correct horse").unwrap();
+
+        let opened = open_identity(&blob, "correct horse").unwrap();
+        assert_eq!(opened.did, identity.did);
+        assert_eq!(opened.additional_dids, identity.additional_dids);
+        assert_eq!(
+            serde_json::to_value(&opened.key_pairs).unwrap(),
+            serde_json::to_value(&identity.key_pairs).unwrap()
+        );
+        assert_eq!(
+            opened.keyring().unwrap().sign.get_public_key(),
+            identity.keyring().unwrap().sign.get_public_key()
+        );
+    }
+
+    #[test]
+    fn test_identity_wrong_password_fails() {
+        let blob = seal_identity(&test_identity(), "correct horse").unwrap();
+
+        assert!(matches!(
+            open_identity(&blob, "battery staple"),
+            Err(IdentityError::Decrypt)
+        ));
+        assert!(matches!(
+            open_identity(&blob[..IDENTITY_MAGIC.len()], "correct horse"),
+            Err(IdentityError::InvalidFormat)
+        ));
+    }
+
+    #[test]
+    fn test_identity_requires_complete_keyring() {
+        let mut identity = test_identity();
+        identity.key_pairs.encrypt = None;
+        let blob = seal_identity(&identity, "correct horse").unwrap();
+
+        assert!(matches!(
+            open_identity(&blob, "correct horse"),
+            Err(IdentityError::Incomplete)
+        ));
+    }
 }
@@ -60,6 +60,12 @@ pub enum AgentErrorCode {
     VerifyVerifiableMessageNotAddressedToMe = 2002,
     #[error("Forbidden")]
     MessageActivityForbidden = 2003,
+    #[error("message does not satisfy the verify policy")]
+    VerifyDidcommMessagePolicyViolation = 2004,
+    #[error("message does not satisfy the verify policy")]
+    VerifyVerifiableMessagePolicyViolation = 2005,
+    #[error("this message has expired")]
+    VerifyVerifiableMessageExpired = 2006,
 
     #[error("verify failed")]
     CreateDidCommMessageVerifyFailed = 3001,
@@ -110,6 +116,8 @@ pub enum AgentErrorCode {
 
     #[error("it have already been verified")]
     MessageActivityConflict = 6001,
+    #[error("an update is already in progress")]
+    VersionUpdateInProgress = 6002,
 }
 
 impl From<AgentErrorCode> for StatusCode {
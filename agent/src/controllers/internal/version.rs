@@ -1,4 +1,7 @@
-use crate::{controllers::errors::AgentErrorCode, services::nodex::NodeX};
+use crate::{
+    controllers::errors::AgentErrorCode,
+    services::nodex::{NodeX, UpdateVersionError},
+};
 use axum::extract::Json;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -23,9 +26,42 @@ pub async fn handler_update(
     let nodex = NodeX::new();
     match nodex.update_version(binary_url).await {
         Ok(_) => Ok(Json("ok")),
+        Err(UpdateVersionError::InProgress) => Err(AgentErrorCode::VersionUpdateInProgress)?,
         Err(e) => {
             log::error!("{}", e);
             Err(AgentErrorCode::VersionInternal)?
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::nodex::UpdateGuard;
+
+    // NOTE: The guard is process-wide, so all scenarios run in a single test
+    // to avoid racing with each other.
+    #[tokio::test]
+    async fn test_update_guard() {
+        let first = UpdateGuard::try_acquire();
+        assert!(first.is_some());
+        assert!(UpdateGuard::try_acquire().is_none());
+
+        let json = MessageContainer {
+            message: serde_json::json!({ "binary_url": "https://example.com/nodex.zip" }),
+        };
+        let result = handler_update(Json(json)).await;
+        assert!(matches!(
+            result,
+            Err(AgentErrorCode::VersionUpdateInProgress)
+        ));
+        let result = NodeX::new()
+            .update_version("https://example.com/nodex.zip")
+            .await;
+        assert!(matches!(result, Err(UpdateVersionError::InProgress)));
+
+        drop(first);
+        let second = UpdateGuard::try_acquire();
+        assert!(second.is_some());
+    }
+}
@@ -0,0 +1,7 @@
+use axum::extract::Json;
+use serde_json::Value;
+
+// NOTE: GET /health
+pub async fn handler() -> Json<Value> {
+    Json(serde_json::json!({ "status": "ok", "version": env!("CARGO_PKG_VERSION") }))
+}
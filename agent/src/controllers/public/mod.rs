@@ -1,3 +1,4 @@
+pub mod health;
 pub mod nodex_create_didcomm_message;
 pub mod nodex_create_identifier;
 pub mod nodex_create_verifiable_message;
@@ -50,6 +50,10 @@ pub async fn handler(Json(json): Json<MessageContainer>) -> Result<String, Agent
                 log::warn!("cannot find public key: {}", e);
                 Err(AgentErrorCode::CreateDidCommMessageNoPubKey)?
             }
+            U::Keyring(e) => {
+                log::error!("failed to load keyring: {}", e);
+                Err(AgentErrorCode::CreateDidcommMessageInternal)?
+            }
             U::Json(e) | U::ServiceGenerate(S::Json(e)) => {
                 log::warn!("json error: {}", e);
                 Err(AgentErrorCode::CreateDidcommMessageInternal)?
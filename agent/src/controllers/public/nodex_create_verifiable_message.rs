@@ -7,6 +7,7 @@ use crate::{
 };
 use axum::extract::Json;
 use chrono::Utc;
+use protocol::verifiable_credentials::{jws::JwsDecodeError, types::VerifiableCredentials};
 use serde::{Deserialize, Serialize};
 
 // NOTE: POST /create-verifiable-message
@@ -18,6 +19,11 @@ pub struct MessageContainer {
     message: String,
     #[serde(default)]
     operation_tag: String,
+    #[serde(default)]
+    verbose_proof: bool,
+    // NOTE: When set, the message is rejected by `verify` this many seconds after creation.
+    #[serde(default)]
+    ttl_seconds: Option<u32>,
 }
 
 pub async fn handler(Json(json): Json<MessageContainer>) -> Result<String, AgentErrorCode> {
@@ -37,9 +43,20 @@ pub async fn handler(Json(json): Json<MessageContainer>) -> Result<String, Agent
         VerifiableMessageUseCase::new(Studio::new(), repo.clone(), DidAccessorImpl {}, repo);
 
     match usecase
-        .generate(json.destination_did, json.message, json.operation_tag, now)
+        .generate(
+            json.destination_did,
+            json.message,
+            json.operation_tag,
+            now,
+            json.ttl_seconds
+                .map(|ttl| chrono::Duration::seconds(ttl.into())),
+        )
         .await
     {
+        Ok(v) if json.verbose_proof => expand_proof_details(&v).map_err(|e| {
+            log::error!("{:?}", e);
+            AgentErrorCode::CreateVerifiableMessageInternal
+        }),
         Ok(v) => Ok(v),
         Err(e) => match e {
             U::MessageActivity(e) => Err(utils::handle_status(e)),
@@ -53,6 +70,10 @@ pub async fn handler(Json(json): Json<MessageContainer>) -> Result<String, Agent
                 log::error!("{:?}", e);
                 Err(AgentErrorCode::CreateVerifiableMessageInternal)?
             }
+            U::Keyring(e) => {
+                log::error!("failed to load keyring: {}", e);
+                Err(AgentErrorCode::CreateVerifiableMessageInternal)?
+            }
             U::Json(e) => {
                 log::warn!("json error: {}", e);
                 Err(AgentErrorCode::CreateVerifiableMessageInternal)?
@@ -60,3 +81,8 @@ pub async fn handler(Json(json): Json<MessageContainer>) -> Result<String, Agent
         },
     }
 }
+
+fn expand_proof_details(vc: &str) -> Result<String, JwsDecodeError> {
+    let vc = serde_json::from_str::<VerifiableCredentials>(vc)?;
+    Ok(serde_json::to_string(&vc.to_value_with_proof_details()?)?)
+}
@@ -1,7 +1,7 @@
 use crate::nodex::utils::did_accessor::{DidAccessor, DidAccessorImpl};
-use crate::services::nodex::NodeX;
+use crate::services::nodex::{validate_binary_url, NodeX, UpdateVersionError};
 use crate::services::studio::{MessageResponse, Studio};
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 use controller::validator::network::can_connect_to_download_server;
 use protocol::didcomm::encrypted::DidCommEncryptedService;
 use serde::{Deserialize, Serialize};
@@ -62,9 +62,12 @@ impl MessageReceiveUsecase {
                 Err(e) => return self.handle_invalid_json(&m, e).await,
             };
             log::info!("Receive message. message_id = {:?}", m.id);
+            let my_keyring = DidAccessorImpl {}
+                .get_my_keyring()
+                .context("failed to load keyring")?;
             match DidCommEncryptedService::verify(
                 self.agent.did_repository(),
-                &DidAccessorImpl {}.get_my_keyring(),
+                &my_keyring,
                 &json_message,
             )
             .await
@@ -89,13 +92,18 @@ impl MessageReceiveUsecase {
                                 if !can_connect_to_download_server("https://github.com").await {
                                     log::error!("Not connected to the Internet");
                                     anyhow::bail!("Not connected to the Internet");
-                                } else if !binary_url.starts_with(
-                                    "https://github.com/nodecross/nodex/releases/download/",
-                                ) {
-                                    log::error!("Invalid url");
-                                    anyhow::bail!("Invalid url");
+                                } else if let Err(e) = validate_binary_url(binary_url) {
+                                    log::error!("Invalid url: {}", e);
+                                    return Err(e);
+                                }
+                                match self.agent.update_version(binary_url).await {
+                                    Err(UpdateVersionError::InProgress) => {
+                                        log::warn!(
+                                            "Skipping UpdateAgent: an update is already in progress"
+                                        );
+                                    }
+                                    result => result?,
                                 }
-                                self.agent.update_version(binary_url).await?;
                             }
                             Ok(OperationType::UpdateNetworkJson) => {
                                 self.studio.network().await?;
@@ -1,4 +1,5 @@
 use super::utils;
+use crate::app_config;
 use crate::controllers::errors::AgentErrorCode;
 use crate::nodex::utils::did_accessor::DidAccessorImpl;
 use crate::{
@@ -26,8 +27,10 @@ pub async fn handler(
 ) -> Result<Json<VerifiableCredentials>, AgentErrorCode> {
     let now = Utc::now();
 
+    let verify_policy = app_config().lock().get_verify_policy();
     let usecase =
-        DidcommMessageUseCase::new(Studio::new(), utils::did_repository(), DidAccessorImpl {});
+        DidcommMessageUseCase::new(Studio::new(), utils::did_repository(), DidAccessorImpl {})
+            .with_verify_policy(verify_policy);
 
     match serde_json::from_str::<DidCommMessage>(&json.message) {
         Err(e) => {
@@ -42,6 +45,10 @@ pub async fn handler(
                     log::warn!("this message is not addressed to me: {}", e);
                     Err(AgentErrorCode::VerifyDidcommMessageNotAddressedToMe)?
                 }
+                U::PolicyViolation(e) => {
+                    log::warn!("verify policy violation: {}", e);
+                    Err(AgentErrorCode::VerifyDidcommMessagePolicyViolation)?
+                }
                 U::ServiceVerify(S::FindSender(e)) => {
                     log::warn!("cannot find sender: {}", e);
                     Err(AgentErrorCode::VerifyDidcommMessageNoSender)?
@@ -63,6 +70,10 @@ pub async fn handler(
                     log::warn!("target DID not found. DID = {}", target);
                     Err(AgentErrorCode::VerifyDidcommMessageNoTargetDid)?
                 }
+                U::Keyring(e) => {
+                    log::error!("failed to load keyring: {}", e);
+                    Err(AgentErrorCode::VerifyDidcommMessageInternal)?
+                }
                 U::Json(e) | U::ServiceVerify(S::Json(e)) => {
                     log::warn!("json error: {}", e);
                     Err(AgentErrorCode::VerifyDidcommMessageInternal)?
@@ -1,4 +1,5 @@
 use super::utils;
+use crate::app_config;
 use crate::controllers::errors::AgentErrorCode;
 use crate::nodex::utils::did_accessor::DidAccessorImpl;
 use crate::usecase::verifiable_message_usecase::VerifyVerifiableMessageUseCaseError as U;
@@ -24,8 +25,10 @@ pub async fn handler(
     let now = Utc::now();
 
     let repo = utils::did_repository();
+    let verify_policy = app_config().lock().get_verify_policy();
     let usecase =
-        VerifiableMessageUseCase::new(Studio::new(), repo.clone(), DidAccessorImpl {}, repo);
+        VerifiableMessageUseCase::new(Studio::new(), repo.clone(), DidAccessorImpl {}, repo)
+            .with_verify_policy(verify_policy);
 
     match serde_json::from_str::<VerifiableCredentials>(&json.message) {
         Err(e) => {
@@ -40,6 +43,10 @@ pub async fn handler(
                     log::warn!("verify failed: {}", e);
                     Err(AgentErrorCode::VerifyVerifiableMessageVerifyFailed)?
                 }
+                U::DidVcServiceVerify(S::NoVerifyingKey(did)) => {
+                    log::warn!("no key of {} verifies the message", did);
+                    Err(AgentErrorCode::VerifyVerifiableMessageVerifyFailed)?
+                }
                 U::DidVcServiceVerify(S::FindIdentifier(e)) => {
                     log::warn!("find identifier error: {}", e);
                     Err(AgentErrorCode::VerifyVerifiableMessageNoIdentifier)?
@@ -48,10 +55,22 @@ pub async fn handler(
                     log::warn!("target DID not found. DID = {}", target);
                     Err(AgentErrorCode::VerifyVerifiableMessageNoTargetDid)?
                 }
+                U::VerificationFailed => {
+                    log::warn!("verify failed: {}", e);
+                    Err(AgentErrorCode::VerifyVerifiableMessageVerifyFailed)?
+                }
+                U::Expired => {
+                    log::warn!("this message has expired: {}", e);
+                    Err(AgentErrorCode::VerifyVerifiableMessageExpired)?
+                }
                 U::NotAddressedToMe => {
                     log::warn!("this message is not addressed to me: {}", e);
                     Err(AgentErrorCode::VerifyVerifiableMessageNotAddressedToMe)?
                 }
+                U::PolicyViolation(e) => {
+                    log::warn!("verify policy violation: {}", e);
+                    Err(AgentErrorCode::VerifyVerifiableMessagePolicyViolation)?
+                }
                 U::Json(e) => {
                     log::warn!("json error: {}", e);
                     Err(AgentErrorCode::VerifyVerifiableMessageInternal)?
@@ -2,13 +2,15 @@ use crate::controllers::public::nodex_receive;
 use cli::AgentCommands;
 use dotenvy::dotenv;
 use mac_address::get_mac_address;
+use nodex::extension::secure_keystore::{KeyStore, SecureKeyStore, SecureKeyStoreKey};
 use nodex::utils::UnwrapLog;
 use services::metrics::{MetricsInMemoryCacheService, MetricsWatchService};
 use services::nodex::NodeX;
 use services::studio::Studio;
+use shutdown::ShutdownCoordinator;
 use std::env;
 use std::fs;
-use tokio::task::JoinSet;
+use std::time::Duration;
 use tokio_util::sync::CancellationToken;
 use usecase::metric_usecase::MetricUsecase;
 pub mod cli;
@@ -19,15 +21,25 @@ mod nodex;
 mod repository;
 mod server;
 mod services;
+mod shutdown;
 mod usecase;
 pub use crate::config::app_config;
 pub use crate::config::server_config;
 pub use crate::network::network_config;
 
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+const IDENTITY_PASSWORD_ENV: &str = "NODEX_IDENTITY_PASSWORD";
+
 #[tokio::main]
 pub async fn run(controlled: bool, options: &cli::AgentOptions) -> std::io::Result<()> {
     dotenv().ok();
 
+    // NOTE: Surface invalid endpoint settings before anything calls server_config().
+    if let Err(e) = config::ServerConfig::try_new() {
+        log::error!("{}", e);
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, e));
+    }
+
     #[cfg(windows)]
     server::windows::kill_other_self_process();
 
@@ -43,47 +55,55 @@ pub async fn run(controlled: bool, options: &cli::AgentOptions) -> std::io::Resu
 
     fs::create_dir_all(&logs_dir).unwrap_log();
 
+    // NOTE: Identity commands replace the DID, so they must not create one first.
+    if let (true, Some(AgentCommands::Identity { command })) =
+        (options.config, options.command.as_ref())
+    {
+        return use_identity_cli(command);
+    }
+
     // NOTE: generate Key Chain
     let node_x = NodeX::new();
     let device_did = node_x.create_identifier().await.unwrap();
 
     if options.config {
-        use_cli(options.command.as_ref(), device_did.did_document.id.clone());
-        return Ok(());
+        return use_cli(options.command.as_ref(), device_did.did_document.id.clone());
     }
 
     studio_initialize(device_did.did_document.id.clone()).await;
     send_device_info().await;
 
-    let shutdown_token = CancellationToken::new();
-    let mut tasks = JoinSet::new();
+    let mut coordinator = ShutdownCoordinator::new();
 
     let cache_repository =
         MetricsInMemoryCacheService::new(app_config().lock().get_metric_cache_capacity());
     let cache_repository_cloned = cache_repository.clone();
-    let shutdown_token_cloned = shutdown_token.clone();
-    tasks.spawn(async move {
+    let collection_finished = CancellationToken::new();
+    let collection_finished_cloned = collection_finished.clone();
+    coordinator.spawn("metrics collector", |token| async move {
+        let per_interface = app_config().lock().get_metric_per_interface();
         let mut metric_usecase = MetricUsecase::new(
             Studio::new(),
-            MetricsWatchService::new(),
+            MetricsWatchService::new().with_per_interface(per_interface),
             app_config(),
             cache_repository_cloned,
-            shutdown_token_cloned,
+            token,
+            collection_finished_cloned,
         );
         metric_usecase.collect_task().await
     });
-    let shutdown_token_cloned = shutdown_token.clone();
-    tasks.spawn(async move {
+    coordinator.spawn("metrics sender", |token| async move {
         let mut metric_usecase = MetricUsecase::new(
             Studio::new(),
             MetricsWatchService::new(),
             app_config(),
             cache_repository,
-            shutdown_token_cloned,
+            token,
+            collection_finished,
         );
         metric_usecase.send_task().await
     });
-    tasks.spawn(nodex_receive::polling_task(shutdown_token.clone()));
+    coordinator.spawn("message receiver", nodex_receive::polling_task);
 
     // NOTE: booting...
     #[cfg(unix)]
@@ -98,10 +118,16 @@ pub async fn run(controlled: bool, options: &cli::AgentOptions) -> std::io::Resu
             server::unix::recieve_listener(&nodex_path)?
         };
         let fd = std::os::unix::io::AsRawFd::as_raw_fd(&listener);
-        let server = server::unix::make_uds_server(server::make_router(), listener);
         let server =
-            server::unix::wrap_with_signal_handler(server, shutdown_token, fd, &nodex_path);
-        let (server, _) = tokio::join!(server.join_all(), tasks.join_all());
+            server::unix::make_uds_server(server::make_router(), listener, coordinator.token());
+        let server = server::unix::wrap_with_signal_handler(
+            server,
+            coordinator.token(),
+            fd,
+            &nodex_path,
+            SHUTDOWN_TIMEOUT,
+        );
+        let (server, _) = tokio::join!(server.join_all(), coordinator.join(SHUTDOWN_TIMEOUT));
         server.into_iter().collect::<Result<Vec<()>, _>>()?;
     };
 
@@ -112,12 +138,12 @@ pub async fn run(controlled: bool, options: &cli::AgentOptions) -> std::io::Resu
         let port = server::windows::validate_port(&port_str).expect("Invalid port number.");
         let router = server::make_router();
         let server = server::windows::new_web_server(port, router).await?;
-        let _ = tokio::join!(server, tasks.join_all());
+        let _ = tokio::join!(server, coordinator.join(SHUTDOWN_TIMEOUT));
     };
     Ok(())
 }
 
-fn use_cli(command: Option<&AgentCommands>, did: String) {
+fn use_cli(command: Option<&AgentCommands>, did: String) -> std::io::Result<()> {
     let network_config = crate::network_config();
     let mut network_config = network_config.lock();
     const SECRET_KEY: &str = "secret_key";
@@ -131,10 +157,16 @@ fn use_cli(command: Option<&AgentCommands>, did: String) {
             AgentCommands::Network { command } => match command {
                 cli::NetworkSubCommands::Set { key, value } => match key.as_str() {
                     SECRET_KEY => {
+                        network::validate_secret_key(value).map_err(|e| {
+                            std::io::Error::new(std::io::ErrorKind::InvalidInput, e)
+                        })?;
                         network_config.save_secret_key(value);
                         log::info!("Network {} is set", SECRET_KEY);
                     }
                     PROJECT_DID => {
+                        network::validate_project_did(value).map_err(|e| {
+                            std::io::Error::new(std::io::ErrorKind::InvalidInput, e)
+                        })?;
                         network_config.save_project_did(value);
                         log::info!("Network {} is set", PROJECT_DID);
                     }
@@ -145,15 +177,15 @@ fn use_cli(command: Option<&AgentCommands>, did: String) {
                 cli::NetworkSubCommands::Get { key } => match key.as_str() {
                     SECRET_KEY => {
                         if let Some(v) = network_config.get_secret_key() {
-                            println!("Network {}: {}", SECRET_KEY, v);
-                            return;
+                            println!("{}", v);
+                            return Ok(());
                         };
                         log::info!("Network {} is not set", SECRET_KEY);
                     }
                     PROJECT_DID => {
                         if let Some(v) = network_config.get_project_did() {
-                            log::info!("Network {}: {}", PROJECT_DID, v);
-                            return;
+                            println!("{}", v);
+                            return Ok(());
                         };
                         log::info!("Network {} is not set", PROJECT_DID);
                     }
@@ -161,9 +193,79 @@ fn use_cli(command: Option<&AgentCommands>, did: String) {
                         log::info!("key is not found");
                     }
                 },
+                cli::NetworkSubCommands::Unset { key } => {
+                    let removed = match key.as_str() {
+                        SECRET_KEY => network_config.remove_secret_key(),
+                        PROJECT_DID => network_config.remove_project_did(),
+                        _ => {
+                            log::info!("key is not found");
+                            return Err(std::io::Error::new(
+                                std::io::ErrorKind::InvalidInput,
+                                format!("unknown network key: {}", key),
+                            ));
+                        }
+                    };
+                    if removed.is_none() {
+                        log::info!("Network {} is not set", key);
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::NotFound,
+                            format!("network {} is not set", key),
+                        ));
+                    }
+                    log::info!("Network {} is unset", key);
+                }
             },
+            AgentCommands::Identity { command } => use_identity_cli(command)?,
         }
     }
+    Ok(())
+}
+
+fn use_identity_cli(command: &cli::IdentitySubCommands) -> std::io::Result<()> {
+    let password = env::var(IDENTITY_PASSWORD_ENV).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{} is not set", IDENTITY_PASSWORD_ENV),
+        )
+    })?;
+    match command {
+        cli::IdentitySubCommands::Export { output } => {
+            let blob = app_config()
+                .lock()
+                .export_identity(&password)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            fs::write(output, blob)?;
+            log::info!("Identity exported to {}", output.display());
+        }
+        cli::IdentitySubCommands::Import { input } => {
+            let blob = fs::read(input)?;
+            let identity = config::open_identity(&blob, &password)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            let keyring = identity
+                .keyring()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            // NOTE: Keys go through the keystore so that a keystore extension receives them,
+            // and are stored before the DID so a failure never pairs it with the old keys.
+            let keystore = KeyStore::from_config(app_config())
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            for key in [
+                SecureKeyStoreKey::Sign(&keyring.sign),
+                SecureKeyStoreKey::Update(&keyring.update),
+                SecureKeyStoreKey::Recovery(&keyring.recovery),
+                SecureKeyStoreKey::Encrypt(&keyring.encrypt),
+            ] {
+                keystore
+                    .write(&key)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            }
+            app_config()
+                .lock()
+                .import_identity(identity)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            log::info!("Identity imported from {}", input.display());
+        }
+    }
+    Ok(())
 }
 
 async fn studio_initialize(my_did: String) {
@@ -208,6 +310,7 @@ async fn send_device_info() {
             mac_address,
             VERSION.to_string(),
             OS.to_string(),
+            server_config().environment(),
         )
         .await
         .unwrap_log();
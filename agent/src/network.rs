@@ -4,12 +4,40 @@ use serde::Serialize;
 use std::fs::OpenOptions;
 use std::io;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::{fs, sync::MutexGuard};
 
 use std::sync::{Arc, Mutex, Once};
 
 use crate::nodex::utils::UnwrapLog;
+use protocol::did_webvh::domain::did::Did;
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum NetworkValueError {
+    #[error("{0} must not be empty")]
+    Empty(&'static str),
+    #[error("project_did is not a valid DID (expected did:<method>:<id>): {0}")]
+    InvalidDid(String),
+}
+
+// NOTE: Checks values given through `--config network set` before they are saved,
+// so a typo does not surface later as an opaque resolution or auth failure.
+pub fn validate_secret_key(value: &str) -> Result<(), NetworkValueError> {
+    if value.trim().is_empty() {
+        return Err(NetworkValueError::Empty("secret_key"));
+    }
+    Ok(())
+}
+
+pub fn validate_project_did(value: &str) -> Result<(), NetworkValueError> {
+    if value.trim().is_empty() {
+        return Err(NetworkValueError::Empty("project_did"));
+    }
+    value
+        .parse::<Did>()
+        .map_err(|_| NetworkValueError::InvalidDid(value.to_string()))?;
+    Ok(())
+}
 
 #[derive(Clone)]
 pub struct SingletonNetworkConfig {
@@ -53,7 +81,7 @@ struct ConfigNetwork {
 
 #[derive(Debug)]
 pub struct Network {
-    config: HomeConfig,
+    path: PathBuf,
     root: ConfigNetwork,
 }
 
@@ -73,19 +101,24 @@ impl Network {
 
     fn new() -> Self {
         let config = HomeConfig::with_config_dir(Network::APP_NAME, Network::CONFIG_FILE);
-        let config_dir = config.path().parent().expect("unreachable");
+        Self::with_path(config.path().to_path_buf())
+    }
+
+    fn with_path(path: PathBuf) -> Self {
+        let config_dir = path.parent().expect("unreachable");
 
-        if !Path::exists(config.path()) {
+        if !Path::exists(&path) {
             fs::create_dir_all(config_dir).unwrap_log();
-            Self::touch(config.path()).unwrap_log();
+            Self::touch(&path).unwrap_log();
         }
-        let root = config.json::<ConfigNetwork>().unwrap_log();
+        let contents = fs::read_to_string(&path).unwrap_log();
+        let root = serde_json::from_str::<ConfigNetwork>(&contents).unwrap_log();
 
-        Network { config, root }
+        Network { path, root }
     }
 
     pub fn write(&self) {
-        self.config.save_json(&self.root).unwrap_log();
+        crate::config::write_json_atomically(&self.path, &self.root).unwrap_log();
     }
 
     // NOTE: secret key
@@ -98,6 +131,13 @@ impl Network {
         self.write();
     }
 
+    // NOTE: Returns the removed value, or None if it was not set.
+    pub fn remove_secret_key(&mut self) -> Option<String> {
+        let removed = self.root.secret_key.take()?;
+        self.write();
+        Some(removed)
+    }
+
     // NOTE: project_did
     pub fn get_project_did(&self) -> Option<String> {
         self.root.project_did.clone()
@@ -108,6 +148,12 @@ impl Network {
         self.write();
     }
 
+    pub fn remove_project_did(&mut self) -> Option<String> {
+        let removed = self.root.project_did.take()?;
+        self.write();
+        Some(removed)
+    }
+
     // NOTE: recipient_dids
     pub fn get_recipient_dids(&self) -> Option<Vec<String>> {
         self.root.recipient_dids.clone()
@@ -139,3 +185,89 @@ impl Network {
         self.write();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_project_did() {
+        assert!(validate_project_did(
+            "did:nodex:test:EiBprXreMiba4loyl3psXm0RsECdtlCiQIjM8G9BtdQplA"
+        )
+        .is_ok());
+        assert!(validate_project_did("did:webvh:QmScid:example.com").is_ok());
+        assert_eq!(
+            validate_project_did(""),
+            Err(NetworkValueError::Empty("project_did"))
+        );
+        for invalid in [
+            "nodex:test:abc",
+            "did:nodex",
+            "did::abc",
+            "did:nodex:abc def",
+        ] {
+            assert_eq!(
+                validate_project_did(invalid),
+                Err(NetworkValueError::InvalidDid(invalid.to_string())),
+                "{} should be rejected",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_secret_key() {
+        assert!(validate_secret_key("secret").is_ok());
+        assert_eq!(
+            validate_secret_key("  "),
+            Err(NetworkValueError::Empty("secret_key"))
+        );
+    }
+
+    fn read_network_file(path: &Path) -> serde_json::Value {
+        serde_json::from_slice(&fs::read(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_remove_secret_key() {
+        let dir = std::env::temp_dir().join(format!("nodex-network-test-{}", std::process::id()));
+        let path = dir.join("secret_key").join(Network::CONFIG_FILE);
+        let mut network = Network::with_path(path.clone());
+
+        network.save_secret_key("secret");
+        assert_eq!(read_network_file(&path)["secret_key"], "secret");
+
+        assert_eq!(network.remove_secret_key().as_deref(), Some("secret"));
+        assert_eq!(network.get_secret_key(), None);
+        assert!(read_network_file(&path)["secret_key"].is_null());
+        assert_eq!(Network::with_path(path.clone()).get_secret_key(), None);
+
+        assert_eq!(network.remove_secret_key(), None);
+
+        fs::remove_dir_all(dir.join("secret_key")).unwrap();
+    }
+
+    #[test]
+    fn test_remove_project_did() {
+        let dir = std::env::temp_dir().join(format!("nodex-network-test-{}", std::process::id()));
+        let path = dir.join("project_did").join(Network::CONFIG_FILE);
+        let mut network = Network::with_path(path.clone());
+        let project_did = "did:nodex:test:EiBprXreMiba4loyl3psXm0RsECdtlCiQIjM8G9BtdQplA";
+
+        network.save_secret_key("secret");
+        network.save_project_did(project_did);
+        assert_eq!(read_network_file(&path)["project_did"], project_did);
+
+        assert_eq!(network.remove_project_did().as_deref(), Some(project_did));
+        assert_eq!(network.get_project_did(), None);
+        let contents = read_network_file(&path);
+        assert!(contents["project_did"].is_null());
+        assert_eq!(contents["secret_key"], "secret");
+        assert_eq!(Network::with_path(path.clone()).get_project_did(), None);
+
+        assert_eq!(network.remove_project_did(), None);
+
+        fs::remove_dir_all(dir.join("project_did")).unwrap();
+    }
+}
@@ -1 +1,29 @@
 pub mod secure_keystore;
+pub mod trng;
+
+use crate::config::Extension;
+use libloading::Library;
+use std::sync::Arc;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExtensionError {
+    #[error("failed to load extension library '{0}': {1}")]
+    LoadFailed(String, #[source] libloading::Error),
+    #[error("symbol '{0}' not found in extension library '{1}': {2}")]
+    SymbolNotFound(String, String, #[source] libloading::Error),
+}
+
+// NOTE: The returned function pointer is only valid while the library is loaded,
+// so callers must keep the `Arc<Library>` alongside it.
+fn load_symbol<T: Copy>(extension: &Extension) -> Result<(Arc<Library>, T), ExtensionError> {
+    // SAFETY: The extension library is configured by the operator and is trusted to
+    // export `symbol` with the C signature `T` expects.
+    unsafe {
+        let library = Library::new(&extension.filename)
+            .map_err(|e| ExtensionError::LoadFailed(extension.filename.clone(), e))?;
+        let symbol = *library.get::<T>(extension.symbol.as_bytes()).map_err(|e| {
+            ExtensionError::SymbolNotFound(extension.symbol.clone(), extension.filename.clone(), e)
+        })?;
+        Ok((Arc::new(library), symbol))
+    }
+}
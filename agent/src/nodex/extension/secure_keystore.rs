@@ -1,6 +1,12 @@
-use protocol::keyring::keypair::{K256KeyPair, X25519KeyPair};
+use libloading::Library;
+use protocol::keyring::keypair::{
+    K256KeyPair, KeyPair, KeyPairHex, KeyPairingError, X25519KeyPair,
+};
+use std::io;
+use std::sync::Arc;
 
-use crate::config::SingletonAppConfig;
+use super::{load_symbol, ExtensionError};
+use crate::config::{AppConfigError, SingletonAppConfig};
 
 pub enum SecureKeyStoreKey<'a> {
     Sign(&'a K256KeyPair),
@@ -9,16 +15,18 @@ pub enum SecureKeyStoreKey<'a> {
     Encrypt(&'a X25519KeyPair),
 }
 
-#[derive(Debug)]
+// NOTE: The discriminant is the `kind` passed to keystore extensions.
+#[derive(Debug, Clone, Copy)]
+#[repr(u32)]
 pub enum SecureKeyStoreType {
-    Sign,
-    Update,
-    Recovery,
-    Encrypt,
+    Sign = 0,
+    Update = 1,
+    Recovery = 2,
+    Encrypt = 3,
 }
 
 pub trait SecureKeyStore {
-    fn write(&self, key_pair: &SecureKeyStoreKey);
+    fn write(&self, key_pair: &SecureKeyStoreKey) -> Result<(), AppConfigError<KeyPairingError>>;
     fn read_sign(&self) -> Option<K256KeyPair>;
     fn read_update(&self) -> Option<K256KeyPair>;
     fn read_recovery(&self) -> Option<K256KeyPair>;
@@ -46,7 +54,7 @@ fn k2t(k: &SecureKeyStoreKey) -> SecureKeyStoreType {
 }
 
 impl SecureKeyStore for FileBaseKeyStore {
-    fn write(&self, key_pair: &SecureKeyStoreKey) {
+    fn write(&self, key_pair: &SecureKeyStoreKey) -> Result<(), AppConfigError<KeyPairingError>> {
         log::info!("Called: write_internal (type: {:?})", k2t(key_pair));
 
         let mut config = self.config.lock();
@@ -56,7 +64,7 @@ impl SecureKeyStore for FileBaseKeyStore {
             SecureKeyStoreKey::Update(k) => config.save_update_key_pair(k),
             SecureKeyStoreKey::Recovery(k) => config.save_recovery_key_pair(k),
             SecureKeyStoreKey::Encrypt(k) => config.save_encrypt_key_pair(k),
-        };
+        }
     }
 
     fn read_sign(&self) -> Option<K256KeyPair> {
@@ -80,3 +88,271 @@ impl SecureKeyStore for FileBaseKeyStore {
         config.load_encrypt_key_pair()
     }
 }
+
+// Extension ABI:
+//   write: int32_t (uint32_t kind, const uint8_t *data, size_t len), 0 on success.
+//   read:  intptr_t (uint32_t kind, uint8_t *buf, size_t cap), the number of bytes
+//          written to `buf`, or a negative value when the key is missing or unreadable.
+// Key material is exchanged as the JSON encoded `KeyPairHex`, as stored in the config.
+type ReadFn = unsafe extern "C" fn(u32, *mut u8, usize) -> isize;
+type WriteFn = unsafe extern "C" fn(u32, *const u8, usize) -> i32;
+
+const READ_BUFFER_SIZE: usize = 4096;
+
+#[derive(Clone)]
+pub struct ExtensionKeyStore {
+    read: ReadFn,
+    write: WriteFn,
+    // Keeps the libraries loaded for as long as `read` and `write` may be called.
+    _libraries: Vec<Arc<Library>>,
+}
+
+impl ExtensionKeyStore {
+    pub fn load(
+        read: &crate::config::Extension,
+        write: &crate::config::Extension,
+    ) -> Result<Self, ExtensionError> {
+        let (read_library, read) = load_symbol::<ReadFn>(read)?;
+        let (write_library, write) = load_symbol::<WriteFn>(write)?;
+        Ok(ExtensionKeyStore {
+            read,
+            write,
+            _libraries: vec![read_library, write_library],
+        })
+    }
+
+    fn read_hex(&self, kind: SecureKeyStoreType) -> Option<KeyPairHex> {
+        log::debug!("Called: read_extension (type: {:?})", kind);
+        let mut buf = vec![0u8; READ_BUFFER_SIZE];
+        // SAFETY: `buf` is valid for `buf.len()` bytes and the library is kept loaded by `self`.
+        let len = unsafe { (self.read)(kind as u32, buf.as_mut_ptr(), buf.len()) };
+        if len < 0 || len as usize > buf.len() {
+            log::error!("Keystore extension failed to read {:?} key: {}", kind, len);
+            return None;
+        }
+        serde_json::from_slice(&buf[..len as usize])
+            .map_err(|e| {
+                log::error!(
+                    "Keystore extension returned an invalid {:?} key: {}",
+                    kind,
+                    e
+                )
+            })
+            .ok()
+    }
+
+    fn read_key_pair<U, V, T: KeyPair<U, V>>(&self, kind: SecureKeyStoreType) -> Option<T> {
+        let hex = self.read_hex(kind)?;
+        T::from_hex_key_pair(&hex)
+            .map_err(|e| log::error!("{:?}", e))
+            .ok()
+    }
+}
+
+impl SecureKeyStore for ExtensionKeyStore {
+    fn write(&self, key_pair: &SecureKeyStoreKey) -> Result<(), AppConfigError<KeyPairingError>> {
+        let kind = k2t(key_pair);
+        log::info!("Called: write_extension (type: {:?})", kind);
+
+        let hex = match key_pair {
+            SecureKeyStoreKey::Sign(k)
+            | SecureKeyStoreKey::Update(k)
+            | SecureKeyStoreKey::Recovery(k) => k.to_hex_key_pair(),
+            SecureKeyStoreKey::Encrypt(k) => k.to_hex_key_pair(),
+        };
+        let data = serde_json::to_vec(&hex)
+            .map_err(|e| AppConfigError::WriteError(io::Error::new(io::ErrorKind::Other, e)))?;
+        // SAFETY: `data` is valid for `data.len()` bytes and the library is kept loaded by `self`.
+        let status = unsafe { (self.write)(kind as u32, data.as_ptr(), data.len()) };
+        if status != 0 {
+            return Err(AppConfigError::WriteError(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "keystore extension failed to write {:?} key: {}",
+                    kind, status
+                ),
+            )));
+        }
+        Ok(())
+    }
+
+    fn read_sign(&self) -> Option<K256KeyPair> {
+        self.read_key_pair(SecureKeyStoreType::Sign)
+    }
+    fn read_update(&self) -> Option<K256KeyPair> {
+        self.read_key_pair(SecureKeyStoreType::Update)
+    }
+    fn read_recovery(&self) -> Option<K256KeyPair> {
+        self.read_key_pair(SecureKeyStoreType::Recovery)
+    }
+    fn read_encrypt(&self) -> Option<X25519KeyPair> {
+        self.read_key_pair(SecureKeyStoreType::Encrypt)
+    }
+}
+
+// NOTE: Uses the keystore extension when one is configured, otherwise the key pairs
+// stored in the config file.
+#[derive(Clone)]
+pub enum KeyStore {
+    File(FileBaseKeyStore),
+    Extension(ExtensionKeyStore),
+}
+
+impl KeyStore {
+    pub fn from_config(config: Box<SingletonAppConfig>) -> Result<Self, ExtensionError> {
+        let (read, write) = {
+            let config = config.lock();
+            (
+                config.load_secure_keystore_read_sig(),
+                config.load_secure_keystore_write_sig(),
+            )
+        };
+        match (read, write) {
+            (Some(read), Some(write)) => {
+                log::info!("Using keystore extension '{}'", read.filename);
+                Ok(KeyStore::Extension(ExtensionKeyStore::load(&read, &write)?))
+            }
+            _ => Ok(KeyStore::File(FileBaseKeyStore::new(config))),
+        }
+    }
+
+    fn inner(&self) -> &dyn SecureKeyStore {
+        match self {
+            KeyStore::File(keystore) => keystore,
+            KeyStore::Extension(keystore) => keystore,
+        }
+    }
+}
+
+impl SecureKeyStore for KeyStore {
+    fn write(&self, key_pair: &SecureKeyStoreKey) -> Result<(), AppConfigError<KeyPairingError>> {
+        self.inner().write(key_pair)
+    }
+    fn read_sign(&self) -> Option<K256KeyPair> {
+        self.inner().read_sign()
+    }
+    fn read_update(&self) -> Option<K256KeyPair> {
+        self.inner().read_update()
+    }
+    fn read_recovery(&self) -> Option<K256KeyPair> {
+        self.inner().read_recovery()
+    }
+    fn read_encrypt(&self) -> Option<X25519KeyPair> {
+        self.inner().read_encrypt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Extension;
+    use protocol::keyring::keypair::KeyPairing;
+    use protocol::rand_core::OsRng;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    // A keystore extension that keeps the key pairs in memory, built as a real shared
+    // library so the test goes through dlopen and symbol resolution.
+    const SHIM_SOURCE: &str = r#"
+        use std::collections::HashMap;
+        use std::sync::Mutex;
+
+        static STORE: Mutex<Option<HashMap<u32, Vec<u8>>>> = Mutex::new(None);
+
+        #[no_mangle]
+        pub unsafe extern "C" fn keystore_read(kind: u32, buf: *mut u8, cap: usize) -> isize {
+            let store = STORE.lock().unwrap();
+            match store.as_ref().and_then(|s| s.get(&kind)) {
+                Some(data) if data.len() <= cap => {
+                    std::ptr::copy_nonoverlapping(data.as_ptr(), buf, data.len());
+                    data.len() as isize
+                }
+                _ => -1,
+            }
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn keystore_write(kind: u32, data: *const u8, len: usize) -> i32 {
+            let data = std::slice::from_raw_parts(data, len).to_vec();
+            STORE.lock().unwrap().get_or_insert_with(HashMap::new).insert(kind, data);
+            0
+        }
+    "#;
+
+    fn build_shim_library() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("nodex-keystore-shim-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("keystore_shim.rs");
+        std::fs::write(&source, SHIM_SOURCE).unwrap();
+        let library = dir.join(format!(
+            "{}keystore_shim{}",
+            std::env::consts::DLL_PREFIX,
+            std::env::consts::DLL_SUFFIX
+        ));
+        let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let status = Command::new(rustc)
+            .args(["--edition", "2021", "--crate-type", "cdylib", "-o"])
+            .arg(&library)
+            .arg(&source)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        library
+    }
+
+    fn shim_extension(filename: &str, symbol: &str) -> Extension {
+        Extension {
+            filename: filename.to_string(),
+            symbol: symbol.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_extension_keystore_round_trip() {
+        let library = build_shim_library();
+        let filename = library.to_str().unwrap();
+        let keystore = ExtensionKeyStore::load(
+            &shim_extension(filename, "keystore_read"),
+            &shim_extension(filename, "keystore_write"),
+        )
+        .unwrap();
+        assert!(keystore.read_sign().is_none());
+
+        let keyring = KeyPairing::create_keyring(OsRng);
+        keystore
+            .write(&SecureKeyStoreKey::Sign(&keyring.sign))
+            .unwrap();
+        keystore
+            .write(&SecureKeyStoreKey::Encrypt(&keyring.encrypt))
+            .unwrap();
+
+        assert_eq!(
+            keystore.read_sign().unwrap().get_public_key(),
+            keyring.sign.get_public_key()
+        );
+        assert_eq!(
+            keystore.read_encrypt().unwrap().get_public_key(),
+            keyring.encrypt.get_public_key()
+        );
+        assert!(keystore.read_update().is_none());
+
+        let result = ExtensionKeyStore::load(
+            &shim_extension(filename, "keystore_read"),
+            &shim_extension(filename, "keystore_missing"),
+        );
+        assert!(matches!(
+            result,
+            Err(ExtensionError::SymbolNotFound(_, _, _))
+        ));
+    }
+
+    #[test]
+    fn test_extension_keystore_load_missing_library() {
+        let extension = Extension {
+            filename: "/nonexistent/libkeystore.so".to_string(),
+            symbol: "read".to_string(),
+        };
+        let result = ExtensionKeyStore::load(&extension, &extension);
+        assert!(matches!(result, Err(ExtensionError::LoadFailed(_, _))));
+    }
+}
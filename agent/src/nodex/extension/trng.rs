@@ -0,0 +1,169 @@
+use libloading::Library;
+use protocol::rand_core::{self, CryptoRng, OsRng, RngCore};
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+use super::{load_symbol, ExtensionError};
+use crate::config::Extension;
+
+// Extension ABI:
+//   read: int32_t (uint8_t *buf, size_t len), fills all `len` bytes with entropy and
+//         returns 0 on success.
+type ReadFn = unsafe extern "C" fn(*mut u8, usize) -> i32;
+
+#[derive(Clone)]
+pub struct ExtensionTrng {
+    read: ReadFn,
+    // Keeps the library loaded for as long as `read` may be called.
+    _library: Option<Arc<Library>>,
+}
+
+impl ExtensionTrng {
+    pub fn load(read: &Extension) -> Result<Self, ExtensionError> {
+        let (library, read) = load_symbol::<ReadFn>(read)?;
+        Ok(ExtensionTrng {
+            read,
+            _library: Some(library),
+        })
+    }
+}
+
+impl RngCore for ExtensionTrng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        // NOTE: Like `OsRng`, an entropy source failing is not recoverable.
+        self.try_fill_bytes(dest)
+            .expect("TRNG extension failed to provide entropy")
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        // SAFETY: `dest` is valid for `dest.len()` bytes and the library is kept loaded by `self`.
+        let status = unsafe { (self.read)(dest.as_mut_ptr(), dest.len()) };
+        if status != 0 {
+            log::error!("TRNG extension failed to read entropy: {}", status);
+            return Err(NonZeroU32::new(rand_core::Error::CUSTOM_START)
+                .unwrap()
+                .into());
+        }
+        Ok(())
+    }
+}
+
+// The extension is configured as a true random number generator.
+impl CryptoRng for ExtensionTrng {}
+
+// NOTE: Uses the TRNG extension when one is configured, otherwise `OsRng`.
+#[derive(Clone)]
+pub enum Trng {
+    Os(OsRng),
+    Extension(ExtensionTrng),
+}
+
+impl Trng {
+    pub fn new(read: Option<Extension>) -> Result<Self, ExtensionError> {
+        match read {
+            Some(read) => {
+                log::info!("Using TRNG extension '{}'", read.filename);
+                Ok(Trng::Extension(ExtensionTrng::load(&read)?))
+            }
+            None => Ok(Trng::Os(OsRng)),
+        }
+    }
+
+    fn inner(&mut self) -> &mut dyn RngCore {
+        match self {
+            Trng::Os(rng) => rng,
+            Trng::Extension(rng) => rng,
+        }
+    }
+}
+
+impl RngCore for Trng {
+    fn next_u32(&mut self) -> u32 {
+        self.inner().next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.inner().next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.inner().fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.inner().try_fill_bytes(dest)
+    }
+}
+
+impl CryptoRng for Trng {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::keyring::keypair::{KeyPair, KeyPairing};
+    use std::sync::atomic::{AtomicU8, Ordering};
+
+    static NEXT_BYTE: AtomicU8 = AtomicU8::new(0);
+
+    // In-process stand-in for a TRNG extension: yields 0, 1, 2, ...
+    unsafe extern "C" fn counting_read(buf: *mut u8, len: usize) -> i32 {
+        for b in std::slice::from_raw_parts_mut(buf, len) {
+            *b = NEXT_BYTE.fetch_add(1, Ordering::SeqCst);
+        }
+        0
+    }
+
+    unsafe extern "C" fn failing_read(_buf: *mut u8, _len: usize) -> i32 {
+        -1
+    }
+
+    #[test]
+    fn test_keys_derive_from_extension_entropy() {
+        let rng = Trng::Extension(ExtensionTrng {
+            read: counting_read,
+            _library: None,
+        });
+        let keyring = KeyPairing::create_keyring(rng);
+
+        let expected = |start: u8| -> Vec<u8> { (start..start + 32).collect() };
+        assert_eq!(
+            keyring.sign.get_secret_key().to_bytes().to_vec(),
+            expected(0)
+        );
+        assert_eq!(
+            keyring.update.get_secret_key().to_bytes().to_vec(),
+            expected(32)
+        );
+        assert_eq!(
+            keyring.recovery.get_secret_key().to_bytes().to_vec(),
+            expected(64)
+        );
+        assert_eq!(
+            keyring.encrypt.get_secret_key().as_bytes().to_vec(),
+            expected(96)
+        );
+    }
+
+    #[test]
+    fn test_extension_failure_is_reported() {
+        let mut rng = ExtensionTrng {
+            read: failing_read,
+            _library: None,
+        };
+        let mut buf = [0u8; 8];
+        assert!(rng.try_fill_bytes(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_falls_back_to_os_rng() {
+        assert!(matches!(Trng::new(None), Ok(Trng::Os(_))));
+    }
+}
@@ -1,9 +1,12 @@
 use crate::{
-    config::SingletonAppConfig,
-    nodex::extension::secure_keystore::{SecureKeyStore, SecureKeyStoreKey},
+    config::{AppConfigError, SingletonAppConfig},
+    nodex::extension::{
+        secure_keystore::{SecureKeyStore, SecureKeyStoreKey},
+        trng::Trng,
+        ExtensionError,
+    },
 };
 use protocol::keyring::keypair::{K256KeyPair, X25519KeyPair};
-use protocol::rand_core::OsRng;
 
 use thiserror::Error;
 
@@ -24,6 +27,12 @@ pub enum KeyPairingError {
     KeyNotFound,
     #[error("DID not found")]
     DIDNotFound,
+    #[error("failed to save keyring: {0}")]
+    SaveFailed(#[from] AppConfigError<protocol::keyring::keypair::KeyPairingError>),
+    #[error("failed to load TRNG extension: {0}")]
+    Trng(#[from] ExtensionError),
+    #[error("failed to load keystore extension: {0}")]
+    KeyStore(#[source] ExtensionError),
 }
 
 impl<S: SecureKeyStore> KeyPairingWithConfig<S> {
@@ -54,18 +63,22 @@ impl<S: SecureKeyStore> KeyPairingWithConfig<S> {
         })
     }
 
-    pub fn create_keyring(config: Box<SingletonAppConfig>, secure_keystore: S) -> Self {
-        // TODO: extension trng support
-        let keyring = protocol::keyring::keypair::KeyPairing::create_keyring(OsRng);
+    pub fn create_keyring(
+        config: Box<SingletonAppConfig>,
+        secure_keystore: S,
+    ) -> Result<Self, KeyPairingError> {
+        let trng_read_sig = config.lock().load_trng_read_sig();
+        let rng = Trng::new(trng_read_sig)?;
+        let keyring = protocol::keyring::keypair::KeyPairing::create_keyring(rng);
 
-        KeyPairingWithConfig {
+        Ok(KeyPairingWithConfig {
             sign: keyring.sign,
             update: keyring.update,
             recovery: keyring.recovery,
             encrypt: keyring.encrypt,
             config,
             secure_keystore,
-        }
+        })
     }
 
     pub fn get_keyring(&self) -> protocol::keyring::keypair::KeyPairing {
@@ -77,20 +90,21 @@ impl<S: SecureKeyStore> KeyPairingWithConfig<S> {
         }
     }
 
-    pub fn save(&mut self, did: &str) {
+    pub fn save(&mut self, did: &str) -> Result<(), KeyPairingError> {
         self.secure_keystore
-            .write(&SecureKeyStoreKey::Sign(&self.sign));
+            .write(&SecureKeyStoreKey::Sign(&self.sign))?;
         self.secure_keystore
-            .write(&SecureKeyStoreKey::Update(&self.update));
+            .write(&SecureKeyStoreKey::Update(&self.update))?;
         self.secure_keystore
-            .write(&SecureKeyStoreKey::Recovery(&self.recovery));
+            .write(&SecureKeyStoreKey::Recovery(&self.recovery))?;
         self.secure_keystore
-            .write(&SecureKeyStoreKey::Encrypt(&self.encrypt));
+            .write(&SecureKeyStoreKey::Encrypt(&self.encrypt))?;
         {
             let mut config = self.config.lock();
-            config.save_did(did);
-            config.save_is_initialized(true);
+            config.save_did(did)?;
+            config.save_is_initialized(true)?;
         }
+        Ok(())
     }
 
     pub fn get_identifier(&self) -> Result<String, KeyPairingError> {
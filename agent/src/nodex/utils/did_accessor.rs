@@ -1,8 +1,10 @@
+use crate::nodex::extension::secure_keystore::KeyStore;
+use crate::nodex::keyring::keypair::{KeyPairingError, KeyPairingWithConfig};
 use protocol::keyring::keypair::KeyPairing;
 
 pub trait DidAccessor {
     fn get_my_did(&self) -> String;
-    fn get_my_keyring(&self) -> KeyPairing;
+    fn get_my_keyring(&self) -> Result<KeyPairing, KeyPairingError>;
 }
 
 pub struct DidAccessorImpl {}
@@ -14,10 +16,12 @@ impl DidAccessor for DidAccessorImpl {
         config.get_did().unwrap().to_string()
     }
 
-    fn get_my_keyring(&self) -> KeyPairing {
-        let config = crate::app_config();
-        let config = config.lock();
-        config.load_keyring().expect("failed to load keyring")
+    // NOTE: Goes through the keystore so that keys kept by an extension are found too.
+    fn get_my_keyring(&self) -> Result<KeyPairing, KeyPairingError> {
+        let keystore =
+            KeyStore::from_config(crate::app_config()).map_err(KeyPairingError::KeyStore)?;
+        let keyring = KeyPairingWithConfig::load_keyring(crate::app_config(), keystore)?;
+        Ok(keyring.get_keyring())
     }
 }
 
@@ -41,8 +45,8 @@ pub mod mocks {
             self.my_did.clone()
         }
 
-        fn get_my_keyring(&self) -> KeyPairing {
-            self.my_keyring.clone()
+        fn get_my_keyring(&self) -> Result<KeyPairing, KeyPairingError> {
+            Ok(self.my_keyring.clone())
         }
     }
 }
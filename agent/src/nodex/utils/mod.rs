@@ -2,6 +2,34 @@ pub mod did_accessor;
 pub mod sidetree_client;
 pub mod studio_client;
 
+use protocol::did::caching_did_repository::CachingDidRepository;
+use protocol::did::did_repository::DidRepositoryImpl;
+use sidetree_client::SideTreeClient;
+use std::sync::OnceLock;
+
+pub type SharedDidRepository = CachingDidRepository<DidRepositoryImpl<SideTreeClient>>;
+
+// NOTE: reqwest::Client is a handle to a connection pool, so cloning this shares
+// keep-alive connections between all clients that use it.
+pub fn shared_http_client() -> reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(reqwest::Client::new).clone()
+}
+
+// NOTE: Clones of CachingDidRepository share one cache, so DID documents resolved by
+// one request are reused by the next.
+pub fn shared_did_repository() -> SharedDidRepository {
+    static REPOSITORY: OnceLock<SharedDidRepository> = OnceLock::new();
+    REPOSITORY
+        .get_or_init(|| {
+            let server_config = crate::server_config();
+            let sidetree_client =
+                SideTreeClient::new(server_config.did_http_endpoint_url().clone());
+            CachingDidRepository::new(DidRepositoryImpl::new(sidetree_client))
+        })
+        .clone()
+}
+
 pub trait UnwrapLog<T, E> {
     fn unwrap_log(self) -> T;
 }
@@ -1,4 +1,3 @@
-use anyhow::Context;
 use protocol::did::sidetree::client::{SidetreeHttpClient, SidetreeHttpClientResponse};
 use url::{ParseError, Url};
 
@@ -9,13 +8,12 @@ pub struct SideTreeClient {
 }
 
 impl SideTreeClient {
-    pub fn new(base_url: &str) -> anyhow::Result<Self> {
-        let base_url =
-            Url::parse(base_url).context("NODEX_DID_HTTP_ENDPOINT must be a valid URL")?;
-        Ok(Self {
-            base_url,
-            client: reqwest::Client::new(),
-        })
+    pub fn new(base_url: Url) -> Self {
+        Self::with_http_client(base_url, super::shared_http_client())
+    }
+
+    pub fn with_http_client(base_url: Url, client: reqwest::Client) -> Self {
+        Self { base_url, client }
     }
 }
 
@@ -1,48 +1,175 @@
 use super::did_accessor::{DidAccessor, DidAccessorImpl};
-use crate::nodex::utils::sidetree_client::SideTreeClient;
+use crate::config::Environment;
+use crate::nodex::utils::{shared_did_repository, SharedDidRepository};
 use crate::{network_config, server_config};
 use anyhow::Context;
 use chrono::Utc;
 use hmac::{Hmac, Mac};
-use protocol::did::did_repository::DidRepositoryImpl;
 use protocol::didcomm::encrypted::{DidCommEncryptedService, DidCommServiceWithAttachment};
+use protocol::rand_core::{OsRng, RngCore};
 use protocol::verifiable_credentials::types::VerifiableCredentials;
 use reqwest::{
     header::{HeaderMap, HeaderValue},
-    Url,
+    StatusCode, Url,
 };
+use serde::Deserialize;
 use serde_json::json;
 use sha2::Sha256;
+use std::time::Duration;
 
 type HmacSha256 = Hmac<Sha256>;
 
+// NOTE: Studio's error envelope, e.g. `{"code": "invalid_signature", "message": "..."}`.
+#[derive(Debug, Deserialize)]
+pub struct StudioError {
+    pub code: Option<String>,
+    pub message: String,
+}
+
+impl StudioError {
+    // NOTE: Falls back to the raw body as the message when it is not an envelope.
+    fn from_body(body: &str) -> Self {
+        serde_json::from_str(body).unwrap_or_else(|_| Self {
+            code: None,
+            message: body.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for StudioError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.code {
+            Some(code) => write!(f, "{} ({})", self.message, code),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StudioHttpError {
+    #[error("Bad Request: {0}")]
+    BadRequest(StudioError),
+    #[error("Unauthorized: {0}")]
+    Unauthorized(StudioError),
+    #[error("Forbidden: {0}")]
+    Forbidden(StudioError),
+    #[error("Not Found: {0}")]
+    NotFound(StudioError),
+    #[error("Conflict: {0}")]
+    Conflict(StudioError),
+    #[error("Server Error: StatusCode={status}, {error}")]
+    ServerError {
+        status: StatusCode,
+        error: StudioError,
+    },
+    #[error("Unexpected response: StatusCode={status}, {error}")]
+    Unexpected {
+        status: StatusCode,
+        error: StudioError,
+    },
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl StudioHttpError {
+    fn from_error_status(status: StatusCode, error: StudioError) -> Self {
+        match status {
+            StatusCode::BAD_REQUEST => Self::BadRequest(error),
+            StatusCode::UNAUTHORIZED => Self::Unauthorized(error),
+            StatusCode::FORBIDDEN => Self::Forbidden(error),
+            StatusCode::NOT_FOUND => Self::NotFound(error),
+            StatusCode::CONFLICT => Self::Conflict(error),
+            status if status.is_server_error() => Self::ServerError { status, error },
+            status => Self::Unexpected { status, error },
+        }
+    }
+
+    // NOTE: Only server errors are worth retrying; 4xx responses will not change.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::ServerError { .. })
+    }
+}
+
+// NOTE: Passes successful responses through and turns any other response into a
+// StudioHttpError carrying Studio's error envelope.
+pub async fn check_status(
+    response: reqwest::Response,
+) -> Result<reqwest::Response, StudioHttpError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response
+        .text()
+        .await
+        .context("Failed to read response body")?;
+    Err(StudioHttpError::from_error_status(
+        status,
+        StudioError::from_body(&body),
+    ))
+}
+
 pub struct StudioClientConfig {
-    pub base_url: String,
+    pub base_url: Url,
+}
+
+// NOTE: Applies only to read-only requests; writes are sent once.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay * 2u32.saturating_pow(attempt)
+    }
+}
+
+fn is_transient(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<reqwest::Error>()
+        .is_some_and(|e| e.is_connect() || e.is_timeout())
 }
 
 pub struct StudioClient {
     pub base_url: Url,
     pub instance: reqwest::Client,
-    pub didcomm_service: DidCommServiceWithAttachment<DidRepositoryImpl<SideTreeClient>>,
+    pub didcomm_service: DidCommServiceWithAttachment<SharedDidRepository>,
     pub did_accessor: DidAccessorImpl,
+    pub retry_policy: RetryPolicy,
 }
 
 impl StudioClient {
     pub fn new(_config: &StudioClientConfig) -> anyhow::Result<Self> {
-        let url = Url::parse(&_config.base_url.to_string())?;
-        let client = reqwest::Client::new();
+        Self::with_http_client(_config, super::shared_http_client())
+    }
+
+    pub fn with_http_client(
+        _config: &StudioClientConfig,
+        client: reqwest::Client,
+    ) -> anyhow::Result<Self> {
         let server_config = server_config();
-        let sidetree_client = SideTreeClient::new(&server_config.did_http_endpoint())?;
-        let did_repository = DidRepositoryImpl::new(sidetree_client);
+        let did_repository = shared_did_repository();
         let didcomm_service =
             DidCommServiceWithAttachment::new(did_repository, server_config.did_attachment_link());
         let did_accessor = DidAccessorImpl {};
 
         Ok(StudioClient {
             instance: client,
-            base_url: url,
+            base_url: _config.base_url.clone(),
             didcomm_service,
             did_accessor,
+            retry_policy: RetryPolicy::default(),
         })
     }
 
@@ -52,12 +179,14 @@ impl StudioClient {
             .lock()
             .get_secret_key()
             .ok_or(anyhow::anyhow!("not found secret key"))?;
-        let mut mac = HmacSha256::new_from_slice(secret.as_bytes())?;
+        let timestamp = Utc::now().timestamp().to_string();
+        let nonce = generate_nonce();
+        let signature = &sign_request(&secret, &timestamp, &nonce, &payload)?;
 
-        mac.update(payload.as_bytes());
-        let signature = &hex::encode(mac.finalize().into_bytes());
         let mut headers = HeaderMap::new();
         headers.insert("X-Nodex-Signature", HeaderValue::from_str(signature)?);
+        headers.insert("X-Nodex-Timestamp", HeaderValue::from_str(&timestamp)?);
+        headers.insert("X-Nodex-Nonce", HeaderValue::from_str(&nonce)?);
         headers.insert(
             reqwest::header::CONTENT_TYPE,
             HeaderValue::from_static("application/json"),
@@ -103,6 +232,38 @@ impl StudioClient {
         Ok(response)
     }
 
+    // NOTE: Retries connection errors, timeouts and 5xx responses; 4xx responses are
+    // returned as is. Only use this for requests that are safe to repeat.
+    async fn post_with_retry(&self, path: &str, body: &str) -> anyhow::Result<reqwest::Response> {
+        let mut attempt = 0;
+        loop {
+            let retryable = attempt + 1 < self.retry_policy.max_attempts;
+            match self.post(path, body).await {
+                Ok(response) if response.status().is_server_error() && retryable => {
+                    log::warn!(
+                        "StatusCode={} from {}, retrying ({}/{})",
+                        response.status(),
+                        path,
+                        attempt + 1,
+                        self.retry_policy.max_attempts
+                    );
+                }
+                Err(e) if is_transient(&e) && retryable => {
+                    log::warn!(
+                        "request to {} failed: {}, retrying ({}/{})",
+                        path,
+                        e,
+                        attempt + 1,
+                        self.retry_policy.max_attempts
+                    );
+                }
+                result => return result,
+            }
+            tokio::time::sleep(self.retry_policy.delay(attempt)).await;
+            attempt += 1;
+        }
+    }
+
     pub async fn send_device_info(
         &self,
         path: &str,
@@ -110,14 +271,14 @@ impl StudioClient {
         mac_address: &str,
         version: &str,
         os: &str,
+        environment: Environment,
     ) -> anyhow::Result<reqwest::Response> {
-        let message = json!({
-            "mac_address": mac_address,
-            "version": version,
-            "os": os,
-        });
+        let message = device_info_message(mac_address, version, os, environment);
         let my_did = self.did_accessor.get_my_did();
-        let my_keyring = self.did_accessor.get_my_keyring();
+        let my_keyring = self
+            .did_accessor
+            .get_my_keyring()
+            .context("failed to load keyring")?;
 
         let model = VerifiableCredentials::new(my_did, json!(message), Utc::now());
         let payload = self
@@ -136,7 +297,10 @@ impl StudioClient {
         project_did: &str,
     ) -> anyhow::Result<reqwest::Response> {
         let my_did = self.did_accessor.get_my_did();
-        let my_keyring = self.did_accessor.get_my_keyring();
+        let my_keyring = self
+            .did_accessor
+            .get_my_keyring()
+            .context("failed to load keyring")?;
 
         let model = VerifiableCredentials::new(my_did, serde_json::Value::Null, Utc::now());
         let payload = self
@@ -145,7 +309,7 @@ impl StudioClient {
             .await?;
         let payload = serde_json::to_string(&payload)?;
         let url = self.base_url.join(path)?;
-        self.post(url.as_ref(), &payload).await
+        self.post_with_retry(url.as_ref(), &payload).await
     }
 
     pub async fn ack_message(
@@ -161,7 +325,10 @@ impl StudioClient {
             "is_verified": is_verified,
         });
         let my_did = self.did_accessor.get_my_did();
-        let my_keyring = self.did_accessor.get_my_keyring();
+        let my_keyring = self
+            .did_accessor
+            .get_my_keyring()
+            .context("failed to load keyring")?;
 
         let model = VerifiableCredentials::new(my_did, payload, Utc::now());
         let payload = self
@@ -179,7 +346,10 @@ impl StudioClient {
         project_did: &str,
     ) -> anyhow::Result<reqwest::Response> {
         let my_did = self.did_accessor.get_my_did();
-        let my_keyring = self.did_accessor.get_my_keyring();
+        let my_keyring = self
+            .did_accessor
+            .get_my_keyring()
+            .context("failed to load keyring")?;
 
         let model = VerifiableCredentials::new(my_did, serde_json::Value::Null, Utc::now());
         let payload = self
@@ -187,7 +357,7 @@ impl StudioClient {
             .generate(model, &my_keyring, project_did, None)
             .await?;
         let payload = serde_json::to_string(&payload)?;
-        self.post(path, &payload).await
+        self.post_with_retry(path, &payload).await
     }
 
     pub async fn put(&self, path: &str, body: &str) -> anyhow::Result<reqwest::Response> {
@@ -208,12 +378,218 @@ impl StudioClient {
 
         Ok(response)
     }
+
+    #[allow(dead_code)]
+    pub async fn patch(&self, path: &str, body: &str) -> anyhow::Result<reqwest::Response> {
+        let url = self.base_url.join(path)?;
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            reqwest::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+
+        let response = self
+            .instance
+            .patch(url)
+            .headers(headers)
+            .body(body.to_string())
+            .send()
+            .await?;
+
+        Ok(response)
+    }
+
+    #[allow(dead_code)]
+    pub async fn delete(&self, path: &str) -> anyhow::Result<reqwest::Response> {
+        let url = self.base_url.join(path)?;
+
+        let response = self.instance.delete(url).send().await?;
+
+        Ok(response)
+    }
+
+    // NOTE: A DELETE has no body, so the signature covers an empty body.
+    #[allow(dead_code)]
+    pub async fn delete_with_auth_header(&self, path: &str) -> anyhow::Result<reqwest::Response> {
+        let url = self.base_url.join(path)?;
+        let headers = self.auth_headers(String::new())?;
+
+        let response = self.instance.delete(url).headers(headers).send().await?;
+
+        Ok(response)
+    }
+}
+
+fn generate_nonce() -> String {
+    let mut nonce = [0u8; 16];
+    OsRng.fill_bytes(&mut nonce);
+    hex::encode(nonce)
+}
+
+// NOTE: The signed material is `{timestamp}.{nonce}.{body}`, where timestamp is the
+// Unix time in seconds sent as X-Nodex-Timestamp and nonce is the hex string sent as
+// X-Nodex-Nonce. The signature is the hex-encoded HMAC-SHA256 of it with the secret key.
+fn sign_request(secret: &str, timestamp: &str, nonce: &str, body: &str) -> anyhow::Result<String> {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())?;
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(nonce.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    Ok(hex::encode(mac.finalize().into_bytes()))
+}
+
+fn device_info_message(
+    mac_address: &str,
+    version: &str,
+    os: &str,
+    environment: Environment,
+) -> serde_json::Value {
+    json!({
+        "mac_address": mac_address,
+        "version": version,
+        "os": os,
+        "environment": environment,
+    })
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use serde::Deserialize;
+
+    #[test]
+    fn test_device_info_message_includes_environment() {
+        let message = device_info_message("00:00:00:00:00:00", "3.6.0", "linux", Environment::Dev);
+        assert_eq!(message["environment"], "dev");
+        assert_eq!(message["mac_address"], "00:00:00:00:00:00");
+    }
+
+    #[test]
+    fn test_studio_error_from_body() {
+        let error = StudioError::from_body(r#"{"code":"invalid_signature","message":"bad sig"}"#);
+        assert_eq!(error.code.as_deref(), Some("invalid_signature"));
+        assert_eq!(error.message, "bad sig");
+        assert_eq!(error.to_string(), "bad sig (invalid_signature)");
+
+        let error = StudioError::from_body(r#"{"message":"not found"}"#);
+        assert_eq!(error.code, None);
+        assert_eq!(error.to_string(), "not found");
+
+        let error = StudioError::from_body("<html>Bad Gateway</html>");
+        assert_eq!(error.code, None);
+        assert_eq!(error.message, "<html>Bad Gateway</html>");
+    }
+
+    fn response(status: StatusCode, body: &'static str) -> reqwest::Response {
+        axum::http::Response::builder()
+            .status(status)
+            .body(body)
+            .unwrap()
+            .into()
+    }
+
+    #[tokio::test]
+    async fn test_check_status() {
+        let ok = check_status(response(StatusCode::OK, "{}")).await.unwrap();
+        assert_eq!(ok.status(), StatusCode::OK);
+
+        let error = check_status(response(
+            StatusCode::CONFLICT,
+            r#"{"code":"duplicated","message":"already exists"}"#,
+        ))
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            error,
+            StudioHttpError::Conflict(StudioError { code: Some(code), message })
+                if code == "duplicated" && message == "already exists"
+        ));
+
+        // NOTE: Redirects are followed by the client, so one reaching here is unexpected
+        // rather than a success.
+        let error = check_status(response(StatusCode::NOT_MODIFIED, ""))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            StudioHttpError::Unexpected { status, .. } if status == StatusCode::NOT_MODIFIED
+        ));
+    }
+
+    #[test]
+    fn test_retry_policy_backoff() {
+        let policy = RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(100),
+        };
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(1), Duration::from_millis(200));
+        assert_eq!(policy.delay(2), Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn test_post_with_retry_retries_connection_errors() {
+        // NOTE: Nothing listens on the discard port, so every attempt is refused.
+        let mut client = StudioClient::new(&StudioClientConfig {
+            base_url: Url::parse("http://127.0.0.1:9").unwrap(),
+        })
+        .unwrap();
+        client.retry_policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(20),
+        };
+
+        let started = std::time::Instant::now();
+        let result = client.post_with_retry("/v1/messages", "{}").await;
+        assert!(result.is_err());
+        assert!(started.elapsed() >= Duration::from_millis(60));
+    }
+
+    #[test]
+    fn test_sign_request_binds_timestamp_and_nonce() {
+        let body = r#"{"device_did":"did:nodex:test:abc"}"#;
+        let first = sign_request("secret", "1700000000", &generate_nonce(), body).unwrap();
+        let second = sign_request("secret", "1700000000", &generate_nonce(), body).unwrap();
+        assert_ne!(first, second);
+
+        let signature = sign_request("secret", "1700000000", "00ff", body).unwrap();
+        assert_eq!(
+            signature,
+            sign_request("secret", "1700000000", "00ff", body).unwrap()
+        );
+        assert_ne!(
+            signature,
+            sign_request("secret", "1700000001", "00ff", body).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_status_maps_status_codes() {
+        let error = check_status(response(StatusCode::BAD_REQUEST, r#"{"message":"bad"}"#))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            StudioHttpError::BadRequest(StudioError { message, .. }) if message == "bad"
+        ));
+        assert!(matches!(
+            check_status(response(StatusCode::NOT_FOUND, "")).await,
+            Err(StudioHttpError::NotFound(_))
+        ));
+        assert!(matches!(
+            check_status(response(StatusCode::TOO_MANY_REQUESTS, "")).await,
+            Err(StudioHttpError::Unexpected { .. })
+        ));
+
+        let server_error = check_status(response(StatusCode::BAD_GATEWAY, ""))
+            .await
+            .unwrap_err();
+        assert!(server_error.is_retryable());
+        let client_error = check_status(response(StatusCode::UNAUTHORIZED, ""))
+            .await
+            .unwrap_err();
+        assert!(!client_error.is_retryable());
+    }
 
     #[derive(Deserialize)]
     struct Res {
@@ -224,7 +600,7 @@ pub mod tests {
     #[ignore]
     async fn it_should_success_post() {
         let client_config: StudioClientConfig = StudioClientConfig {
-            base_url: "https://httpbin.org".to_string(),
+            base_url: Url::parse("https://httpbin.org").unwrap(),
         };
 
         let client = match StudioClient::new(&client_config) {
@@ -249,7 +625,7 @@ pub mod tests {
     #[ignore]
     async fn it_should_success_put() {
         let client_config: StudioClientConfig = StudioClientConfig {
-            base_url: "https://httpbin.org".to_string(),
+            base_url: Url::parse("https://httpbin.org").unwrap(),
         };
 
         let client = match StudioClient::new(&client_config) {
@@ -269,4 +645,54 @@ pub mod tests {
 
         assert!(!json.origin.is_empty());
     }
+
+    #[tokio::test]
+    #[ignore]
+    async fn it_should_success_patch() {
+        let client_config: StudioClientConfig = StudioClientConfig {
+            base_url: Url::parse("https://httpbin.org").unwrap(),
+        };
+
+        let client = match StudioClient::new(&client_config) {
+            Ok(v) => v,
+            Err(_) => panic!(),
+        };
+
+        let res = match client.patch("/patch", r#"{"key":"value"}"#).await {
+            Ok(v) => v,
+            Err(_) => panic!(),
+        };
+
+        let json: Res = match res.json().await {
+            Ok(v) => v,
+            Err(_) => panic!(),
+        };
+
+        assert!(!json.origin.is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn it_should_success_delete() {
+        let client_config: StudioClientConfig = StudioClientConfig {
+            base_url: Url::parse("https://httpbin.org").unwrap(),
+        };
+
+        let client = match StudioClient::new(&client_config) {
+            Ok(v) => v,
+            Err(_) => panic!(),
+        };
+
+        let res = match client.delete("/delete").await {
+            Ok(v) => v,
+            Err(_) => panic!(),
+        };
+
+        let json: Res = match res.json().await {
+            Ok(v) => v,
+            Err(_) => panic!(),
+        };
+
+        assert!(!json.origin.is_empty());
+    }
 }
@@ -9,6 +9,9 @@ use std::{
 pub struct Metric {
     pub metric_type: MetricType,
     pub value: f32,
+    // NOTE: Set to the network interface name when metrics are collected per interface.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -44,6 +47,8 @@ pub enum MetricType {
     NetworkTransmittedPackets,
     DiskReadBytes,
     DiskWrittenBytes,
+    CpuTemperature,
+    Uptime,
 }
 
 impl Display for MetricType {
@@ -57,6 +62,8 @@ impl Display for MetricType {
             MetricType::NetworkTransmittedPackets => write!(f, "network_transmitted_packets"),
             MetricType::DiskReadBytes => write!(f, "disk_read_bytes"),
             MetricType::DiskWrittenBytes => write!(f, "disk_written_bytes"),
+            MetricType::CpuTemperature => write!(f, "cpu_temperature"),
+            MetricType::Uptime => write!(f, "uptime"),
         }
     }
 }
@@ -2,6 +2,9 @@ use crate::config::app_config;
 use crate::controllers;
 use axum::{
     extract::DefaultBodyLimit,
+    http::HeaderValue,
+    middleware,
+    response::Response,
     routing::{get, post},
     Router,
 };
@@ -15,6 +18,9 @@ pub mod unix {
     use std::os::unix::fs::PermissionsExt;
     use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
     use std::path::Path;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
     use tokio::net::{UnixListener, UnixStream};
     use tokio::signal::unix::{signal, SignalKind};
     use tokio::task::JoinSet;
@@ -43,33 +49,62 @@ pub mod unix {
         Ok(uds)
     }
 
-    pub async fn make_uds_server(router: Router, uds: UnixListener) -> std::io::Result<()> {
+    // NOTE: Once `token` is cancelled the server stops accepting connections and
+    // finishes the requests already in flight.
+    pub async fn make_uds_server(
+        router: Router,
+        uds: UnixListener,
+        token: CancellationToken,
+    ) -> std::io::Result<()> {
         // https://github.com/tokio-rs/axum/blob/main/examples/unix-domain-socket/src/main.rs
         let app = router.into_make_service();
-        axum::serve(uds, app).await
+        axum::serve(uds, app)
+            .with_graceful_shutdown(token.cancelled_owned())
+            .await
     }
 
+    // NOTE: Cancelling `token` (or SIGTERM / Ctrl+C) stops the server, waiting up to
+    // `timeout` for in-flight requests, and then removes `uds_path`. On SIGUSR1 the
+    // listener is handed over to the new agent, so the socket file is left in place.
     pub fn wrap_with_signal_handler(
         server: impl std::future::Future<Output = std::io::Result<()>> + Send + 'static,
         token: CancellationToken,
         fd: RawFd,
         uds_path: impl AsRef<Path>,
+        timeout: Duration,
     ) -> JoinSet<std::io::Result<()>> {
         let mut set = JoinSet::new();
+        let uds_path = uds_path.as_ref().to_owned();
+        let handed_over = Arc::new(AtomicBool::new(false));
         let cloned_token = token.clone();
+        let cloned_uds_path = uds_path.clone();
+        let cloned_handed_over = handed_over.clone();
         let tasks = async move {
-            tokio::select! {
-                _ = cloned_token.cancelled() => Ok(()),
-                res = server => res,
+            let mut server = std::pin::pin!(server);
+            let res = tokio::select! {
+                res = &mut server => res,
+                _ = cloned_token.cancelled() => {
+                    match tokio::time::timeout(timeout, &mut server).await {
+                        Ok(res) => res,
+                        Err(_) => {
+                            log::warn!("Server did not stop within {:?}", timeout);
+                            Ok(())
+                        }
+                    }
+                }
+            };
+            if !cloned_handed_over.load(Ordering::SeqCst) {
+                remove_file_if_exists(&cloned_uds_path);
             }
+            res
         };
         set.spawn(tasks);
-        let uds_path = uds_path.as_ref().to_owned();
         set.spawn(async move {
             let ctrl_c = tokio::signal::ctrl_c();
             let mut sigterm = signal(SignalKind::terminate())?;
             let mut sigusr1 = signal(SignalKind::user_defined1())?;
             tokio::select! {
+                _ = token.cancelled() => Ok(()),
                 _ = ctrl_c => {
                     log::info!("Received Ctrl+C");
                     token.cancel();
@@ -97,6 +132,7 @@ pub mod unix {
                         }
                     };
                     send_fd(stream.as_raw_fd(), Some(fd))?;
+                    handed_over.store(true, Ordering::SeqCst);
                     token.cancel();
                     Ok(())
                 }
@@ -185,9 +221,47 @@ pub mod windows {
     }
 }
 
+// NOTE: Overrides `didcomm.http_body_size_limit` in the config, in bytes.
+const BODY_SIZE_LIMIT_ENV: &str = "NODEX_HTTP_BODY_SIZE_LIMIT";
+
+fn body_size_limit(value: Option<&str>, configured: usize) -> usize {
+    match value.map(str::parse::<usize>) {
+        Some(Ok(limit)) => limit,
+        Some(Err(e)) => {
+            log::warn!(
+                "ignoring invalid {}: {}, using {} bytes",
+                BODY_SIZE_LIMIT_ENV,
+                e,
+                configured
+            );
+            configured
+        }
+        None => configured,
+    }
+}
+
 pub fn make_router() -> Router {
-    let body_limit = app_config().lock().get_didcomm_body_size();
+    let configured = app_config().lock().get_didcomm_body_size();
+    let body_limit = body_size_limit(
+        std::env::var(BODY_SIZE_LIMIT_ENV).ok().as_deref(),
+        configured,
+    );
+    routes(body_limit)
+}
+
+// NOTE: Stamps every response with the version of the running agent binary.
+async fn set_version_header(mut response: Response) -> Response {
+    response.headers_mut().insert(
+        "x-version",
+        HeaderValue::from_static(env!("CARGO_PKG_VERSION")),
+    );
+    response
+}
+
+// NOTE: Requests whose body exceeds `body_limit` are rejected with 413 Payload Too Large.
+fn routes(body_limit: usize) -> Router {
     Router::new()
+        .route("/health", get(controllers::public::health::handler))
         .route(
             "/identifiers",
             post(controllers::public::nodex_create_identifier::handler),
@@ -208,12 +282,10 @@ pub fn make_router() -> Router {
             "/create-didcomm-message",
             post(controllers::public::nodex_create_didcomm_message::handler),
         )
-        .layer(DefaultBodyLimit::max(body_limit))
         .route(
             "/verify-didcomm-message",
             post(controllers::public::nodex_verify_didcomm_message::handler),
         )
-        .layer(DefaultBodyLimit::max(body_limit))
         .route("/events", post(controllers::public::send_event::handler))
         .route(
             "/custom-metrics",
@@ -236,4 +308,117 @@ pub fn make_router() -> Router {
             "/internal/network",
             post(controllers::internal::network::handler),
         )
+        .layer(DefaultBodyLimit::max(body_limit))
+        .layer(middleware::map_response(set_version_header))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use tower::ServiceExt;
+
+    fn post_json(uri: &str, body: String) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_is_rejected() {
+        let body = format!(r#"{{"message":"{}"}}"#, "a".repeat(1024));
+        for uri in ["/create-didcomm-message", "/events", "/internal/network"] {
+            let response = routes(256)
+                .oneshot(post_json(uri, body.clone()))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_body_within_limit_reaches_handler() {
+        let response = routes(256)
+            .oneshot(post_json("/create-didcomm-message", "{}".to_string()))
+            .await
+            .unwrap();
+        assert_ne!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_shutdown_removes_socket() {
+        use std::os::unix::io::AsRawFd;
+        use tokio_util::sync::CancellationToken;
+
+        let dir =
+            std::env::temp_dir().join(format!("nodex-server-shutdown-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let uds_path = dir.join("nodex.sock");
+        let listener = tokio::net::UnixListener::bind(&uds_path).unwrap();
+        let fd = listener.as_raw_fd();
+
+        let token = CancellationToken::new();
+        let server = unix::make_uds_server(routes(1024), listener, token.clone());
+        let server = unix::wrap_with_signal_handler(
+            server,
+            token.clone(),
+            fd,
+            &uds_path,
+            std::time::Duration::from_secs(1),
+        );
+        assert!(uds_path.exists());
+
+        token.cancel();
+        for res in server.join_all().await {
+            res.unwrap();
+        }
+        assert!(!uds_path.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_responses_carry_crate_version() {
+        let request = Request::builder()
+            .uri("/health")
+            .body(Body::empty())
+            .unwrap();
+        let response = routes(1024).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("x-version").unwrap(),
+            env!("CARGO_PKG_VERSION")
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+
+        // Rejected requests are stamped as well.
+        let response = routes(16)
+            .oneshot(post_json("/events", "[]".repeat(16)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(
+            response.headers().get("x-version").unwrap(),
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+
+    #[test]
+    fn test_body_size_limit_override() {
+        assert_eq!(body_size_limit(None, 1024), 1024);
+        assert_eq!(body_size_limit(Some("2048"), 1024), 2048);
+        assert_eq!(body_size_limit(Some("2MB"), 1024), 1024);
+    }
 }
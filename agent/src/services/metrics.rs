@@ -6,11 +6,13 @@ use crate::repository::metric_repository::{
     Metric, MetricType, MetricsCacheRepository, MetricsWatchRepository, MetricsWithTimestamp,
 };
 use chrono::{DateTime, Utc};
-use sysinfo::{Networks, System};
+use sysinfo::{Components, Networks, System};
 
 pub struct MetricsWatchService {
     system: System,
     networks: Networks,
+    components: Components,
+    per_interface: bool,
 }
 
 #[derive(Clone)]
@@ -24,14 +26,24 @@ impl MetricsWatchService {
         Self {
             system: System::new(),
             networks: Networks::new(),
+            components: Components::new(),
+            per_interface: false,
         }
     }
 
+    // NOTE: When enabled, network metrics are emitted once per interface with the
+    // interface name as the label instead of being summed.
+    pub fn with_per_interface(mut self, per_interface: bool) -> Self {
+        self.per_interface = per_interface;
+        self
+    }
+
     fn cpu_usage(&mut self) -> Metric {
         self.system.refresh_cpu_usage();
         Metric {
             metric_type: MetricType::CpuUsage,
             value: self.system.global_cpu_info().cpu_usage(),
+            label: None,
         }
     }
 
@@ -40,16 +52,60 @@ impl MetricsWatchService {
         Metric {
             metric_type: MetricType::MemoryUsage,
             value: self.system.used_memory() as f32,
+            label: None,
+        }
+    }
+
+    // NOTE: Returns None when the device exposes no temperature sensors, so that
+    // a missing reading is not reported as 0 degrees.
+    fn cpu_temperature(&mut self) -> Option<Metric> {
+        self.components.refresh_list();
+        self.components
+            .list()
+            .iter()
+            .map(|component| component.temperature())
+            .filter(|temperature| temperature.is_finite() && *temperature > 0.0)
+            .reduce(f32::max)
+            .map(|value| Metric {
+                metric_type: MetricType::CpuTemperature,
+                value,
+                label: None,
+            })
+    }
+
+    fn uptime(&self) -> Metric {
+        Metric {
+            metric_type: MetricType::Uptime,
+            value: System::uptime() as f32,
+            label: None,
         }
     }
 
     fn network_info(&mut self) -> Vec<Metric> {
+        self.networks.refresh_list();
+
+        if self.per_interface {
+            return self
+                .networks
+                .list()
+                .iter()
+                .flat_map(|(interface_name, network)| {
+                    Self::network_metrics(
+                        network.received(),
+                        network.transmitted(),
+                        network.packets_received(),
+                        network.packets_transmitted(),
+                        Some(interface_name.clone()),
+                    )
+                })
+                .collect();
+        }
+
         let mut received_bytes = 0;
         let mut transmitted_bytes = 0;
         let mut received_packets = 0;
         let mut transmitted_packets = 0;
 
-        self.networks.refresh_list();
         for network in self.networks.list().values() {
             received_bytes += network.received();
             transmitted_bytes += network.transmitted();
@@ -57,22 +113,42 @@ impl MetricsWatchService {
             transmitted_packets += network.packets_transmitted();
         }
 
+        Self::network_metrics(
+            received_bytes,
+            transmitted_bytes,
+            received_packets,
+            transmitted_packets,
+            None,
+        )
+    }
+
+    fn network_metrics(
+        received_bytes: u64,
+        transmitted_bytes: u64,
+        received_packets: u64,
+        transmitted_packets: u64,
+        label: Option<String>,
+    ) -> Vec<Metric> {
         vec![
             Metric {
                 metric_type: MetricType::NetworkReceivedBytes,
                 value: received_bytes as f32,
+                label: label.clone(),
             },
             Metric {
                 metric_type: MetricType::NetworkTransmittedBytes,
                 value: transmitted_bytes as f32,
+                label: label.clone(),
             },
             Metric {
                 metric_type: MetricType::NetworkReceivedPackets,
                 value: received_packets as f32,
+                label: label.clone(),
             },
             Metric {
                 metric_type: MetricType::NetworkTransmittedPackets,
                 value: transmitted_packets as f32,
+                label,
             },
         ]
     }
@@ -92,10 +168,12 @@ impl MetricsWatchService {
             Metric {
                 metric_type: MetricType::DiskReadBytes,
                 value: read_bytes as f32,
+                label: None,
             },
             Metric {
                 metric_type: MetricType::DiskWrittenBytes,
                 value: written_bytes as f32,
+                label: None,
             },
         ]
     }
@@ -109,6 +187,8 @@ impl MetricsWatchRepository for MetricsWatchService {
         metrics.push(self.memory_usage());
         metrics.append(&mut self.network_info());
         metrics.append(&mut self.disk_info());
+        metrics.extend(self.cpu_temperature());
+        metrics.push(self.uptime());
 
         metrics
     }
@@ -178,6 +258,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_network_info_per_interface() {
+        let mut service = MetricsWatchService::new().with_per_interface(true);
+        let network_metrics = service.network_info();
+        assert!(network_metrics.len() % 4 == 0);
+        for network_metric in network_metrics {
+            let label = network_metric.label.expect("interface name should be set");
+            assert!(service.networks.list().contains_key(&label));
+        }
+    }
+
+    #[test]
+    fn test_metric_label_serialization() {
+        let mut metrics = MetricsWatchService::network_metrics(1, 2, 3, 4, None);
+        let value = serde_json::to_value(&metrics[0]).unwrap();
+        assert!(value.get("label").is_none());
+
+        metrics = MetricsWatchService::network_metrics(1, 2, 3, 4, Some("eth0".to_string()));
+        let value = serde_json::to_value(&metrics[0]).unwrap();
+        assert_eq!(value["label"], "eth0");
+    }
+
     #[test]
     fn test_disk_info() {
         let mut service = MetricsWatchService::new();
@@ -191,10 +293,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cpu_temperature() {
+        let mut service = MetricsWatchService::new();
+        if let Some(cpu_temperature) = service.cpu_temperature() {
+            assert!(cpu_temperature.value > 0.0);
+            assert!(cpu_temperature.metric_type == MetricType::CpuTemperature);
+        }
+    }
+
+    #[test]
+    fn test_uptime() {
+        let service = MetricsWatchService::new();
+        let uptime = service.uptime();
+        assert!(uptime.value >= 0.0);
+        assert!(uptime.metric_type == MetricType::Uptime);
+    }
+
     #[test]
     fn test_watch_metrics() {
         let mut service = MetricsWatchService::new();
         let metrics = service.watch_metrics();
-        assert!(metrics.len() == 8);
+        let has_temperature = metrics
+            .iter()
+            .any(|metric| metric.metric_type == MetricType::CpuTemperature);
+        assert!(metrics.len() == if has_temperature { 10 } else { 9 });
     }
 }
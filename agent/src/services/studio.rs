@@ -1,5 +1,6 @@
+use crate::config::Environment;
 use crate::nodex::utils::did_accessor::{DidAccessor, DidAccessorImpl};
-use crate::nodex::utils::sidetree_client::SideTreeClient;
+use crate::nodex::utils::{shared_did_repository, SharedDidRepository};
 use crate::repository::attribute_repository::{AttributeStoreRepository, AttributeStoreRequest};
 use crate::repository::custom_metric_repository::{
     CustomMetricStoreRepository, CustomMetricStoreRequest,
@@ -9,20 +10,21 @@ use crate::repository::message_activity_repository::MessageActivityHttpError;
 use crate::repository::metric_repository::{MetricStoreRepository, MetricsWithTimestamp};
 use crate::server_config;
 use crate::{
-    nodex::utils::studio_client::{StudioClient, StudioClientConfig},
+    nodex::utils::studio_client::{
+        check_status, StudioClient, StudioClientConfig, StudioHttpError,
+    },
     repository::message_activity_repository::{
         CreatedMessageActivityRequest, MessageActivityRepository, VerifiedMessageActivityRequest,
     },
 };
 use anyhow::Context;
-use protocol::did::did_repository::DidRepositoryImpl;
 use protocol::didcomm::encrypted::DidCommEncryptedService;
 use protocol::verifiable_credentials::did_vc::DidVcService;
 use protocol::verifiable_credentials::types::VerifiableCredentials;
 use std::collections::VecDeque;
 
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use serde_json::json;
 
 // The maximum JSON body size is actually 1MB
 // We reserve 100KB as a buffer for Verifiable Credential capacity
@@ -37,14 +39,9 @@ pub struct MessageResponse {
     pub raw_message: String,
 }
 
-#[derive(Deserialize, Debug, Clone)]
-struct ErrorResponse {
-    pub message: String,
-}
-
 pub struct Studio {
     http_client: StudioClient,
-    did_repository: DidRepositoryImpl<SideTreeClient>,
+    did_repository: SharedDidRepository,
     did_accessor: DidAccessorImpl,
 }
 
@@ -75,7 +72,7 @@ impl Studio {
     pub fn new() -> Self {
         let server_config = server_config();
         let client_config: StudioClientConfig = StudioClientConfig {
-            base_url: server_config.studio_http_endpoint(),
+            base_url: server_config.studio_http_endpoint_url().clone(),
         };
 
         let client = match StudioClient::new(&client_config) {
@@ -86,9 +83,7 @@ impl Studio {
             }
         };
 
-        let sidetree_client = SideTreeClient::new(&server_config.did_http_endpoint())
-            .expect("failed to create sidetree client");
-        let did_repository = DidRepositoryImpl::new(sidetree_client);
+        let did_repository = shared_did_repository();
         let did_accessor = DidAccessorImpl {};
 
         Studio {
@@ -112,33 +107,8 @@ impl Studio {
             .http_client
             .post_with_auth_header("/v1/device", &payload)
             .await?;
-
-        let status = res.status();
-
-        let json: Value = res.json().await.context("Failed to read response body")?;
-        let message = json
-            .get("message")
-            .map(|v| v.to_string())
-            .unwrap_or_default();
-
-        match status {
-            reqwest::StatusCode::OK => Ok(()),
-            reqwest::StatusCode::BAD_REQUEST => {
-                anyhow::bail!("StatusCode=400, {}", message)
-            }
-            reqwest::StatusCode::UNAUTHORIZED => {
-                anyhow::bail!("StatusCode=401, {}", message)
-            }
-            reqwest::StatusCode::NOT_FOUND => {
-                anyhow::bail!("StatusCode=404, {}", message)
-            }
-            reqwest::StatusCode::INTERNAL_SERVER_ERROR => {
-                anyhow::bail!("StatusCode=500, {}", message);
-            }
-            other => {
-                anyhow::bail!("StatusCode={other}, {}", message);
-            }
-        }
+        check_status(res).await?;
+        Ok(())
     }
 
     pub async fn send_device_info(
@@ -147,32 +117,21 @@ impl Studio {
         mac_address: String,
         version: String,
         os: String,
+        environment: Environment,
     ) -> anyhow::Result<()> {
         let res = self
             .http_client
-            .send_device_info("/v1/device-info", &project_did, &mac_address, &version, &os)
+            .send_device_info(
+                "/v1/device-info",
+                &project_did,
+                &mac_address,
+                &version,
+                &os,
+                environment,
+            )
             .await?;
-
-        let status = res.status();
-
-        let json: Value = res.json().await.context("Failed to read response body")?;
-        let message = json
-            .get("message")
-            .map(|v| v.to_string())
-            .unwrap_or_default();
-
-        match status {
-            reqwest::StatusCode::OK => Ok(()),
-            reqwest::StatusCode::BAD_REQUEST => {
-                anyhow::bail!("StatusCode=400, {}", message)
-            }
-            reqwest::StatusCode::NOT_FOUND => {
-                anyhow::bail!("StatusCode=404, {}", message)
-            }
-            other => {
-                anyhow::bail!("StatusCode={other}, {}", message);
-            }
-        }
+        check_status(res).await?;
+        Ok(())
     }
 
     pub async fn get_message(&self, project_did: &str) -> anyhow::Result<Vec<MessageResponse>> {
@@ -180,17 +139,11 @@ impl Studio {
             .http_client
             .get_message("/v1/message/list", project_did)
             .await?;
+        let res = check_status(res).await?;
 
-        match res.status() {
-            reqwest::StatusCode::OK => match res.json::<Vec<MessageResponse>>().await {
-                Ok(v) => Ok(v),
-                Err(e) => anyhow::bail!("StatusCode=200, but parse failed. {:?}", e),
-            },
-            reqwest::StatusCode::BAD_REQUEST => match res.json::<ErrorResponse>().await {
-                Ok(v) => anyhow::bail!("StatusCode=400, error message = {:?}", v.message),
-                Err(e) => anyhow::bail!("StatusCode=400, but parse failed. {:?}", e),
-            },
-            other => anyhow::bail!("StatusCode={other}, unexpected response"),
+        match res.json::<Vec<MessageResponse>>().await {
+            Ok(v) => Ok(v),
+            Err(e) => anyhow::bail!("StatusCode=200, but parse failed. {:?}", e),
         }
     }
 
@@ -204,6 +157,7 @@ impl Studio {
             .http_client
             .ack_message("/v1/message/ack", project_did, message_id, is_verified)
             .await?;
+        let res = check_status(res).await?;
 
         res.json::<EmptyResponse>().await?;
         Ok(())
@@ -220,26 +174,20 @@ impl Studio {
             .http_client
             .network("/v1/network", &project_did)
             .await?;
-
-        match res.status() {
-            reqwest::StatusCode::OK => match res.json::<NetworkResponse>().await {
-                Ok(v) => {
-                    let network = crate::network_config();
-                    let mut network = network.lock();
-                    network.save_secret_key(&v.secret_key);
-                    network.save_project_did(&v.project_did);
-                    network.save_recipient_dids(v.recipient_dids);
-                    network.save_studio_endpoint(&v.studio_endpoint);
-                    network.save_heartbeat(v.heartbeat);
-                    Ok(())
-                }
-                Err(e) => anyhow::bail!("StatusCode=200, but parse failed. {:?}", e),
-            },
-            reqwest::StatusCode::BAD_REQUEST => match res.json::<ErrorResponse>().await {
-                Ok(v) => anyhow::bail!("StatusCode=400, error message = {:?}", v.message),
-                Err(e) => anyhow::bail!("StatusCode=400, but parse failed. {:?}", e),
-            },
-            other => anyhow::bail!("StatusCode={other}, unexpected response"),
+        let res = check_status(res).await?;
+
+        match res.json::<NetworkResponse>().await {
+            Ok(v) => {
+                let network = crate::network_config();
+                let mut network = network.lock();
+                network.save_secret_key(&v.secret_key);
+                network.save_project_did(&v.project_did);
+                network.save_recipient_dids(v.recipient_dids);
+                network.save_studio_endpoint(&v.studio_endpoint);
+                network.save_heartbeat(v.heartbeat);
+                Ok(())
+            }
+            Err(e) => anyhow::bail!("StatusCode=200, but parse failed. {:?}", e),
         }
     }
 
@@ -250,50 +198,30 @@ impl Studio {
         request: T,
     ) -> anyhow::Result<()> {
         let my_did = self.did_accessor.get_my_did();
-        let my_keyring = self.did_accessor.get_my_keyring();
+        let my_keyring = self
+            .did_accessor
+            .get_my_keyring()
+            .context("failed to load keyring")?;
         let model =
             VerifiableCredentials::new(my_did, serde_json::to_value(request)?, chrono::Utc::now());
         let payload = DidVcService::generate(&self.did_repository, model, &my_keyring)
             .context("failed to generate payload")?;
         let payload = serde_json::to_string(&payload).context("failed to serialize")?;
 
-        async fn send(
-            studio: &Studio,
-            path: &str,
-            payload: String,
-        ) -> anyhow::Result<(reqwest::StatusCode, String)> {
-            let res = studio.http_client.post(path, &payload).await?;
-
-            let status = res.status();
-            let json: Value = res.json().await.context("Failed to read response body")?;
-            let message = if let Some(message) = json.get("message").map(|v| v.to_string()) {
-                message
-            } else {
-                "".to_string()
-            };
-
-            Ok((status, message))
+        async fn send(studio: &Studio, path: &str, payload: &str) -> Result<(), StudioHttpError> {
+            let res = studio.http_client.post(path, payload).await?;
+            check_status(res).await?;
+            Ok(())
         }
 
-        let (status, message) = send(self, path, payload.clone()).await?;
-
-        match status {
-            reqwest::StatusCode::OK => Ok(()),
-            reqwest::StatusCode::NOT_FOUND => anyhow::bail!("StatusCode=404, {}", message),
-            reqwest::StatusCode::INTERNAL_SERVER_ERROR => {
+        match send(self, path, &payload).await {
+            Err(e) if e.is_retryable() => {
                 // retry once
-                log::info!("failed to send custom_metric: {}, retrying...", message);
-                let (status, message) = send(self, path, payload).await?;
-                match status {
-                    reqwest::StatusCode::OK => Ok(()),
-                    reqwest::StatusCode::NOT_FOUND => anyhow::bail!("StatusCode=404, {}", message),
-                    reqwest::StatusCode::INTERNAL_SERVER_ERROR => {
-                        anyhow::bail!("StatusCode=500, {}", message);
-                    }
-                    other => anyhow::bail!("StatusCode={other}, {}", message),
-                }
+                log::info!("failed to send to {}: {}, retrying...", path, e);
+                send(self, path, &payload).await?;
+                Ok(())
             }
-            other => anyhow::bail!("StatusCode={other}, {}", message),
+            result => Ok(result?),
         }
     }
 }
@@ -312,7 +240,10 @@ impl MessageActivityRepository for Studio {
             network.get_project_did().expect("project_did is not set")
         };
         let my_did = self.did_accessor.get_my_did();
-        let my_keyring = self.did_accessor.get_my_keyring();
+        let my_keyring = self
+            .did_accessor
+            .get_my_keyring()
+            .context("failed to load keyring")?;
 
         let model = VerifiableCredentials::new(my_did, json!(request), request.occurred_at);
         let payload = DidCommEncryptedService::generate(
@@ -330,31 +261,8 @@ impl MessageActivityRepository for Studio {
             .http_client
             .post("/v1/message-activity", &payload)
             .await?;
-
-        let status = res.status();
-        let json: Value = res.json().await.context("Failed to read response body")?;
-        let message = json
-            .get("message")
-            .map(|v| v.to_string())
-            .unwrap_or("".to_string());
-
-        match status {
-            reqwest::StatusCode::OK => Ok(()),
-            reqwest::StatusCode::BAD_REQUEST => Err(MessageActivityHttpError::BadRequest(message)),
-            reqwest::StatusCode::UNAUTHORIZED => {
-                Err(MessageActivityHttpError::Unauthorized(message))
-            }
-            reqwest::StatusCode::FORBIDDEN => Err(MessageActivityHttpError::Forbidden(message)),
-            reqwest::StatusCode::NOT_FOUND => Err(MessageActivityHttpError::NotFound(message)),
-            reqwest::StatusCode::CONFLICT => Err(MessageActivityHttpError::Conflict(message)),
-            reqwest::StatusCode::INTERNAL_SERVER_ERROR => {
-                Err(MessageActivityHttpError::InternalServerError(message))
-            }
-
-            other => Err(MessageActivityHttpError::Other(anyhow::anyhow!(
-                "StatusCode={other}, unexpected response"
-            ))),
-        }
+        check_status(res).await?;
+        Ok(())
     }
 
     async fn add_verify_activity(
@@ -368,7 +276,10 @@ impl MessageActivityRepository for Studio {
             network.get_project_did().expect("project_did is not set")
         };
         let my_did = self.did_accessor.get_my_did();
-        let my_keyring = self.did_accessor.get_my_keyring();
+        let my_keyring = self
+            .did_accessor
+            .get_my_keyring()
+            .context("failed to load keyring")?;
 
         let model = VerifiableCredentials::new(my_did, json!(request), request.verified_at);
         let payload = DidCommEncryptedService::generate(
@@ -386,44 +297,54 @@ impl MessageActivityRepository for Studio {
             .http_client
             .put("/v1/message-activity", &payload)
             .await?;
+        check_status(res).await?;
+        Ok(())
+    }
+}
 
-        let status = res.status();
-        let json: Value = res.json().await.context("Failed to read response body")?;
-        let message = json
-            .get("message")
-            .map(|v| v.to_string())
-            .unwrap_or("".to_string());
-
-        match status {
-            reqwest::StatusCode::OK => Ok(()),
-            reqwest::StatusCode::BAD_REQUEST => Err(MessageActivityHttpError::BadRequest(message)),
-            reqwest::StatusCode::UNAUTHORIZED => {
-                Err(MessageActivityHttpError::Unauthorized(message))
-            }
-            reqwest::StatusCode::FORBIDDEN => Err(MessageActivityHttpError::Forbidden(message)),
-            reqwest::StatusCode::NOT_FOUND => Err(MessageActivityHttpError::NotFound(message)),
-            reqwest::StatusCode::CONFLICT => Err(MessageActivityHttpError::Conflict(message)),
-            reqwest::StatusCode::INTERNAL_SERVER_ERROR => {
-                Err(MessageActivityHttpError::InternalServerError(message))
+impl From<StudioHttpError> for MessageActivityHttpError {
+    fn from(e: StudioHttpError) -> Self {
+        match e {
+            StudioHttpError::BadRequest(error) => Self::BadRequest(error.to_string()),
+            StudioHttpError::Unauthorized(error) => Self::Unauthorized(error.to_string()),
+            StudioHttpError::Forbidden(error) => Self::Forbidden(error.to_string()),
+            StudioHttpError::NotFound(error) => Self::NotFound(error.to_string()),
+            StudioHttpError::Conflict(error) => Self::Conflict(error.to_string()),
+            StudioHttpError::ServerError { error, .. } => {
+                Self::InternalServerError(error.to_string())
             }
-            other => Err(MessageActivityHttpError::Other(anyhow::anyhow!(
-                "StatusCode={other}, unexpected response"
-            ))),
+            e @ StudioHttpError::Unexpected { .. } => Self::Other(e.into()),
+            StudioHttpError::Other(e) => Self::Other(e),
         }
     }
 }
 
+#[derive(Serialize)]
+struct LabeledMetrics<'a> {
+    #[serde(flatten)]
+    metrics: &'a MetricsWithTimestamp,
+    environment: Environment,
+}
+
 impl MetricStoreRepository for Studio {
     async fn save(&self, request: VecDeque<MetricsWithTimestamp>) -> anyhow::Result<()> {
+        let environment = server_config().environment();
         let mut metrics = request;
         while !metrics.is_empty() {
             let my_did = self.did_accessor.get_my_did();
-            let my_keyring = self.did_accessor.get_my_keyring();
+            let my_keyring = self
+                .did_accessor
+                .get_my_keyring()
+                .context("failed to load keyring")?;
             let mut metrics_str = Vec::new();
             let mut current_size = 0;
 
             while let Some(m) = metrics.pop_front() {
-                let item_size = serde_json::to_string(&m)?.len();
+                let item = serde_json::to_value(LabeledMetrics {
+                    metrics: &m,
+                    environment,
+                })?;
+                let item_size = item.to_string().len();
                 if item_size > JSON_BODY_MAX_SIZE {
                     anyhow::bail!("invalid item size: JSON body size too large")
                 }
@@ -432,7 +353,7 @@ impl MetricStoreRepository for Studio {
                     break;
                 }
                 current_size += item_size;
-                metrics_str.push(m);
+                metrics_str.push(item);
             }
 
             let model = VerifiableCredentials::new(my_did, json!(metrics_str), chrono::Utc::now());
@@ -441,22 +362,7 @@ impl MetricStoreRepository for Studio {
 
             let payload = serde_json::to_string(&payload).context("failed to serialize")?;
             let res = self.http_client.post("/v1/metrics", &payload).await?;
-
-            let status = res.status();
-            let json: Value = res.json().await.context("Failed to read response body")?;
-            let message = if let Some(message) = json.get("message").map(|v| v.to_string()) {
-                message
-            } else {
-                "".to_string()
-            };
-            match status {
-                reqwest::StatusCode::OK => continue,
-                reqwest::StatusCode::NOT_FOUND => anyhow::bail!("StatusCode=404, {}", message),
-                reqwest::StatusCode::INTERNAL_SERVER_ERROR => {
-                    anyhow::bail!("StatusCode=500, {}", message);
-                }
-                other => anyhow::bail!("StatusCode={other}, {}", message),
-            }
+            check_status(res).await?;
         }
 
         Ok(())
@@ -480,3 +386,67 @@ impl AttributeStoreRepository for Studio {
         self.relay_to_studio("/v1/tag-values", request).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodex::utils::studio_client::StudioError;
+    use crate::repository::metric_repository::{Metric, MetricType};
+
+    #[test]
+    fn test_labeled_metrics_include_environment() {
+        let metrics = MetricsWithTimestamp {
+            timestamp: chrono::Utc::now(),
+            metrics: vec![Metric {
+                metric_type: MetricType::CpuUsage,
+                value: 1.0,
+                label: None,
+            }],
+        };
+
+        let value = serde_json::to_value(LabeledMetrics {
+            metrics: &metrics,
+            environment: Environment::Staging,
+        })
+        .unwrap();
+
+        assert_eq!(value["environment"], "staging");
+        assert_eq!(value["metrics"][0]["metric_type"], "cpu_usage");
+    }
+
+    #[test]
+    fn test_message_activity_error_from_studio_error() {
+        let error: MessageActivityHttpError = StudioHttpError::Conflict(StudioError {
+            code: Some("duplicated".into()),
+            message: "dup".into(),
+        })
+        .into();
+        assert!(matches!(
+            error,
+            MessageActivityHttpError::Conflict(message) if message == "dup (duplicated)"
+        ));
+
+        let error: MessageActivityHttpError = StudioHttpError::ServerError {
+            status: reqwest::StatusCode::BAD_GATEWAY,
+            error: StudioError {
+                code: None,
+                message: "down".into(),
+            },
+        }
+        .into();
+        assert!(matches!(
+            error,
+            MessageActivityHttpError::InternalServerError(message) if message == "down"
+        ));
+
+        let error: MessageActivityHttpError = StudioHttpError::Unexpected {
+            status: reqwest::StatusCode::TOO_MANY_REQUESTS,
+            error: StudioError {
+                code: None,
+                message: "slow down".into(),
+            },
+        }
+        .into();
+        assert!(matches!(error, MessageActivityHttpError::Other(_)));
+    }
+}
@@ -0,0 +1,121 @@
+use std::future::Future;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ShutdownOutcome {
+    Completed,
+    TimedOut,
+}
+
+// NOTE: Owns the long-running subsystems of the agent. A single token is
+// broadcast to every subsystem, and `join` waits for them to finish after it
+// has been cancelled.
+pub struct ShutdownCoordinator {
+    token: CancellationToken,
+    tasks: JoinSet<()>,
+}
+
+impl Default for ShutdownCoordinator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShutdownCoordinator {
+    pub fn new() -> Self {
+        ShutdownCoordinator {
+            token: CancellationToken::new(),
+            tasks: JoinSet::new(),
+        }
+    }
+
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    pub fn spawn<F, Fut>(&mut self, name: &'static str, f: F)
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let task = f(self.token.clone());
+        self.tasks.spawn(async move {
+            task.await;
+            log::info!("{} stopped", name);
+        });
+    }
+
+    #[cfg(test)]
+    pub fn shutdown(&self) {
+        self.token.cancel();
+    }
+
+    pub async fn join(mut self, timeout: Duration) -> ShutdownOutcome {
+        loop {
+            tokio::select! {
+                _ = self.token.cancelled() => break,
+                res = self.tasks.join_next() => {
+                    if res.is_none() {
+                        return ShutdownOutcome::Completed;
+                    }
+                }
+            }
+        }
+        match tokio::time::timeout(timeout, async {
+            while self.tasks.join_next().await.is_some() {}
+        })
+        .await
+        {
+            Ok(()) => ShutdownOutcome::Completed,
+            Err(_) => {
+                log::warn!(
+                    "{} subsystem(s) did not stop within {:?}",
+                    self.tasks.len(),
+                    timeout
+                );
+                ShutdownOutcome::TimedOut
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_all_subsystems_receive_shutdown() {
+        let mut coordinator = ShutdownCoordinator::new();
+        let stopped = Arc::new(AtomicUsize::new(0));
+        for name in ["a", "b", "c"] {
+            let stopped = stopped.clone();
+            coordinator.spawn(name, move |token| async move {
+                token.cancelled().await;
+                stopped.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        coordinator.shutdown();
+        let outcome = coordinator.join(Duration::from_secs(1)).await;
+
+        assert_eq!(outcome, ShutdownOutcome::Completed);
+        assert_eq!(stopped.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_join_times_out_on_stuck_subsystem() {
+        let mut coordinator = ShutdownCoordinator::new();
+        coordinator.spawn("stuck", |_| async {
+            std::future::pending::<()>().await;
+        });
+
+        coordinator.shutdown();
+        let outcome = coordinator.join(Duration::from_millis(50)).await;
+
+        assert_eq!(outcome, ShutdownOutcome::TimedOut);
+    }
+}
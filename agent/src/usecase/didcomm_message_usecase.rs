@@ -10,11 +10,12 @@ use protocol::{
 };
 
 use crate::{
-    nodex::utils::did_accessor::DidAccessor,
+    nodex::{keyring::keypair::KeyPairingError, utils::did_accessor::DidAccessor},
     repository::message_activity_repository::{
         CreatedMessageActivityRequest, MessageActivityRepository, VerifiedMessageActivityRequest,
         VerifiedStatus,
     },
+    usecase::verify_policy::{VerifyPolicy, VerifyPolicyViolation},
 };
 
 pub struct DidcommMessageUseCase<R, D, A>
@@ -26,6 +27,7 @@ where
     message_activity_repository: R,
     didcomm_service: D,
     did_accessor: A,
+    verify_policy: VerifyPolicy,
 }
 
 #[derive(Debug, Error)]
@@ -38,6 +40,8 @@ where
     ServiceGenerate(E),
     #[error("message activity error: {0}")]
     MessageActivity(F),
+    #[error("failed to load keyring: {0}")]
+    Keyring(#[from] KeyPairingError),
     #[error("failed serialize/deserialize : {0}")]
     Json(#[from] serde_json::Error),
 }
@@ -55,6 +59,10 @@ where
     NotAddressedToMe,
     #[error("message activity error: {0}")]
     MessageActivity(F),
+    #[error("verify policy violation: {0}")]
+    PolicyViolation(#[from] VerifyPolicyViolation),
+    #[error("failed to load keyring: {0}")]
+    Keyring(#[from] KeyPairingError),
     #[error("failed serialize/deserialize : {0}")]
     Json(#[from] serde_json::Error),
 }
@@ -70,9 +78,15 @@ where
             message_activity_repository,
             didcomm_service,
             did_accessor,
+            verify_policy: VerifyPolicy::default(),
         }
     }
 
+    pub fn with_verify_policy(mut self, verify_policy: VerifyPolicy) -> Self {
+        self.verify_policy = verify_policy;
+        self
+    }
+
     pub async fn generate(
         &self,
         destination_did: String,
@@ -89,16 +103,12 @@ where
         };
         let message = serde_json::to_value(message)?;
         let my_did = self.did_accessor.get_my_did();
+        let my_keyring = self.did_accessor.get_my_keyring()?;
 
         let model = VerifiableCredentials::new(my_did.clone(), message, now);
         let didcomm_message = self
             .didcomm_service
-            .generate(
-                model,
-                &self.did_accessor.get_my_keyring(),
-                &destination_did,
-                None,
-            )
+            .generate(model, &my_keyring, &destination_did, None)
             .await
             .map_err(GenerateDidcommMessageUseCaseError::ServiceGenerate)?;
 
@@ -129,12 +139,14 @@ where
         if !message.find_receivers().contains(&my_did) {
             return Err(VerifyDidcommMessageUseCaseError::NotAddressedToMe);
         }
+        let my_keyring = self.did_accessor.get_my_keyring()?;
         let verified = self
             .didcomm_service
-            .verify(&self.did_accessor.get_my_keyring(), &message)
+            .verify(&my_keyring, &message)
             .await
             .map_err(VerifyDidcommMessageUseCaseError::ServiceVerify)?;
         let verified = verified.message;
+        self.verify_policy.check(&verified, true, now)?;
         let from_did = verified.issuer.id.clone();
         // check in verified. maybe exists?
         let container = verified.clone().credential_subject.container;
@@ -317,6 +329,35 @@ mod tests {
             }
         }
 
+        #[tokio::test]
+        async fn test_verify_policy_rejects_unknown_sender() {
+            let presets = TestPresets::default();
+            let generated = create_test_message_for_verify_test(presets.clone()).await;
+            let generated = serde_json::from_str::<DidCommMessage>(&generated).unwrap();
+
+            let usecase = DidcommMessageUseCase::new(
+                MockMessageActivityRepository::verify_success(),
+                presets.create_mock_did_repository(),
+                MockDidAccessor::new(presets.to_did, presets.to_keyring),
+            )
+            .with_verify_policy(VerifyPolicy {
+                require_encrypted: true,
+                allowed_senders: Some(vec!["did:example:other".to_string()]),
+                ..Default::default()
+            });
+
+            let verified = usecase.verify(generated, Utc::now()).await;
+
+            if let Err(VerifyDidcommMessageUseCaseError::PolicyViolation(
+                VerifyPolicyViolation::SenderNotAllowed(sender),
+            )) = verified
+            {
+                assert_eq!(sender, presets.from_did);
+            } else {
+                panic!("unexpected result: {:?}", verified);
+            }
+        }
+
         #[tokio::test]
         async fn test_verify_add_activity_failed() {
             let presets = TestPresets::default();
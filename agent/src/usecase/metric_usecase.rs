@@ -16,6 +16,9 @@ where
     config: Box<SingletonAppConfig>,
     cache_repository: C,
     shutdown_token: CancellationToken,
+    // NOTE: Cancelled once the collector has made its final collection, so that the
+    // sender's final flush includes it.
+    collection_finished: CancellationToken,
 }
 
 impl<S, W, C> MetricUsecase<S, W, C>
@@ -30,6 +33,7 @@ where
         config: Box<SingletonAppConfig>,
         cache_repository: C,
         shutdown_token: CancellationToken,
+        collection_finished: CancellationToken,
     ) -> Self {
         MetricUsecase {
             store_repository,
@@ -37,59 +41,84 @@ where
             config,
             cache_repository,
             shutdown_token,
+            collection_finished,
         }
     }
 
     pub async fn collect_task(&mut self) {
+        let _finished = self.collection_finished.clone().drop_guard();
         let interval_time: u64 = self.config.lock().get_metric_collect_interval();
         let mut interval = tokio::time::interval(Duration::from_secs(interval_time));
         loop {
             tokio::select! {
                 _ = interval.tick() => {
-                    let metrics = self.watch_repository.watch_metrics();
-                    for metric in metrics {
-                        self.cache_repository.push(chrono::Utc::now(), vec![metric]).await;
-                    }
+                    self.collect().await;
                     log::info!("collected metrics");
                 }
                 _ = self.shutdown_token.cancelled() => {
+                    // NOTE: Collect once more so that a clean stop does not drop the
+                    // partial interval since the last tick.
+                    self.collect().await;
+                    log::info!("collected final metrics before shutdown");
                     break;
                 },
             }
         }
     }
 
+    async fn collect(&mut self) {
+        let metrics = self.watch_repository.watch_metrics();
+        for metric in metrics {
+            self.cache_repository
+                .push(chrono::Utc::now(), vec![metric])
+                .await;
+        }
+    }
+
     pub async fn send_task(&mut self) {
         let interval_time: u64 = self.config.lock().get_metric_send_interval();
         let mut interval = tokio::time::interval(Duration::from_secs(interval_time));
         loop {
             tokio::select! {
                 _ = interval.tick() => {
-                    let metrics_with_timestamp_list = self.cache_repository.get().await;
-
-                    if metrics_with_timestamp_list.is_empty() {
-                        continue;
-                    }
-
-                    match self.store_repository.save(metrics_with_timestamp_list).await {
-                        Ok(_) => {
-                            self.cache_repository.clear().await;
-                            log::info!("sent metrics");
-                        },
-                        Err(e) => log::error!("failed to send metric{:?}", e),
-                    }
+                    self.send().await;
                 }
                 _ = self.shutdown_token.cancelled() => {
+                    // NOTE: Flush what is still cached so that it is not lost on shutdown,
+                    // including the collector's final collection.
+                    self.collection_finished.cancelled().await;
+                    self.send().await;
                     break;
                 },
             }
         }
     }
+
+    async fn send(&mut self) {
+        let metrics_with_timestamp_list = self.cache_repository.get().await;
+
+        if metrics_with_timestamp_list.is_empty() {
+            return;
+        }
+
+        match self
+            .store_repository
+            .save(metrics_with_timestamp_list)
+            .await
+        {
+            Ok(_) => {
+                self.cache_repository.clear().await;
+                log::info!("sent metrics");
+            }
+            Err(e) => log::error!("failed to send metric{:?}", e),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
 
     use super::*;
     use crate::services::metrics::MetricsInMemoryCacheService;
@@ -108,6 +137,18 @@ mod tests {
         }
     }
 
+    #[derive(Clone, Default)]
+    pub struct RecordingMetricStoreRepository {
+        saved: Arc<Mutex<Vec<VecDeque<MetricsWithTimestamp>>>>,
+    }
+
+    impl MetricStoreRepository for RecordingMetricStoreRepository {
+        async fn save(&self, request: VecDeque<MetricsWithTimestamp>) -> anyhow::Result<()> {
+            self.saved.lock().unwrap().push(request);
+            Ok(())
+        }
+    }
+
     pub struct MockMetricWatchRepository {}
 
     impl MetricsWatchRepository for MockMetricWatchRepository {
@@ -116,10 +157,12 @@ mod tests {
                 Metric {
                     metric_type: MetricType::CpuUsage,
                     value: 0.0,
+                    label: None,
                 },
                 Metric {
                     metric_type: MetricType::MemoryUsage,
                     value: 0.0,
+                    label: None,
                 },
             ]
         }
@@ -129,15 +172,20 @@ mod tests {
     async fn test_collect_task() {
         let token = CancellationToken::new();
         let cloned_token = token.clone();
+        let mut cache_repository = MetricsInMemoryCacheService::new(1 << 16);
         let mut usecase = MetricUsecase {
             store_repository: MockMetricStoreRepository {},
             watch_repository: MockMetricWatchRepository {},
             config: app_config(),
-            cache_repository: MetricsInMemoryCacheService::new(1 << 16),
+            cache_repository: cache_repository.clone(),
             shutdown_token: cloned_token,
+            collection_finished: CancellationToken::new(),
         };
         token.cancel();
         usecase.collect_task().await;
+
+        // NOTE: The interval fires immediately, so there is at least the shutdown collection.
+        assert!(cache_repository.get().await.len() >= 2);
     }
 
     #[tokio::test]
@@ -150,8 +198,83 @@ mod tests {
             config: app_config(),
             cache_repository: MetricsInMemoryCacheService::new(1 << 16),
             shutdown_token: cloned_token,
+            collection_finished: CancellationToken::new(),
         };
         token.cancel();
+        usecase.collection_finished.cancel();
         usecase.send_task().await;
     }
+
+    #[tokio::test]
+    async fn test_send_task_flushes_cache_on_shutdown() {
+        let token = CancellationToken::new();
+        let store_repository = RecordingMetricStoreRepository::default();
+        let mut cache_repository = MetricsInMemoryCacheService::new(1 << 16);
+        let mut usecase = MetricUsecase {
+            store_repository: store_repository.clone(),
+            watch_repository: MockMetricWatchRepository {},
+            config: app_config(),
+            cache_repository: cache_repository.clone(),
+            shutdown_token: token.clone(),
+            collection_finished: CancellationToken::new(),
+        };
+        usecase.collection_finished.cancel();
+
+        // NOTE: The metrics are cached after the first tick, so only the final send on
+        // shutdown can deliver them.
+        let shutdown = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            cache_repository
+                .push(
+                    chrono::Utc::now(),
+                    MockMetricWatchRepository {}.watch_metrics(),
+                )
+                .await;
+            token.cancel();
+        };
+        tokio::join!(usecase.send_task(), shutdown);
+
+        let saved = store_repository.saved.lock().unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].len(), 1);
+        assert_eq!(saved[0][0].metrics.len(), 2);
+        drop(saved);
+        assert!(cache_repository.get().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_final_collection_is_sent_on_shutdown() {
+        let token = CancellationToken::new();
+        let collection_finished = CancellationToken::new();
+        let store_repository = RecordingMetricStoreRepository::default();
+        let mut cache_repository = MetricsInMemoryCacheService::new(1 << 16);
+        let mut collector = MetricUsecase::new(
+            MockMetricStoreRepository {},
+            MockMetricWatchRepository {},
+            app_config(),
+            cache_repository.clone(),
+            token.clone(),
+            collection_finished.clone(),
+        );
+        let mut sender = MetricUsecase::new(
+            store_repository.clone(),
+            MockMetricWatchRepository {},
+            app_config(),
+            cache_repository.clone(),
+            token.clone(),
+            collection_finished,
+        );
+
+        let shutdown = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            token.cancel();
+        };
+        tokio::join!(collector.collect_task(), sender.send_task(), shutdown);
+
+        // NOTE: One collection on the first tick and one on shutdown, two metrics each.
+        let saved = store_repository.saved.lock().unwrap();
+        assert_eq!(saved.iter().map(VecDeque::len).sum::<usize>(), 4);
+        drop(saved);
+        assert!(cache_repository.get().await.is_empty());
+    }
 }
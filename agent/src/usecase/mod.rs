@@ -4,6 +4,7 @@ pub mod didcomm_message_usecase;
 pub mod event_usecase;
 pub mod metric_usecase;
 pub mod verifiable_message_usecase;
+pub mod verify_policy;
 
 #[cfg(test)]
 mod test_util {
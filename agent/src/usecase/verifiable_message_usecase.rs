@@ -1,6 +1,9 @@
+use crate::nodex::keyring::keypair::KeyPairingError;
 use crate::nodex::utils::did_accessor::DidAccessor;
 use crate::repository::message_activity_repository::*;
+use crate::usecase::verify_policy::{VerifyPolicy, VerifyPolicyViolation};
 use chrono::DateTime;
+use chrono::Duration;
 use chrono::Utc;
 use protocol::{
     did::did_repository::DidRepository,
@@ -21,6 +24,7 @@ where
     vc_service: S,
     message_activity_repository: R,
     did_accessor: A,
+    verify_policy: VerifyPolicy,
 }
 
 #[derive(Debug, Error)]
@@ -36,6 +40,8 @@ where
     MessageActivity(F),
     #[error("destination did not found")]
     DestinationNotFound(Option<D>),
+    #[error("failed to load keyring: {0}")]
+    Keyring(#[from] KeyPairingError),
     #[error("failed serialize/deserialize : {0}")]
     Json(#[from] serde_json::Error),
 }
@@ -52,6 +58,12 @@ where
     MessageActivity(F),
     #[error("This message is not addressed to me")]
     NotAddressedToMe,
+    #[error("signature key does not belong to the issuer")]
+    VerificationFailed,
+    #[error("This message has expired")]
+    Expired,
+    #[error("verify policy violation: {0}")]
+    PolicyViolation(#[from] VerifyPolicyViolation),
     #[error("failed serialize/deserialize : {0}")]
     Json(#[from] serde_json::Error),
 }
@@ -74,14 +86,21 @@ where
             vc_service,
             message_activity_repository,
             did_accessor,
+            verify_policy: VerifyPolicy::default(),
         }
     }
+
+    pub fn with_verify_policy(mut self, verify_policy: VerifyPolicy) -> Self {
+        self.verify_policy = verify_policy;
+        self
+    }
     pub async fn generate(
         &self,
         destination_did: String,
         message: String,
         operation_tag: String,
         now: DateTime<Utc>,
+        ttl: Option<Duration>,
     ) -> Result<
         String,
         CreateVerifiableMessageUseCaseError<D::FindIdentifierError, S::GenerateError, R::Error>,
@@ -100,13 +119,15 @@ where
             payload: message,
             destination_did: destination_did.clone(),
             created_at: now.to_rfc3339(),
+            expires_at: ttl.map(|ttl| now + ttl),
         };
 
         let message = serde_json::to_value(message)?;
+        let my_keyring = self.did_accessor.get_my_keyring()?;
         let model = VerifiableCredentials::new(my_did.clone(), message, now);
         let vc = self
             .vc_service
-            .generate(model, &self.did_accessor.get_my_keyring())
+            .generate(model, &my_keyring)
             .map_err(CreateVerifiableMessageUseCaseError::DidVcServiceGenerate)?;
 
         let result = serde_json::to_string(&vc)?;
@@ -131,6 +152,18 @@ where
         now: DateTime<Utc>,
     ) -> Result<VerifiableCredentials, VerifyVerifiableMessageUseCaseError<S::VerifyError, R::Error>>
     {
+        // NOTE: The signature is checked against the issuer's key, so the proof must also
+        // claim a key of the issuer; otherwise the recorded sender could be forged.
+        if let Some(proof) = &message.proof {
+            let (controller, _) = proof
+                .verification_method
+                .split_once('#')
+                .unwrap_or((&proof.verification_method, ""));
+            if controller != message.issuer.id {
+                return Err(VerifyVerifiableMessageUseCaseError::VerificationFailed);
+            }
+        }
+
         let vc = self
             .vc_service
             .verify(message)
@@ -147,6 +180,15 @@ where
             return Err(VerifyVerifiableMessageUseCaseError::NotAddressedToMe);
         }
 
+        if message
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= now)
+        {
+            return Err(VerifyVerifiableMessageUseCaseError::Expired);
+        }
+
+        self.verify_policy.check(&vc, false, now)?;
+
         self.message_activity_repository
             .add_verify_activity(VerifiedMessageActivityRequest {
                 from: from_did,
@@ -167,6 +209,9 @@ struct EncodedMessage {
     pub payload: String,
     pub destination_did: String,
     pub created_at: String,
+    // NOTE: Messages generated without a TTL never expire; older messages lack the field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 #[cfg(test)]
@@ -176,7 +221,10 @@ pub mod tests {
     use crate::repository::did_repository::mocks::MockDidRepository;
 
     use crate::usecase::test_util::*;
-    use protocol::verifiable_credentials::did_vc::DidVcServiceVerifyError;
+    use protocol::verifiable_credentials::{
+        credential_signer::{CredentialSigner, CredentialSignerSuite},
+        did_vc::DidVcServiceVerifyError,
+    };
     use serde_json::Value;
     use tests::mocks::MockMessageActivityRepository;
 
@@ -201,6 +249,7 @@ pub mod tests {
                 message.clone(),
                 "test".to_string(),
                 now,
+                None,
             )
             .await
             .unwrap();
@@ -236,6 +285,48 @@ pub mod tests {
         assert_eq!(encoded_message.payload, message);
     }
 
+    #[tokio::test]
+    async fn test_verify_before_expiry() {
+        let presets = TestPresets::default();
+        let repository = presets.create_mock_did_repository();
+
+        let usecase = VerifiableMessageUseCase::new(
+            MockMessageActivityRepository::create_success(),
+            repository.clone(),
+            MockDidAccessor::new(presets.from_did, presets.from_keyring.clone()),
+            repository.clone(),
+        );
+        let now = Utc::now();
+        let generated = usecase
+            .generate(
+                presets.to_did.clone(),
+                "Hello".to_string(),
+                "test".to_string(),
+                now,
+                Some(Duration::minutes(5)),
+            )
+            .await
+            .unwrap();
+
+        let result: Value = serde_json::from_str(&generated).unwrap();
+        let expires_at = result["credentialSubject"]["container"]["expires_at"]
+            .as_str()
+            .unwrap()
+            .parse::<DateTime<Utc>>()
+            .unwrap();
+        assert_eq!(expires_at, now + Duration::minutes(5));
+
+        let usecase = VerifiableMessageUseCase::new(
+            MockMessageActivityRepository::verify_success(),
+            repository.clone(),
+            MockDidAccessor::new(presets.to_did, presets.to_keyring),
+            repository.clone(),
+        );
+        let generated = serde_json::from_str::<VerifiableCredentials>(&generated).unwrap();
+        let verified = usecase.verify(generated, now + Duration::minutes(4)).await;
+        assert!(verified.is_ok(), "unexpected result: {:?}", verified);
+    }
+
     mod generate_failed {
         use crate::nodex::utils::did_accessor::mocks::MockDidAccessor;
 
@@ -256,7 +347,7 @@ pub mod tests {
 
             let now = Utc::now();
             let generated = usecase
-                .generate(presets.to_did, message, "test".to_string(), now)
+                .generate(presets.to_did, message, "test".to_string(), now, None)
                 .await;
 
             if let Err(CreateVerifiableMessageUseCaseError::DestinationNotFound(_)) = generated {
@@ -281,7 +372,7 @@ pub mod tests {
 
             let now = Utc::now();
             let generated = usecase
-                .generate(presets.to_did, message, "test".to_string(), now)
+                .generate(presets.to_did, message, "test".to_string(), now, None)
                 .await;
 
             if let Err(CreateVerifiableMessageUseCaseError::MessageActivity(_)) = generated {
@@ -315,6 +406,7 @@ pub mod tests {
                     message.clone(),
                     "test".to_string(),
                     now,
+                    None,
                 )
                 .await
                 .unwrap();
@@ -363,6 +455,36 @@ pub mod tests {
             }
         }
 
+        #[tokio::test]
+        async fn test_verify_policy_requires_encryption() {
+            let presets = TestPresets::default();
+            let repository = presets.create_mock_did_repository();
+
+            let generated = create_test_message_for_verify_test(presets.clone()).await;
+
+            let usecase = VerifiableMessageUseCase::new(
+                MockMessageActivityRepository::verify_success(),
+                repository.clone(),
+                MockDidAccessor::new(presets.to_did, presets.to_keyring),
+                repository.clone(),
+            )
+            .with_verify_policy(VerifyPolicy {
+                require_encrypted: true,
+                ..Default::default()
+            });
+
+            let generated = serde_json::from_str::<VerifiableCredentials>(&generated).unwrap();
+            let verified = usecase.verify(generated, Utc::now()).await;
+
+            if let Err(VerifyVerifiableMessageUseCaseError::PolicyViolation(
+                VerifyPolicyViolation::NotEncrypted,
+            )) = verified
+            {
+            } else {
+                panic!("unexpected result: {:?}", verified);
+            }
+        }
+
         #[tokio::test]
         async fn test_verify_did_not_found() {
             let presets = TestPresets::default();
@@ -389,6 +511,89 @@ pub mod tests {
             }
         }
 
+        #[tokio::test]
+        async fn test_verify_expired() {
+            let presets = TestPresets::default();
+            let repository = presets.create_mock_did_repository();
+
+            let usecase = VerifiableMessageUseCase::new(
+                MockMessageActivityRepository::create_success(),
+                repository.clone(),
+                MockDidAccessor::new(presets.from_did.clone(), presets.from_keyring.clone()),
+                repository.clone(),
+            );
+            let now = Utc::now();
+            let generated = usecase
+                .generate(
+                    presets.to_did.clone(),
+                    "Hello".to_string(),
+                    "test".to_string(),
+                    now,
+                    Some(Duration::minutes(5)),
+                )
+                .await
+                .unwrap();
+            let generated = serde_json::from_str::<VerifiableCredentials>(&generated).unwrap();
+
+            // Verified exactly at `expires_at`, which already counts as expired. The activity
+            // repository fails so that reaching it would surface as `MessageActivity` instead.
+            let usecase = VerifiableMessageUseCase::new(
+                MockMessageActivityRepository::verify_fail(),
+                repository.clone(),
+                MockDidAccessor::new(presets.to_did, presets.to_keyring),
+                repository.clone(),
+            );
+            let verified = usecase.verify(generated, now + Duration::minutes(5)).await;
+
+            if let Err(VerifyVerifiableMessageUseCaseError::Expired) = verified {
+            } else {
+                panic!("unexpected result: {:?}", verified);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_verify_issuer_not_signer() {
+            let presets = TestPresets::default();
+            let repository = presets.create_mock_did_repository();
+
+            let message = EncodedMessage {
+                message_id: Uuid::new_v4(),
+                payload: "Hello".to_string(),
+                destination_did: presets.to_did.clone(),
+                created_at: Utc::now().to_rfc3339(),
+            };
+            // Claims to be issued by `to_did` but is signed with the key of `from_did`.
+            let model = VerifiableCredentials::new(
+                presets.to_did.clone(),
+                serde_json::to_value(message).unwrap(),
+                Utc::now(),
+            );
+            let forged = CredentialSigner::sign(
+                model,
+                CredentialSignerSuite {
+                    did: &presets.from_did,
+                    key_id: "signingKey",
+                    context: &presets.from_keyring.sign,
+                },
+            )
+            .unwrap();
+
+            // A failing activity repository makes sure no activity is recorded before rejecting.
+            let usecase = VerifiableMessageUseCase::new(
+                MockMessageActivityRepository::verify_fail(),
+                repository.clone(),
+                MockDidAccessor::new(presets.to_did, presets.to_keyring),
+                repository.clone(),
+            );
+
+            let verified = usecase.verify(forged, Utc::now()).await;
+
+            if let Err(VerifyVerifiableMessageUseCaseError::VerificationFailed) = verified {
+            } else {
+                panic!("unexpected result: {:?}", verified);
+            }
+        }
+
         #[tokio::test]
         async fn test_verify_add_activity_failed() {
             let presets = TestPresets::default();
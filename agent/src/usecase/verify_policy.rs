@@ -0,0 +1,184 @@
+use chrono::{DateTime, Utc};
+use protocol::verifiable_credentials::{jws, types::VerifiableCredentials};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+// NOTE: Minimum requirements for inbound messages. Every requirement is
+// disabled by default, which keeps the previous behaviour.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct VerifyPolicy {
+    pub require_encrypted: bool,
+    pub max_age_seconds: Option<u64>,
+    pub allowed_senders: Option<Vec<String>>,
+    pub allowed_algorithms: Option<Vec<String>>,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum VerifyPolicyViolation {
+    #[error("message must be encrypted")]
+    NotEncrypted,
+    #[error("message is too old: issued {age_seconds}s ago, allowed {max_age_seconds}s")]
+    Stale {
+        age_seconds: i64,
+        max_age_seconds: u64,
+    },
+    #[error("sender is not allowed: {0}")]
+    SenderNotAllowed(String),
+    #[error("proof not found")]
+    ProofNotFound,
+    #[error("signature algorithm is not allowed: {0}")]
+    AlgorithmNotAllowed(String),
+}
+
+impl VerifyPolicy {
+    pub fn check(
+        &self,
+        vc: &VerifiableCredentials,
+        is_encrypted: bool,
+        now: DateTime<Utc>,
+    ) -> Result<(), VerifyPolicyViolation> {
+        if self.require_encrypted && !is_encrypted {
+            return Err(VerifyPolicyViolation::NotEncrypted);
+        }
+
+        if let Some(max_age_seconds) = self.max_age_seconds {
+            let age_seconds = (now - vc.issuance_date).num_seconds();
+            if age_seconds > max_age_seconds as i64 {
+                return Err(VerifyPolicyViolation::Stale {
+                    age_seconds,
+                    max_age_seconds,
+                });
+            }
+        }
+
+        if let Some(allowed_senders) = &self.allowed_senders {
+            if !allowed_senders.contains(&vc.issuer.id) {
+                return Err(VerifyPolicyViolation::SenderNotAllowed(
+                    vc.issuer.id.clone(),
+                ));
+            }
+        }
+
+        if let Some(allowed_algorithms) = &self.allowed_algorithms {
+            let proof = vc
+                .proof
+                .as_ref()
+                .ok_or(VerifyPolicyViolation::ProofNotFound)?;
+            // NOTE: The signature itself has already been verified, so a header
+            // that cannot be decoded is treated the same as an unknown algorithm.
+            let algorithm = jws::algorithm(&proof.jws).unwrap_or_default();
+            if !allowed_algorithms.contains(&algorithm) {
+                return Err(VerifyPolicyViolation::AlgorithmNotAllowed(algorithm));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use protocol::keyring::keypair::KeyPairing;
+    use protocol::rand_core::OsRng;
+    use protocol::verifiable_credentials::did_vc::DidVcService;
+    use serde_json::json;
+
+    use crate::repository::did_repository::mocks::MockDidRepository;
+
+    const SENDER: &str = "did:example:from";
+
+    fn signed_vc(issued_at: DateTime<Utc>) -> VerifiableCredentials {
+        let model = VerifiableCredentials::new(SENDER.to_string(), json!({}), issued_at);
+        MockDidRepository::empty()
+            .generate(model, &KeyPairing::create_keyring(OsRng))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_default_policy_accepts_everything() {
+        let vc = signed_vc(Utc::now() - Duration::days(365));
+        assert!(VerifyPolicy::default()
+            .check(&vc, false, Utc::now())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_require_encrypted() {
+        let policy = VerifyPolicy {
+            require_encrypted: true,
+            ..Default::default()
+        };
+        let vc = signed_vc(Utc::now());
+
+        assert!(policy.check(&vc, true, Utc::now()).is_ok());
+        assert_eq!(
+            policy.check(&vc, false, Utc::now()),
+            Err(VerifyPolicyViolation::NotEncrypted)
+        );
+    }
+
+    #[test]
+    fn test_require_fresh() {
+        let policy = VerifyPolicy {
+            max_age_seconds: Some(60),
+            ..Default::default()
+        };
+        let now = Utc::now();
+
+        assert!(policy
+            .check(&signed_vc(now - Duration::seconds(30)), false, now)
+            .is_ok());
+        assert_eq!(
+            policy.check(&signed_vc(now - Duration::seconds(90)), false, now),
+            Err(VerifyPolicyViolation::Stale {
+                age_seconds: 90,
+                max_age_seconds: 60,
+            })
+        );
+    }
+
+    #[test]
+    fn test_require_allowed_sender() {
+        let vc = signed_vc(Utc::now());
+
+        let policy = VerifyPolicy {
+            allowed_senders: Some(vec![SENDER.to_string()]),
+            ..Default::default()
+        };
+        assert!(policy.check(&vc, false, Utc::now()).is_ok());
+
+        let policy = VerifyPolicy {
+            allowed_senders: Some(vec!["did:example:other".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            policy.check(&vc, false, Utc::now()),
+            Err(VerifyPolicyViolation::SenderNotAllowed(SENDER.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_require_allowed_algorithm() {
+        let vc = signed_vc(Utc::now());
+
+        let policy = VerifyPolicy {
+            allowed_algorithms: Some(vec!["ES256K".to_string()]),
+            ..Default::default()
+        };
+        assert!(policy.check(&vc, false, Utc::now()).is_ok());
+
+        let policy = VerifyPolicy {
+            allowed_algorithms: Some(vec!["EdDSA".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            policy.check(&vc, false, Utc::now()),
+            Err(VerifyPolicyViolation::AlgorithmNotAllowed(
+                "ES256K".to_string()
+            ))
+        );
+    }
+}
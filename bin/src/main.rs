@@ -60,6 +60,9 @@ fn main() {
         } else {
             agent::cli::AgentOptions::default()
         };
-        let _ = agent::run(controlled, &options);
+        if let Err(e) = agent::run(controlled, &options) {
+            log::error!("{}", e);
+            std::process::exit(1);
+        }
     }
 }
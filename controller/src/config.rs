@@ -5,7 +5,6 @@ use std::sync::Mutex;
 
 pub struct Config {
     pub config_dir: PathBuf,
-    #[allow(dead_code)]
     pub nodex_dir: PathBuf,
     #[allow(dead_code)]
     pub runtime_dir: PathBuf,
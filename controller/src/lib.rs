@@ -78,12 +78,12 @@ where
     // - SIGUSR1 is sent to the Agent by SIGINT etc. The Agent that receives SIGUSR1 sends fd of the Unix domain socket.
     tokio::select! {
         _ = sigint.recv() => {
-            if let Err(e) = runtime_manager.lock().await.cleanup_all() {
+            if let Err(e) = runtime_manager.lock().await.cleanup_all().await {
                 log::error!("Failed to handle sigint: {}", e);
             }
         },
         _ = ctrl_c => {
-            if let Err(e) = runtime_manager.lock().await.cleanup_all() {
+            if let Err(e) = runtime_manager.lock().await.cleanup_all().await {
                 log::error!("Failed to handle CTRL+C: {}", e);
             }
         },
@@ -93,7 +93,7 @@ where
             let _ = runtime_manager.lock().await.cleanup();
         },
         _ = sigabrt.recv() => {
-            if let Err(e) = runtime_manager.lock().await.cleanup_all() {
+            if let Err(e) = runtime_manager.lock().await.cleanup_all().await {
                 log::error!("Failed to handle SIGABRT: {}", e);
             }
         }
@@ -10,23 +10,11 @@ pub struct FileHandler {
 
 impl RuntimeInfoStorage for FileHandler {
     fn read(&mut self) -> Result<RuntimeInfo, RuntimeError> {
-        let mut content = String::new();
-        self.file
-            .read_to_string(&mut content)
-            .map_err(RuntimeError::FileRead)?;
-        self.file
-            .seek(std::io::SeekFrom::Start(0))
-            .map_err(RuntimeError::FileRead)?;
-        if content.trim().is_empty() {
-            // We assume that the file is empty means that it is the first execution.
-            let process_infos = [None, None, None, None];
-            return Ok(RuntimeInfo {
-                state: State::Idle,
-                process_infos,
-                exec_path: std::env::current_exe().map_err(RuntimeError::FailedCurrentExe)?,
-            });
-        }
-        serde_json::from_str(&content).map_err(RuntimeError::JsonDeserialize)
+        // Shared lock so we never observe a half-written file from `apply_with_lock`.
+        fs2::FileExt::lock_shared(&self.file).map_err(RuntimeError::FileLock)?;
+        let runtime_info = self.read_locked().map_err(self.handle_err_id())?;
+        fs2::FileExt::unlock(&self.file).map_err(RuntimeError::FileUnlock)?;
+        Ok(runtime_info)
     }
 
     fn apply_with_lock<F>(&mut self, operation: F) -> Result<(), RuntimeError>
@@ -36,7 +24,7 @@ impl RuntimeInfoStorage for FileHandler {
         fs2::FileExt::lock_exclusive(&self.file)
             .map_err(self.handle_err(RuntimeError::FileLock))?;
 
-        let mut runtime_info = self.read().map_err(self.handle_err_id())?;
+        let mut runtime_info = self.read_locked().map_err(self.handle_err_id())?;
 
         operation(&mut runtime_info).map_err(self.handle_err_id())?;
 
@@ -60,6 +48,26 @@ impl FileHandler {
         Ok(FileHandler { file })
     }
 
+    fn read_locked(&mut self) -> Result<RuntimeInfo, RuntimeError> {
+        let mut content = String::new();
+        self.file
+            .read_to_string(&mut content)
+            .map_err(RuntimeError::FileRead)?;
+        self.file
+            .seek(std::io::SeekFrom::Start(0))
+            .map_err(RuntimeError::FileRead)?;
+        if content.trim().is_empty() {
+            // We assume that the file is empty means that it is the first execution.
+            let process_infos = [None, None, None, None];
+            return Ok(RuntimeInfo {
+                state: State::Idle,
+                process_infos,
+                exec_path: std::env::current_exe().map_err(RuntimeError::FailedCurrentExe)?,
+            });
+        }
+        serde_json::from_str(&content).map_err(RuntimeError::JsonDeserialize)
+    }
+
     fn handle_err_id(&mut self) -> impl Fn(RuntimeError) -> RuntimeError + '_ {
         self.handle_err(|x| x)
     }
@@ -106,9 +114,11 @@ impl FileHandler {
 mod tests {
     use super::*;
     use crate::managers::runtime::{
-        FeatType, ProcessInfo, RuntimeInfo, RuntimeManagerImpl, RuntimeManagerWithoutAsync,
+        FeatType, ProcessInfo, ProcessManager, RuntimeInfo, RuntimeManagerImpl,
+        RuntimeManagerWithoutAsync,
     };
     use crate::managers::unix_process_manager::UnixProcessManager;
+    use serial_test::serial;
     use std::fs::File;
     use tempfile::tempdir;
 
@@ -134,6 +144,65 @@ mod tests {
         assert_eq!(read_runtime_info, initial_runtime_info);
     }
 
+    #[test]
+    fn test_apply_with_lock_concurrent_adds() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let temp_file_path = temp_dir.path().join("runtime_info.json");
+        File::create(&temp_file_path).expect("Failed to create temporary runtime_info.json");
+
+        let handles: Vec<_> = [[1001, 1002], [2001, 2002]]
+            .into_iter()
+            .map(|pids| {
+                let path = temp_file_path.clone();
+                std::thread::spawn(move || {
+                    // Each thread opens its own handle so the flock is actually contended.
+                    let mut file_handler = FileHandler::new(path).unwrap();
+                    for pid in pids {
+                        file_handler
+                            .apply_with_lock(|runtime_info| {
+                                runtime_info
+                                    .add_process_info(ProcessInfo::new(pid, FeatType::Agent))
+                            })
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let mut file_handler = FileHandler::new(temp_file_path).unwrap();
+        let mut pids: Vec<_> = file_handler
+            .read()
+            .unwrap()
+            .process_infos
+            .into_iter()
+            .flatten()
+            .map(|p| p.process_id)
+            .collect();
+        pids.sort();
+        assert_eq!(pids, vec![1001, 1002, 2001, 2002]);
+    }
+
+    #[test]
+    fn test_read_releases_shared_lock() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let temp_file_path = temp_dir.path().join("runtime_info.json");
+        let mut reader = FileHandler::new(temp_file_path.clone()).unwrap();
+        let other = File::open(&temp_file_path).unwrap();
+
+        // A shared lock held elsewhere must not block reading.
+        fs2::FileExt::lock_shared(&other).unwrap();
+        reader.read().unwrap();
+        fs2::FileExt::unlock(&other).unwrap();
+
+        // And reading must not leave a lock behind.
+        reader.read().unwrap();
+        fs2::FileExt::try_lock_exclusive(&other).unwrap();
+        fs2::FileExt::unlock(&other).unwrap();
+    }
+
     #[test]
     fn test_update_state() {
         let temp_dir = tempdir().expect("Failed to create temporary directory");
@@ -152,6 +221,24 @@ mod tests {
         assert_eq!(state, State::Update);
     }
 
+    #[test]
+    fn test_update_state_rejects_invalid_transition() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let temp_file_path = temp_dir.path().join("runtime_info.json");
+        let file_handler = FileHandler::new(temp_file_path).unwrap();
+        let mut runtime_manager =
+            RuntimeManagerImpl::new_by_agent(file_handler, UnixProcessManager);
+
+        let err = runtime_manager
+            .update_state_without_send(State::Rollback)
+            .unwrap_err();
+        assert!(matches!(err, RuntimeError::InvalidTransition(_)));
+        assert_eq!(
+            runtime_manager.get_runtime_info().unwrap().state,
+            State::Idle
+        );
+    }
+
     #[test]
     fn test_cleanup_process_info() {
         let temp_dir = tempdir().expect("Failed to create temporary directory");
@@ -185,29 +272,215 @@ mod tests {
         assert!(!process_infos.contains(&process_info));
     }
 
-    // TODO: Fix fork bomb
-    // #[tokio::test]
-    // async fn test_launch_and_terminate_agent() {
-    //     let temp_dir = tempfile::tempdir().unwrap();
-    //     let temp_file_path = temp_dir.path().join("runtime_info.json");
-    //     File::create(&temp_file_path).expect("Failed to create temporary runtime_info.json");
-    //     let uds_path = temp_dir.path().join("test_socket");
-    //     let file_handler = FileHandler::new(temp_file_path.clone()).unwrap();
-    //     let mut manager = RuntimeManagerImpl::new_by_controller(
-    //         file_handler,
-    //         UnixProcessManager,
-    //         uds_path,
-    //     )
-    //     .unwrap()
-    //     .0;
-
-    //     let process_info = manager.launch_agent(false);
-    //     assert!(process_info.is_ok(), "Agent launch should succeed");
-
-    //     let process_info = process_info.unwrap();
-    //     assert!(
-    //         manager.kill_process(&process_info).is_ok(),
-    //         "Agent termination should succeed"
-    //     );
-    // }
+    // Rollback relies on this: launch_controller must signal every tracked agent
+    // (the old binary) and forget it before the restored controller starts.
+    #[test]
+    #[serial]
+    fn test_launch_controller_signals_tracked_agents() {
+        use nix::sys::wait::{waitpid, WaitStatus};
+        use nix::unistd::Pid;
+
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let temp_file_path = temp_dir.path().join("runtime_info.json");
+        let old_agent = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+
+        let mut file_handler = FileHandler::new(temp_file_path).unwrap();
+        file_handler
+            .apply_with_lock(|runtime_info| {
+                runtime_info.add_process_info(ProcessInfo::new(old_agent.id(), FeatType::Agent))
+            })
+            .unwrap();
+        let mut runtime_manager =
+            RuntimeManagerImpl::new_by_agent(file_handler, UnixProcessManager);
+
+        // Under systemd launch_controller stops after signaling, so nothing is spawned here.
+        std::env::set_var("INVOCATION_ID", "dummy_id");
+        let result = runtime_manager.launch_controller("/nonexistent/nodex-agent");
+        std::env::remove_var("INVOCATION_ID");
+        result.unwrap();
+
+        let status = waitpid(Pid::from_raw(old_agent.id() as i32), None).unwrap();
+        assert!(matches!(status, WaitStatus::Signaled(_, _, _)));
+        assert!(runtime_manager
+            .get_runtime_info()
+            .unwrap()
+            .find_process_info(old_agent.id())
+            .is_none());
+    }
+
+    // cleanup_all waits for stubborn processes, which must not keep the runtime
+    // info locked for the whole time.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_cleanup_all_releases_lock_while_waiting() {
+        use std::time::{Duration, Instant};
+
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let temp_file_path = temp_dir.path().join("runtime_info.json");
+        let stubborn = std::process::Command::new("sh")
+            .args(["-c", "trap '' TERM; while :; do sleep 1; done"])
+            .spawn()
+            .unwrap();
+        // Give the shell a moment to install the trap.
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let mut file_handler = FileHandler::new(temp_file_path.clone()).unwrap();
+        file_handler
+            .apply_with_lock(|runtime_info| {
+                runtime_info.add_process_info(ProcessInfo::new(stubborn.id(), FeatType::Agent))
+            })
+            .unwrap();
+        let mut runtime_manager =
+            RuntimeManagerImpl::new_by_agent(file_handler, UnixProcessManager)
+                .with_terminate_timeout(Duration::from_secs(1));
+
+        let mut other_handler = FileHandler::new(temp_file_path).unwrap();
+        let probe = async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            let started = Instant::now();
+            tokio::task::spawn_blocking(move || {
+                other_handler.apply_with_lock(|_| Ok(())).unwrap();
+            })
+            .await
+            .unwrap();
+            started.elapsed()
+        };
+        let (result, waited) = tokio::join!(runtime_manager.cleanup_all(), probe);
+
+        result.unwrap();
+        assert!(waited < Duration::from_millis(500), "{:?}", waited);
+        assert!(!UnixProcessManager.is_running(stubborn.id()));
+        let runtime_info = runtime_manager.get_runtime_info().unwrap();
+        assert!(runtime_info.process_infos.iter().all(Option::is_none));
+        assert_eq!(runtime_info.state, State::Idle);
+    }
+
+    const HELPER_OUT_ENV: &str = "NODEX_LAUNCH_HELPER_OUT";
+    const HELPER_UDS_ENV: &str = "NODEX_LAUNCH_HELPER_UDS";
+
+    // Plays the agent for `test_launch_agent_with_helper`: receives the listener
+    // handed over by the controller and reports what arrived.
+    #[test]
+    #[ignore = "run as a child process by test_launch_agent_with_helper"]
+    fn launch_helper() {
+        use crate::unix_utils::{convention_of_meta_uds_path, recv_fd};
+        use std::os::unix::io::{AsRawFd, FromRawFd};
+        use std::os::unix::net::UnixListener;
+
+        let (Ok(out_path), Ok(uds_path)) =
+            (std::env::var(HELPER_OUT_ENV), std::env::var(HELPER_UDS_ENV))
+        else {
+            return;
+        };
+        let meta_uds_path = convention_of_meta_uds_path(&uds_path).unwrap();
+        let sock = UnixListener::bind(&meta_uds_path).unwrap();
+        let (stream, _) = sock.accept().unwrap();
+        let listener = match recv_fd(stream.as_raw_fd()).unwrap() {
+            Some(fd) => {
+                let listener = unsafe { UnixListener::from_raw_fd(fd) };
+                let addr = listener.local_addr().unwrap();
+                addr.as_pathname().unwrap().display().to_string()
+            }
+            None => "none".to_string(),
+        };
+        let report = format!(
+            "listener={}\npid={}\nLISTEN_PID={}\n",
+            listener,
+            std::process::id(),
+            std::env::var("LISTEN_PID").unwrap_or_default()
+        );
+        std::fs::write(out_path, report).unwrap();
+    }
+
+    // NOTE: The agent is launched from `RuntimeInfo::exec_path`, so pointing it
+    // at a tiny helper script instead of the test binary keeps this from
+    // re-running the test suite in the child. The script re-enters this binary
+    // only for `launch_helper`.
+    #[test]
+    #[serial]
+    fn test_launch_agent_with_helper() {
+        use nix::sys::wait::{waitpid, WaitStatus};
+        use nix::unistd::Pid;
+        use std::os::unix::fs::PermissionsExt;
+        use std::os::unix::io::AsRawFd;
+
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let temp_file_path = temp_dir.path().join("runtime_info.json");
+        let uds_path = temp_dir.path().join("nodex.sock");
+        let args_path = temp_dir.path().join("args");
+        let report_path = temp_dir.path().join("report");
+        let helper_path = temp_dir.path().join("helper.sh");
+        std::fs::write(
+            &helper_path,
+            format!(
+                "#!/bin/sh\necho \"$@\" > {}\nexec env {}={} {}={} {} --exact managers::file_storage::tests::launch_helper --ignored --quiet\n",
+                args_path.display(),
+                HELPER_OUT_ENV,
+                report_path.display(),
+                HELPER_UDS_ENV,
+                uds_path.display(),
+                std::env::current_exe().unwrap().display(),
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&helper_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        // Pretend systemd passed the listener: fds 3..=fd, with ours named "nodex".
+        let listener = std::os::unix::net::UnixListener::bind(&uds_path).unwrap();
+        let fd = listener.as_raw_fd();
+        let mut names = vec!["unused"; (fd - 3) as usize];
+        names.push(crate::unix_utils::NODEX_FD_NAME);
+        std::env::set_var("INVOCATION_ID", "dummy_id");
+        std::env::set_var("LISTEN_PID", std::process::id().to_string());
+        std::env::set_var("LISTEN_FDS", names.len().to_string());
+        std::env::set_var("LISTEN_FDNAMES", names.join(":"));
+
+        let mut file_handler = FileHandler::new(temp_file_path.clone()).unwrap();
+        file_handler
+            .apply_with_lock(|runtime_info| {
+                runtime_info.exec_path = helper_path.clone();
+                Ok(())
+            })
+            .unwrap();
+        let (mut runtime_manager, _) =
+            RuntimeManagerImpl::new_by_controller(file_handler, UnixProcessManager, &uds_path)
+                .unwrap();
+
+        let process_info = runtime_manager.launch_agent(true);
+        for key in [
+            "INVOCATION_ID",
+            "LISTEN_PID",
+            "LISTEN_FDS",
+            "LISTEN_FDNAMES",
+        ] {
+            std::env::remove_var(key);
+        }
+        let process_info = process_info.unwrap();
+        assert_eq!(process_info.feat_type, FeatType::Agent);
+
+        let status = waitpid(Pid::from_raw(process_info.process_id as i32), None).unwrap();
+        assert!(matches!(status, WaitStatus::Exited(_, 0)));
+        assert_eq!(
+            std::fs::read_to_string(&args_path).unwrap().trim(),
+            "controlled"
+        );
+        let report = std::fs::read_to_string(&report_path).unwrap();
+        let report: std::collections::HashMap<_, _> = report
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .collect();
+        // The child got our listener through the meta socket...
+        assert_eq!(report["listener"], uds_path.display().to_string());
+        // ...because the inherited systemd variables name the controller, not the agent.
+        assert_eq!(report["LISTEN_PID"], std::process::id().to_string());
+        assert_eq!(report["pid"], process_info.process_id.to_string());
+
+        let registered = runtime_manager.get_runtime_info().unwrap();
+        assert_eq!(
+            registered.find_process_info(process_info.process_id),
+            Some(&process_info)
+        );
+    }
 }
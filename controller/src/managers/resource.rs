@@ -2,16 +2,20 @@ use crate::config::get_config;
 use bytes::Bytes;
 use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use glob::glob;
+use sha2::{Digest, Sha256};
 use std::{
+    collections::BTreeMap,
     fs::{self, File},
-    io::{self, Cursor},
-    path::{Path, PathBuf},
-    time::SystemTime,
+    io::{self, Cursor, Read, Seek, Write},
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime},
 };
 use tar::{Archive, Builder, Header};
 #[cfg(unix)]
 use users::{get_current_gid, get_current_uid};
 use zip::{result::ZipError, ZipArchive};
+#[cfg(windows)]
+use zip::{write::SimpleFileOptions, ZipWriter};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ResourceError {
@@ -21,6 +25,17 @@ pub enum ResourceError {
     IoError(#[from] io::Error),
     #[error("Failed to extract zip file")]
     ZipError(#[from] ZipError),
+    #[error("Failed to extract zip entry #{index} ({name}): {source}")]
+    ExtractEntry {
+        index: usize,
+        name: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Checksum mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch { expected: String, actual: String },
+    #[error("Unsupported archive format")]
+    UnsupportedArchiveFormat,
     #[error("Failed to create tarball: {0}")]
     TarError(String),
     #[error("Failed to delete files in {0}")]
@@ -29,6 +44,195 @@ pub enum ResourceError {
     RollbackFailed(String),
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum BackupOutcome {
+    Created(PathBuf),
+    // NOTE: The sources are identical to the latest backup, which is reused.
+    Unchanged(PathBuf),
+}
+
+// NOTE: Relative path of every backed up file mapped to its SHA-256.
+type BackupManifest = BTreeMap<String, String>;
+
+#[derive(Debug, PartialEq, Eq)]
+enum ArchiveFormat {
+    Zip,
+    TarGz,
+    TarZstd,
+}
+
+impl ArchiveFormat {
+    fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"PK\x03\x04") {
+            Some(ArchiveFormat::Zip)
+        } else if data.starts_with(&[0x1f, 0x8b]) {
+            Some(ArchiveFormat::TarGz)
+        } else if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(ArchiveFormat::TarZstd)
+        } else {
+            None
+        }
+    }
+}
+
+async fn fetch(binary_url: &str) -> Result<Bytes, ResourceError> {
+    let response = reqwest::get(binary_url)
+        .await
+        .map_err(|_| ResourceError::DownloadFailed(binary_url.to_string()))?;
+    response
+        .bytes()
+        .await
+        .map_err(|_| ResourceError::DownloadFailed(binary_url.to_string()))
+}
+
+// NOTE: A stalled server must not hang an update, but the whole transfer still gets
+// enough time for a large file.
+const DOWNLOAD_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+// NOTE: Streams `url` into `dest` while hashing it, so the file only appears at `dest`
+// once its SHA-256 matches `expected_sha256`.
+pub async fn download_file_verified(
+    url: &str,
+    dest: &Path,
+    expected_sha256: &str,
+) -> Result<(), ResourceError> {
+    let client = reqwest::Client::builder()
+        .connect_timeout(DOWNLOAD_CONNECT_TIMEOUT)
+        .timeout(DOWNLOAD_TIMEOUT)
+        .build()
+        .map_err(|e| ResourceError::DownloadFailed(format!("{}: {}", url, e)))?;
+    download_file_verified_with_client(&client, url, dest, expected_sha256).await
+}
+
+async fn download_file_verified_with_client(
+    client: &reqwest::Client,
+    url: &str,
+    dest: &Path,
+    expected_sha256: &str,
+) -> Result<(), ResourceError> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file_name = dest
+        .file_name()
+        .ok_or_else(|| ResourceError::DownloadFailed(url.to_string()))?;
+    let part_path = dest.with_file_name(format!(".{}.part", file_name.to_string_lossy()));
+
+    let result = async {
+        let download_failed =
+            |e: reqwest::Error| ResourceError::DownloadFailed(format!("{}: {}", url, e));
+        let mut response = client
+            .get(url)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(download_failed)?;
+        let mut file = File::create(&part_path)?;
+        let mut hasher = Sha256::new();
+        while let Some(chunk) = response.chunk().await.map_err(download_failed)? {
+            hasher.update(&chunk);
+            file.write_all(&chunk)?;
+        }
+        file.sync_all()?;
+        let actual = hex::encode(hasher.finalize());
+        if !actual.eq_ignore_ascii_case(expected_sha256) {
+            return Err(ResourceError::ChecksumMismatch {
+                expected: expected_sha256.to_string(),
+                actual,
+            });
+        }
+        fs::rename(&part_path, dest)?;
+        Ok(())
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = fs::remove_file(&part_path);
+    }
+    result
+}
+
+// NOTE: Connection errors, timeouts and errors while reading the body are retried
+// by downloading the whole file again. An error status such as 404 fails immediately.
+async fn fetch_with_retry(
+    binary_url: &str,
+    max_retries: u32,
+    base_delay: Duration,
+) -> Result<Bytes, ResourceError> {
+    let mut attempt = 0;
+    loop {
+        let result = async {
+            reqwest::get(binary_url)
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await
+        }
+        .await;
+        match result {
+            Ok(content) => return Ok(content),
+            Err(e) if is_retryable(&e) && attempt < max_retries => {
+                let delay = base_delay * 2u32.saturating_pow(attempt);
+                attempt += 1;
+                log::warn!(
+                    "Download of {} failed (attempt {}/{}): {}. Retrying in {:?}",
+                    binary_url,
+                    attempt,
+                    max_retries + 1,
+                    e,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => {
+                log::error!("Download of {} failed: {}", binary_url, e);
+                return Err(ResourceError::DownloadFailed(format!(
+                    "{}: {}",
+                    binary_url, e
+                )));
+            }
+        }
+    }
+}
+
+// NOTE: A body read fails with a decode error when the connection drops mid-stream.
+fn is_retryable(e: &reqwest::Error) -> bool {
+    e.is_connect() || e.is_timeout() || e.is_body() || e.is_decode()
+}
+
+// NOTE: Number of backups left in `tmp_path` after a new one is created.
+const BACKUPS_TO_KEEP: usize = 3;
+
+// NOTE: Returns the timestamp embedded in `nodex_backup_<timestamp>.<ext>`, or None if
+// the path is not a backup created by a resource manager.
+fn backup_timestamp(path: &Path) -> Option<u64> {
+    let name = path.file_name()?.to_str()?;
+    let rest = name.strip_prefix("nodex_backup_")?;
+    #[cfg(unix)]
+    let timestamp = rest.strip_suffix(".tar.gz")?;
+    #[cfg(windows)]
+    let timestamp = rest
+        .strip_suffix(".tar.gz")
+        .or_else(|| rest.strip_suffix(".zip"))?;
+    timestamp.parse().ok()
+}
+
+// NOTE: Backup metadata comes from the archive itself, so a relative path must not be
+// able to point outside the directory it was extracted to.
+fn validate_relative_path(relative_path: &Path) -> Result<(), ResourceError> {
+    let is_safe = relative_path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if relative_path.as_os_str().is_empty() || !is_safe {
+        return Err(ResourceError::RollbackFailed(format!(
+            "Invalid path in backup metadata: {:?}",
+            relative_path
+        )));
+    }
+    Ok(())
+}
+
 // ref: https://stackoverflow.com/questions/26958489/how-to-copy-a-folder-recursively-in-rust
 fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> io::Result<()> {
     if !fs::metadata(&src)?.is_dir() {
@@ -61,9 +265,10 @@ fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> io::Result<()>
 
 #[trait_variant::make(Send)]
 pub trait ResourceManagerTrait: Send + Sync {
-    fn backup(&self) -> Result<(), ResourceError>;
+    fn backup(&self) -> Result<BackupOutcome, ResourceError>;
 
-    fn rollback(&self, backup_file: &Path) -> Result<(), ResourceError>;
+    // NOTE: Returns the original paths that were restored from the backup.
+    fn rollback(&self, backup_file: &Path) -> Result<Vec<PathBuf>, ResourceError>;
 
     fn tmp_path(&self) -> &PathBuf;
 
@@ -78,15 +283,94 @@ pub trait ResourceManagerTrait: Send + Sync {
             let output_path = output_path.map(|x| x.as_ref().to_path_buf());
             let download_path = output_path.as_ref().unwrap_or(self.tmp_path());
 
-            let response = reqwest::get(binary_url)
-                .await
-                .map_err(|_| ResourceError::DownloadFailed(binary_url.to_string()))?;
-            let content = response
-                .bytes()
-                .await
-                .map_err(|_| ResourceError::DownloadFailed(binary_url.to_string()))?;
+            let content = fetch(binary_url).await?;
+
+            self.extract_archive(content, download_path)?;
+            Ok(())
+        }
+    }
+
+    // NOTE: Writes the response body to a file under `tmp_path` chunk by chunk
+    // so that peak memory does not depend on the archive size.
+    async fn download_update_resources_streaming(
+        &self,
+        binary_url: &str,
+        output_path: Option<impl AsRef<Path> + Send>,
+    ) -> Result<(), ResourceError> {
+        async move {
+            let output_path = output_path.map(|x| x.as_ref().to_path_buf());
+            let download_path = output_path.as_ref().unwrap_or(self.tmp_path());
+
+            let timestamp = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos();
+            fs::create_dir_all(self.tmp_path())?;
+            let archive_path = self.tmp_path().join(format!(
+                "nodex_download_{}_{}.part",
+                std::process::id(),
+                timestamp
+            ));
+
+            let result = async {
+                let download_failed = |_| ResourceError::DownloadFailed(binary_url.to_string());
+                let mut response = reqwest::get(binary_url)
+                    .await
+                    .and_then(|res| res.error_for_status())
+                    .map_err(download_failed)?;
+                let mut file = File::create(&archive_path)?;
+                while let Some(chunk) = response.chunk().await.map_err(download_failed)? {
+                    file.write_all(&chunk)?;
+                }
+                file.flush()?;
+                drop(file);
+                self.extract_archive_file(&archive_path, download_path)
+            }
+            .await;
+
+            let _ = fs::remove_file(&archive_path);
+            result
+        }
+    }
+
+    async fn download_update_resources_verified(
+        &self,
+        binary_url: &str,
+        output_path: Option<impl AsRef<Path> + Send>,
+        expected_sha256: &str,
+    ) -> Result<(), ResourceError> {
+        async move {
+            let output_path = output_path.map(|x| x.as_ref().to_path_buf());
+            let download_path = output_path.as_ref().unwrap_or(self.tmp_path());
+
+            let content = fetch(binary_url).await?;
+            let actual = hex::encode(Sha256::digest(&content));
+            if !actual.eq_ignore_ascii_case(expected_sha256) {
+                return Err(ResourceError::ChecksumMismatch {
+                    expected: expected_sha256.to_string(),
+                    actual,
+                });
+            }
+
+            self.extract_archive(content, download_path)?;
+            Ok(())
+        }
+    }
+
+    async fn download_update_resources_with_retry(
+        &self,
+        binary_url: &str,
+        output_path: Option<impl AsRef<Path> + Send>,
+        max_retries: u32,
+        base_delay: Duration,
+    ) -> Result<(), ResourceError> {
+        async move {
+            let output_path = output_path.map(|x| x.as_ref().to_path_buf());
+            let download_path = output_path.as_ref().unwrap_or(self.tmp_path());
+
+            let content = fetch_with_retry(binary_url, max_retries, base_delay).await?;
 
-            self.extract_zip(content, download_path)?;
+            self.extract_archive(content, download_path)?;
             Ok(())
         }
     }
@@ -96,6 +380,42 @@ pub trait ResourceManagerTrait: Send + Sync {
         Ok(vec![self.agent_path().clone(), config.config_dir.clone()])
     }
 
+    fn compute_manifest(
+        &self,
+        metadata: &[(PathBuf, PathBuf)],
+    ) -> Result<BackupManifest, ResourceError> {
+        fn walk(
+            src: &Path,
+            relative_path: &Path,
+            manifest: &mut BackupManifest,
+        ) -> Result<(), ResourceError> {
+            if src.is_dir() {
+                for entry in fs::read_dir(src)? {
+                    let entry = entry?;
+                    walk(
+                        &entry.path(),
+                        &relative_path.join(entry.file_name()),
+                        manifest,
+                    )?;
+                }
+            } else if src.is_file() {
+                let mut hasher = Sha256::new();
+                io::copy(&mut File::open(src)?, &mut hasher)?;
+                manifest.insert(
+                    relative_path.to_string_lossy().into_owned(),
+                    hex::encode(hasher.finalize()),
+                );
+            }
+            Ok(())
+        }
+
+        let mut manifest = BackupManifest::new();
+        for (original_path, relative_path) in metadata {
+            walk(original_path, relative_path, &mut manifest)?;
+        }
+        Ok(manifest)
+    }
+
     fn collect_downloaded_bundles(&self) -> Vec<PathBuf> {
         let pattern = self
             .tmp_path()
@@ -115,7 +435,12 @@ pub trait ResourceManagerTrait: Send + Sync {
             .ok()?
             .filter_map(|entry| entry.ok().map(|e| e.path()))
             .filter(|path| {
-                path.is_file() && path.extension().and_then(|ext| ext.to_str()) == Some("gz")
+                let ext = path.extension().and_then(|ext| ext.to_str());
+                #[cfg(unix)]
+                let is_backup = ext == Some("gz");
+                #[cfg(windows)]
+                let is_backup = matches!(ext, Some("gz") | Some("zip"));
+                path.is_file() && is_backup
             })
             .max_by_key(|path| {
                 path.metadata()
@@ -124,35 +449,178 @@ pub trait ResourceManagerTrait: Send + Sync {
             })
     }
 
+    // NOTE: Removes all but the newest `keep` backups and returns the removed paths.
+    fn prune_backups(&self, keep: usize) -> Result<Vec<PathBuf>, ResourceError> {
+        let mut backups: Vec<(u64, PathBuf)> = fs::read_dir(self.tmp_path())?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_file())
+            .filter_map(|path| backup_timestamp(&path).map(|timestamp| (timestamp, path)))
+            .collect();
+        backups.sort_by(|a, b| b.cmp(a));
+
+        let mut removed = Vec::new();
+        for (_, path) in backups.into_iter().skip(keep) {
+            fs::remove_file(&path)?;
+            log::info!("Removed old backup {:?}", path);
+            removed.push(path);
+        }
+        Ok(removed)
+    }
+
+    fn extract_archive(
+        &self,
+        archive_data: Bytes,
+        output_path: &Path,
+    ) -> Result<(), ResourceError> {
+        match ArchiveFormat::detect(&archive_data) {
+            Some(ArchiveFormat::Zip) => self.extract_zip(archive_data, output_path),
+            Some(ArchiveFormat::TarGz) => {
+                self.extract_tar(GzDecoder::new(Cursor::new(archive_data)), output_path)
+            }
+            Some(ArchiveFormat::TarZstd) => {
+                let decoder = zstd::Decoder::new(Cursor::new(archive_data))?;
+                self.extract_tar(decoder, output_path)
+            }
+            None => Err(ResourceError::UnsupportedArchiveFormat),
+        }
+    }
+
+    fn extract_archive_file(
+        &self,
+        archive_path: &Path,
+        output_path: &Path,
+    ) -> Result<(), ResourceError> {
+        let mut file = File::open(archive_path)?;
+        let mut magic = [0u8; 4];
+        let read = file.read(&mut magic)?;
+        file.seek(io::SeekFrom::Start(0))?;
+        match ArchiveFormat::detect(&magic[..read]) {
+            Some(ArchiveFormat::Zip) => self.extract_zip_reader(file, output_path),
+            Some(ArchiveFormat::TarGz) => self.extract_tar(GzDecoder::new(file), output_path),
+            Some(ArchiveFormat::TarZstd) => {
+                self.extract_tar(zstd::Decoder::new(file)?, output_path)
+            }
+            None => Err(ResourceError::UnsupportedArchiveFormat),
+        }
+    }
+
+    fn extract_tar(&self, reader: impl Read, output_path: &Path) -> Result<(), ResourceError> {
+        fs::create_dir_all(output_path)?;
+        Archive::new(reader)
+            .unpack(output_path)
+            .map_err(|e| ResourceError::TarError(format!("Failed to extract tarball: {}", e)))
+    }
+
     fn extract_zip(&self, archive_data: Bytes, output_path: &Path) -> Result<(), ResourceError> {
-        let cursor = Cursor::new(archive_data);
-        let mut archive = ZipArchive::new(cursor)?;
+        self.extract_zip_reader(Cursor::new(archive_data), output_path)
+    }
+
+    fn extract_zip_reader(
+        &self,
+        reader: impl Read + Seek,
+        output_path: &Path,
+    ) -> Result<(), ResourceError> {
+        let mut archive = ZipArchive::new(reader)?;
 
         for i in 0..archive.len() {
-            let mut file = archive.by_index(i)?;
+            let name = archive.name_for_index(i).unwrap_or_default().to_string();
+            let to_entry_error = |source: io::Error| ResourceError::ExtractEntry {
+                index: i,
+                name: name.clone(),
+                source,
+            };
+            let mut file = archive.by_index(i).map_err(|e| to_entry_error(e.into()))?;
             let file_path = output_path.join(file.mangled_name());
 
             if file.is_file() {
                 if let Some(parent) = file_path.parent() {
-                    fs::create_dir_all(parent)?;
+                    fs::create_dir_all(parent).map_err(to_entry_error)?;
                 }
                 let _ = fs::remove_file(&file_path);
-                let mut output_file = File::create(&file_path)?;
-                io::copy(&mut file, &mut output_file)?;
+                let mut output_file = File::create(&file_path).map_err(to_entry_error)?;
+                io::copy(&mut file, &mut output_file).map_err(to_entry_error)?;
+                #[cfg(unix)]
+                if let Some(mode) = file.unix_mode() {
+                    use std::os::unix::fs::PermissionsExt;
+                    // NOTE: setuid, setgid and sticky bits from the archive are dropped.
+                    fs::set_permissions(&file_path, fs::Permissions::from_mode(mode & 0o777))
+                        .map_err(to_entry_error)?;
+                }
                 #[cfg(unix)]
                 if let Some(file_name) = file_path.file_name() {
                     if file_name == "nodex-agent" {
-                        crate::unix_utils::change_to_executable(&file_path)?;
+                        crate::unix_utils::change_to_executable(&file_path)
+                            .map_err(to_entry_error)?;
                     }
                 }
             } else if file.is_dir() {
-                fs::create_dir_all(&file_path)?;
+                fs::create_dir_all(&file_path).map_err(to_entry_error)?;
             }
         }
 
         Ok(())
     }
 
+    fn read_metadata(&self, temp_dir: &Path) -> Result<Vec<(PathBuf, PathBuf)>, ResourceError> {
+        let metadata_file = temp_dir.join("backup_metadata.json");
+        let metadata_contents = std::fs::read_to_string(&metadata_file).map_err(|e| {
+            ResourceError::RollbackFailed(format!(
+                "Failed to read metadata file {:?}: {}",
+                metadata_file, e
+            ))
+        })?;
+        let metadata: Vec<(PathBuf, PathBuf)> =
+            serde_json::from_str(&metadata_contents).map_err(|e| {
+                ResourceError::RollbackFailed(format!(
+                    "Failed to parse metadata file {:?}: {}",
+                    metadata_file, e
+                ))
+            })?;
+        for (_, relative_path) in &metadata {
+            validate_relative_path(relative_path)?;
+        }
+        Ok(metadata)
+    }
+
+    fn move_files_to_original_paths(
+        &self,
+        temp_dir: &Path,
+        metadata: &[(PathBuf, PathBuf)],
+    ) -> Result<Vec<PathBuf>, ResourceError> {
+        let mut restored = Vec::new();
+        let canonical_temp_dir = temp_dir.canonicalize()?;
+        for (original_path, relative_path) in metadata {
+            validate_relative_path(relative_path)?;
+            let temp_path = temp_dir.join(relative_path);
+            if temp_path.exists() {
+                // NOTE: Guards against symlinks inside the archive escaping temp_dir.
+                if !temp_path.canonicalize()?.starts_with(&canonical_temp_dir) {
+                    return Err(ResourceError::RollbackFailed(format!(
+                        "Path {:?} resolves outside of {:?}",
+                        temp_path, temp_dir
+                    )));
+                }
+                if original_path.exists() {
+                    self.remove_directory(original_path).map_err(|e| {
+                        ResourceError::RollbackFailed(format!(
+                            "Failed to remove existing path {:?}: {}",
+                            original_path, e
+                        ))
+                    })?;
+                }
+                // fs::rename does not work with another partition
+                copy_dir_all(&temp_path, original_path).map_err(|e| {
+                    ResourceError::RollbackFailed(format!(
+                        "Failed to move file from {:?} to {:?}: {}",
+                        temp_path, original_path, e
+                    ))
+                })?;
+                restored.push(original_path.clone());
+            }
+        }
+        Ok(restored)
+    }
+
     fn remove_directory(&self, path: &Path) -> Result<(), io::Error> {
         if !path.exists() {
             return Ok(());
@@ -212,28 +680,42 @@ impl ResourceManagerTrait for UnixResourceManager {
         &self.agent_path
     }
 
-    fn backup(&self) -> Result<(), ResourceError> {
+    fn backup(&self) -> Result<BackupOutcome, ResourceError> {
         let paths_to_backup = self.get_paths_to_backup()?;
         let metadata = self.generate_metadata(&paths_to_backup)?;
-        let tar_gz_path = self.create_tar_gz_with_metadata(&metadata)?;
+        let manifest = self.compute_manifest(&metadata)?;
+        if let Some(latest_backup) = self.get_latest_backup() {
+            if self.read_backup_manifest(&latest_backup).ok().flatten() == Some(manifest.clone()) {
+                log::info!("Nothing changed since backup {:?}", latest_backup);
+                return Ok(BackupOutcome::Unchanged(latest_backup));
+            }
+        }
+        let tar_gz_path = self.create_tar_gz_with_metadata(&metadata, &manifest)?;
         log::info!("Backup created successfully at {:?}", tar_gz_path);
-        Ok(())
+        if let Err(e) = self.prune_backups(BACKUPS_TO_KEEP) {
+            log::warn!("Failed to prune old backups: {}", e);
+        }
+        Ok(BackupOutcome::Created(tar_gz_path))
     }
 
-    fn rollback(&self, backup_file: &Path) -> Result<(), ResourceError> {
+    fn rollback(&self, backup_file: &Path) -> Result<Vec<PathBuf>, ResourceError> {
         let temp_dir = self.extract_tar_to_temp(backup_file)?;
         // Might be safer to check for the existence of config.json and binary
         let metadata = self.read_metadata(&temp_dir)?;
-        self.move_files_to_original_paths(&temp_dir, &metadata)?;
+        let restored = self.move_files_to_original_paths(&temp_dir, &metadata)?;
+        if let Err(e) = self.remove_directory(&temp_dir) {
+            log::warn!("Failed to clean up restore directory {:?}: {}", temp_dir, e);
+        }
+        self.verify_restored_agent(backup_file, &metadata)?;
 
         log::info!("Rollback completed successfully from {:?}", backup_file);
-        Ok(())
+        Ok(restored)
     }
 }
 
 #[cfg(unix)]
 impl UnixResourceManager {
-    pub fn new(agent_path: impl AsRef<Path>) -> Self {
+    pub fn new(agent_path: impl AsRef<Path>) -> Result<Self, ResourceError> {
         let tmp_path = if PathBuf::from("/home/nodex/").exists() {
             PathBuf::from("/home/nodex/tmp")
         } else if PathBuf::from("/tmp/nodex").exists() || fs::create_dir_all("/tmp/nodex").is_ok() {
@@ -242,14 +724,18 @@ impl UnixResourceManager {
             PathBuf::from("/tmp")
         };
 
-        if !tmp_path.exists() {
-            fs::create_dir_all(&tmp_path).expect("Failed to create tmp dir");
-        }
+        fs::create_dir_all(&tmp_path)?;
 
-        Self {
+        Ok(Self {
             tmp_path,
             agent_path: agent_path.as_ref().into(),
-        }
+        })
+    }
+
+    pub fn with_tmp_path(mut self, tmp_path: PathBuf) -> Result<Self, ResourceError> {
+        fs::create_dir_all(&tmp_path)?;
+        self.tmp_path = tmp_path;
+        Ok(self)
     }
 
     fn generate_metadata(
@@ -268,6 +754,7 @@ impl UnixResourceManager {
     fn create_tar_gz_with_metadata(
         &self,
         metadata: &[(PathBuf, PathBuf)],
+        manifest: &BackupManifest,
     ) -> Result<PathBuf, ResourceError> {
         let timestamp = SystemTime::now()
             .duration_since(SystemTime::UNIX_EPOCH)
@@ -288,6 +775,15 @@ impl UnixResourceManager {
 
             self.add_files_to_tar(&mut tar_builder, metadata)?;
             self.add_metadata_to_tar(&mut tar_builder, metadata, timestamp)?;
+            let manifest_json = serde_json::to_string(manifest).map_err(|e| {
+                ResourceError::TarError(format!("Failed to serialize manifest: {}", e))
+            })?;
+            self.add_json_to_tar(
+                &mut tar_builder,
+                "backup_manifest.json",
+                &manifest_json,
+                timestamp,
+            )?;
             tar_builder
                 .finish()
                 .map_err(|e| ResourceError::TarError(format!("Failed to finish tarball: {}", e)))?;
@@ -335,9 +831,6 @@ impl UnixResourceManager {
         metadata: &[(PathBuf, PathBuf)],
         timestamp: u64,
     ) -> Result<(), ResourceError> {
-        let uid = get_current_uid();
-        let gid = get_current_gid();
-
         let metadata: Vec<_> = metadata
             .iter()
             .map(|(x, y)| (x.as_path().to_str(), y.as_path().to_str()))
@@ -345,11 +838,29 @@ impl UnixResourceManager {
         let metadata_json = serde_json::to_string(&metadata)
             .map_err(|e| ResourceError::TarError(format!("Failed to serialize metadata: {}", e)))?;
 
+        self.add_json_to_tar(
+            tar_builder,
+            "backup_metadata.json",
+            &metadata_json,
+            timestamp,
+        )
+    }
+
+    fn add_json_to_tar<W: std::io::Write>(
+        &self,
+        tar_builder: &mut Builder<W>,
+        name: &str,
+        json: &str,
+        timestamp: u64,
+    ) -> Result<(), ResourceError> {
+        let uid = get_current_uid();
+        let gid = get_current_gid();
+
         let mut header = Header::new_gnu();
         header
-            .set_path("backup_metadata.json")
+            .set_path(name)
             .map_err(|e| ResourceError::TarError(format!("Failed to set header path: {}", e)))?;
-        header.set_size(metadata_json.len() as u64);
+        header.set_size(json.len() as u64);
         header.set_mode(0o644);
         header.set_mtime(timestamp);
         header.set_uid(uid as u64);
@@ -357,13 +868,76 @@ impl UnixResourceManager {
         header.set_cksum();
 
         tar_builder
-            .append_data(
-                &mut header,
-                "backup_metadata.json",
-                metadata_json.as_bytes(),
-            )
-            .map_err(|e| ResourceError::TarError(format!("Failed to add metadata: {}", e)))?;
+            .append_data(&mut header, name, json.as_bytes())
+            .map_err(|e| ResourceError::TarError(format!("Failed to add {}: {}", name, e)))?;
+
+        Ok(())
+    }
+
+    fn read_backup_manifest(
+        &self,
+        backup_file: &Path,
+    ) -> Result<Option<BackupManifest>, ResourceError> {
+        let mut archive = Archive::new(GzDecoder::new(File::open(backup_file)?));
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if entry.path()?.as_ref() == Path::new("backup_manifest.json") {
+                let mut contents = String::new();
+                io::Read::read_to_string(&mut entry, &mut contents)?;
+                let manifest = serde_json::from_str(&contents).map_err(io::Error::from)?;
+                return Ok(Some(manifest));
+            }
+        }
+        Ok(None)
+    }
+
+    // NOTE: A corrupt backup would otherwise "restore" into a broken agent, so check the
+    // binary is back, executable and identical to what was recorded at backup time.
+    fn verify_restored_agent(
+        &self,
+        backup_file: &Path,
+        metadata: &[(PathBuf, PathBuf)],
+    ) -> Result<(), ResourceError> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let agent_path = self.agent_path();
+        let (_, relative_path) = metadata
+            .iter()
+            .find(|(original_path, _)| original_path == agent_path)
+            .ok_or_else(|| {
+                ResourceError::RollbackFailed(format!(
+                    "Backup {:?} does not contain the agent binary {:?}",
+                    backup_file, agent_path
+                ))
+            })?;
+        let mode = fs::metadata(agent_path)
+            .map_err(|e| {
+                ResourceError::RollbackFailed(format!(
+                    "Restored agent binary {:?} is missing: {}",
+                    agent_path, e
+                ))
+            })?
+            .permissions()
+            .mode();
+        if !agent_path.is_file() || mode & 0o111 == 0 {
+            return Err(ResourceError::RollbackFailed(format!(
+                "Restored agent binary {:?} is not an executable file",
+                agent_path
+            )));
+        }
 
+        // Backups taken before manifests existed can only be checked for presence.
+        let Some(expected) = self.read_backup_manifest(backup_file)? else {
+            return Ok(());
+        };
+        let key = relative_path.to_string_lossy();
+        let actual = self.compute_manifest(&[(agent_path.clone(), relative_path.clone())])?;
+        if actual.get(key.as_ref()) != expected.get(key.as_ref()) {
+            return Err(ResourceError::RollbackFailed(format!(
+                "Restored agent binary {:?} does not match the backup manifest",
+                agent_path
+            )));
+        }
         Ok(())
     }
 
@@ -377,7 +951,14 @@ impl UnixResourceManager {
         let decompressed = GzDecoder::new(file);
         let mut archive = Archive::new(decompressed);
 
-        let temp_dir = PathBuf::from("/tmp/restore_temp");
+        // NOTE: Unique per call so that concurrent rollbacks do not share a directory.
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let temp_dir =
+            self.tmp_path
+                .join(format!("restore_temp_{}_{}", std::process::id(), timestamp));
         std::fs::create_dir_all(&temp_dir).map_err(|e| {
             ResourceError::RollbackFailed(format!(
                 "Failed to create temp directory {:?}: {}",
@@ -394,90 +975,193 @@ impl UnixResourceManager {
 
         Ok(temp_dir)
     }
+}
 
-    fn read_metadata(&self, temp_dir: &Path) -> Result<Vec<(PathBuf, PathBuf)>, ResourceError> {
-        let metadata_file = temp_dir.join("backup_metadata.json");
-        let metadata_contents = std::fs::read_to_string(&metadata_file).map_err(|e| {
-            ResourceError::RollbackFailed(format!(
-                "Failed to read metadata file {:?}: {}",
-                metadata_file, e
-            ))
-        })?;
-        let metadata = serde_json::from_str(&metadata_contents).map_err(|e| {
-            ResourceError::RollbackFailed(format!(
-                "Failed to parse metadata file {:?}: {}",
-                metadata_file, e
-            ))
-        })?;
-        Ok(metadata)
+#[cfg(windows)]
+pub struct WindowsResourceManager {
+    tmp_path: PathBuf,
+    agent_path: PathBuf,
+}
+
+#[cfg(windows)]
+impl ResourceManagerTrait for WindowsResourceManager {
+    fn tmp_path(&self) -> &PathBuf {
+        &self.tmp_path
     }
 
-    fn move_files_to_original_paths(
-        &self,
-        temp_dir: &Path,
-        metadata: &[(PathBuf, PathBuf)],
-    ) -> Result<(), ResourceError> {
-        for (original_path, relative_path) in metadata {
-            let temp_path = temp_dir.join(relative_path);
-            if temp_path.exists() {
-                if original_path.exists() {
-                    self.remove_directory(original_path).map_err(|e| {
-                        ResourceError::RollbackFailed(format!(
-                            "Failed to remove existing path {:?}: {}",
-                            original_path, e
-                        ))
-                    })?;
-                }
-                // fs::rename does not work with another partition
-                copy_dir_all(&temp_path, original_path).map_err(|e| {
-                    ResourceError::RollbackFailed(format!(
-                        "Failed to move file from {:?} to {:?}: {}",
-                        temp_path, original_path, e
-                    ))
-                })?;
+    fn agent_path(&self) -> &PathBuf {
+        &self.agent_path
+    }
+
+    fn backup(&self) -> Result<BackupOutcome, ResourceError> {
+        let paths_to_backup = self.get_paths_to_backup()?;
+        let metadata = self.generate_metadata(&paths_to_backup);
+        let manifest = self.compute_manifest(&metadata)?;
+        if let Some(latest_backup) = self.get_latest_backup() {
+            if self.read_backup_manifest(&latest_backup).ok().flatten() == Some(manifest.clone()) {
+                log::info!("Nothing changed since backup {:?}", latest_backup);
+                return Ok(BackupOutcome::Unchanged(latest_backup));
             }
         }
-        Ok(())
+        let zip_path = self.create_zip_with_metadata(&metadata, &manifest)?;
+        log::info!("Backup created successfully at {:?}", zip_path);
+        if let Err(e) = self.prune_backups(BACKUPS_TO_KEEP) {
+            log::warn!("Failed to prune old backups: {}", e);
+        }
+        Ok(BackupOutcome::Created(zip_path))
     }
-}
 
-#[cfg(windows)]
-pub struct WindowsResourceManager {
-    tmp_path: PathBuf,
+    fn rollback(&self, backup_file: &Path) -> Result<Vec<PathBuf>, ResourceError> {
+        let temp_dir = self.extract_backup_to_temp(backup_file)?;
+        let metadata = self.read_metadata(&temp_dir)?;
+        let restored = self.move_files_to_original_paths(&temp_dir, &metadata)?;
+
+        log::info!("Rollback completed successfully from {:?}", backup_file);
+        Ok(restored)
+    }
 }
 
 #[cfg(windows)]
-impl ResourceManagerTrait for WindowsResourceManager {
-    fn tmp_path(&self) -> &PathBuf {
-        &self.tmp_path
+impl WindowsResourceManager {
+    pub fn new(agent_path: impl AsRef<Path>) -> Result<Self, ResourceError> {
+        // provisional implementation
+        let tmp_path = PathBuf::from("C:\\Temp\\nodex-agent");
+        fs::create_dir_all(&tmp_path)?;
+        Ok(Self {
+            tmp_path,
+            agent_path: agent_path.as_ref().into(),
+        })
     }
 
-    fn agent_path(&self) -> &PathBuf {
-        unimplemented!()
+    // NOTE: Paths are stored relative to their drive root (e.g. `C:\\`), since
+    // there is no single `/` to strip on Windows.
+    fn generate_metadata(&self, src_paths: &[PathBuf]) -> Vec<(PathBuf, PathBuf)> {
+        src_paths
+            .iter()
+            .map(|path| {
+                let relative_path = path
+                    .ancestors()
+                    .last()
+                    .and_then(|root| path.strip_prefix(root).ok())
+                    .unwrap_or(path)
+                    .to_path_buf();
+                (path.clone(), relative_path)
+            })
+            .collect()
     }
 
-    fn backup(&self) -> Result<(), ResourceError> {
-        unimplemented!()
+    fn create_zip_with_metadata(
+        &self,
+        metadata: &[(PathBuf, PathBuf)],
+        manifest: &BackupManifest,
+    ) -> Result<PathBuf, ResourceError> {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_err(|e| {
+                ResourceError::IoError(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("Failed to get current timestamp: {}", e),
+                ))
+            })?
+            .as_secs();
+
+        let dest_path = self
+            .tmp_path
+            .join(format!("nodex_backup_{}.zip", timestamp));
+        let mut zip = ZipWriter::new(File::create(&dest_path)?);
+        let options = SimpleFileOptions::default();
+
+        for (original_path, relative_path) in metadata {
+            Self::add_path_to_zip(&mut zip, original_path, relative_path, options)?;
+        }
+
+        let metadata: Vec<_> = metadata
+            .iter()
+            .map(|(x, y)| (x.as_path().to_str(), y.as_path().to_str()))
+            .collect();
+        let metadata_json = serde_json::to_string(&metadata).map_err(io::Error::from)?;
+        zip.start_file("backup_metadata.json", options)?;
+        io::Write::write_all(&mut zip, metadata_json.as_bytes())?;
+        let manifest_json = serde_json::to_string(manifest).map_err(io::Error::from)?;
+        zip.start_file("backup_manifest.json", options)?;
+        io::Write::write_all(&mut zip, manifest_json.as_bytes())?;
+        zip.finish()?;
+
+        Ok(dest_path)
     }
 
-    fn rollback(&self, backup_file: &Path) -> Result<(), ResourceError> {
-        unimplemented!()
+    fn add_path_to_zip(
+        zip: &mut ZipWriter<File>,
+        src: &Path,
+        relative_path: &Path,
+        options: SimpleFileOptions,
+    ) -> Result<(), ResourceError> {
+        // NOTE: Zip entry names always use `/` as the separator.
+        let entry_name = relative_path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if src.is_dir() {
+            zip.add_directory(entry_name, options)?;
+            for entry in fs::read_dir(src)? {
+                let entry = entry?;
+                Self::add_path_to_zip(
+                    zip,
+                    &entry.path(),
+                    &relative_path.join(entry.file_name()),
+                    options,
+                )?;
+            }
+        } else if src.is_file() {
+            zip.start_file(entry_name, options)?;
+            io::copy(&mut File::open(src)?, zip)?;
+        }
+        Ok(())
     }
-}
 
-#[cfg(windows)]
-impl WindowsResourceManager {
-    pub fn new() -> Self {
-        // provisional implementation
-        let tmp_path = PathBuf::from("C:\\Temp\\nodex-agent");
-        Self { tmp_path }
+    fn read_backup_manifest(
+        &self,
+        backup_file: &Path,
+    ) -> Result<Option<BackupManifest>, ResourceError> {
+        let mut archive = ZipArchive::new(File::open(backup_file)?)?;
+        let mut entry = match archive.by_name("backup_manifest.json") {
+            Ok(entry) => entry,
+            Err(ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut contents = String::new();
+        io::Read::read_to_string(&mut entry, &mut contents)?;
+        let manifest = serde_json::from_str(&contents).map_err(io::Error::from)?;
+        Ok(Some(manifest))
+    }
+
+    fn extract_backup_to_temp(&self, backup_file: &Path) -> Result<PathBuf, ResourceError> {
+        let archive_data = fs::read(backup_file).map_err(|e| {
+            ResourceError::RollbackFailed(format!(
+                "Failed to open backup file {:?}: {}",
+                backup_file, e
+            ))
+        })?;
+
+        let temp_dir = self.tmp_path.join("restore_temp");
+        self.remove_directory(&temp_dir)?;
+        fs::create_dir_all(&temp_dir).map_err(|e| {
+            ResourceError::RollbackFailed(format!(
+                "Failed to create temp directory {:?}: {}",
+                temp_dir, e
+            ))
+        })?;
+
+        self.extract_zip(Bytes::from(archive_data), &temp_dir)?;
+        Ok(temp_dir)
     }
 }
 
 #[cfg(windows)]
 impl Default for WindowsResourceManager {
     fn default() -> Self {
-        Self::new()
+        Self::new(std::env::current_exe().unwrap()).expect("failed to create resource manager")
     }
 }
 
@@ -497,7 +1181,7 @@ mod tests {
 
     impl Default for UnixResourceManager {
         fn default() -> Self {
-            Self::new(std::env::current_exe().unwrap())
+            Self::new(std::env::current_exe().unwrap()).unwrap()
         }
     }
 
@@ -516,6 +1200,156 @@ mod tests {
         file
     }
 
+    fn create_sample_tar() -> Vec<u8> {
+        let mut builder = Builder::new(Vec::new());
+        let content = b"This is a test file.";
+        let mut header = Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "bin/nodex-agent", &content[..])
+            .unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_extract_archive_zstd_matches_gzip() {
+        let tar = create_sample_tar();
+
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&tar).unwrap();
+        let gz = gz.finish().unwrap();
+        let zst = zstd::encode_all(&tar[..], 0).unwrap();
+
+        let resource_manager = UnixResourceManager::default();
+        let gz_dir = tempdir().unwrap();
+        let zst_dir = tempdir().unwrap();
+        resource_manager
+            .extract_archive(Bytes::from(gz), gz_dir.path())
+            .unwrap();
+        resource_manager
+            .extract_archive(Bytes::from(zst), zst_dir.path())
+            .unwrap();
+
+        let gz_content = fs::read(gz_dir.path().join("bin/nodex-agent")).unwrap();
+        let zst_content = fs::read(zst_dir.path().join("bin/nodex-agent")).unwrap();
+        assert_eq!(gz_content, b"This is a test file.");
+        assert_eq!(gz_content, zst_content);
+    }
+
+    #[test]
+    fn test_extract_archive_unsupported_format() {
+        let resource_manager = UnixResourceManager::default();
+        let temp_dir = tempdir().unwrap();
+        let result =
+            resource_manager.extract_archive(Bytes::from_static(b"plain text"), temp_dir.path());
+        assert!(matches!(
+            result,
+            Err(ResourceError::UnsupportedArchiveFormat)
+        ));
+    }
+
+    #[test]
+    fn test_extract_zip_preserves_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut buffer = Cursor::new(Vec::new());
+        {
+            let mut zip = ZipWriter::new(&mut buffer);
+            let options: FileOptions<ExtendedFileOptions> = FileOptions::default()
+                .compression_method(CompressionMethod::Stored)
+                .unix_permissions(0o755);
+            zip.start_file("helper", options).unwrap();
+            zip.write_all(b"#!/bin/sh\n").unwrap();
+            zip.finish().unwrap();
+        }
+
+        let resource_manager = UnixResourceManager::default();
+        let temp_dir = tempdir().unwrap();
+        resource_manager
+            .extract_zip(Bytes::from(buffer.into_inner()), temp_dir.path())
+            .unwrap();
+
+        let mode = fs::metadata(temp_dir.path().join("helper"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+
+    #[test]
+    fn test_extract_zip_drops_setuid_bit() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut buffer = Cursor::new(Vec::new());
+        {
+            let mut zip = ZipWriter::new(&mut buffer);
+            let options: FileOptions<ExtendedFileOptions> = FileOptions::default()
+                .compression_method(CompressionMethod::Stored)
+                .unix_permissions(0o755);
+            zip.start_file("helper", options).unwrap();
+            zip.write_all(b"#!/bin/sh\n").unwrap();
+            zip.finish().unwrap();
+        }
+        // NOTE: The writer masks permissions to 0o777, so the setuid bit is patched into
+        // the external attributes of the central directory entry.
+        let mut data = buffer.into_inner();
+        let central = data
+            .windows(4)
+            .position(|window| window == b"PK\x01\x02")
+            .unwrap();
+        let attributes = central + 38;
+        data[attributes..attributes + 4].copy_from_slice(&((0o104755u32) << 16).to_le_bytes());
+
+        let resource_manager = UnixResourceManager::default();
+        let temp_dir = tempdir().unwrap();
+        resource_manager
+            .extract_zip(Bytes::from(data), temp_dir.path())
+            .unwrap();
+
+        let mode = fs::metadata(temp_dir.path().join("helper"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o7777, 0o755);
+    }
+
+    #[test]
+    fn test_extract_zip_names_corrupt_entry() {
+        let mut buffer = Cursor::new(Vec::new());
+        {
+            let mut zip = ZipWriter::new(&mut buffer);
+            let options: FileOptions<ExtendedFileOptions> =
+                FileOptions::default().compression_method(CompressionMethod::Stored);
+            zip.start_file("good.txt", options.clone()).unwrap();
+            zip.write_all(b"good content").unwrap();
+            zip.start_file("broken.txt", options).unwrap();
+            zip.write_all(b"BROKEN-CONTENT").unwrap();
+            zip.finish().unwrap();
+        }
+        // NOTE: Flip the stored bytes of the second entry so its CRC check fails.
+        let mut data = buffer.into_inner();
+        let offset = data
+            .windows(b"BROKEN-CONTENT".len())
+            .position(|w| w == b"BROKEN-CONTENT")
+            .unwrap();
+        data[offset] = b'X';
+
+        let resource_manager = UnixResourceManager::default();
+        let temp_dir = tempdir().unwrap();
+        let result = resource_manager.extract_zip(Bytes::from(data), temp_dir.path());
+
+        match result {
+            Err(ResourceError::ExtractEntry { index, name, .. }) => {
+                assert_eq!(index, 1);
+                assert_eq!(name, "broken.txt");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(temp_dir.path().join("good.txt").exists());
+    }
+
     #[tokio::test]
     async fn test_download_update_resources() {
         let sample_zip = create_sample_zip();
@@ -555,6 +1389,215 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_download_update_resources_streaming() {
+        let sample_zip = create_sample_zip();
+        let zip_data = fs::read(sample_zip.path()).unwrap();
+
+        let mut server = mockito::Server::new_async().await;
+        let path = "/test.zip";
+        let _mock = server
+            .mock("GET", path)
+            .with_status(200)
+            .with_body(zip_data)
+            .create();
+
+        let tmp_dir = tempdir().unwrap();
+        let resource_manager = UnixResourceManager {
+            tmp_path: tmp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let output_dir = tempdir().unwrap();
+
+        let url = server.url() + path;
+        resource_manager
+            .download_update_resources_streaming(&url, Some(output_dir.path()))
+            .await
+            .unwrap();
+
+        let content = fs::read_to_string(output_dir.path().join("sample.txt")).unwrap();
+        assert_eq!(content, "This is a test file.");
+        assert_eq!(
+            fs::read_dir(tmp_dir.path()).unwrap().count(),
+            0,
+            "Expected the partial download to be removed"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_update_resources_verified() {
+        let sample_zip = create_sample_zip();
+        let zip_data = fs::read(sample_zip.path()).unwrap();
+        let checksum = hex::encode(Sha256::digest(&zip_data));
+
+        let mut server = mockito::Server::new_async().await;
+        let path = "/test.zip";
+        let _mock = server
+            .mock("GET", path)
+            .with_status(200)
+            .with_body(zip_data)
+            .expect(2)
+            .create();
+
+        let resource_manager = UnixResourceManager::default();
+        let url = server.url() + path;
+
+        let temp_dir = tempdir().unwrap();
+        let result = resource_manager
+            .download_update_resources_verified(&url, Some(temp_dir.path()), &checksum)
+            .await;
+        assert!(result.is_ok(), "Expected checksum verification to succeed");
+        assert!(temp_dir.path().join("sample.txt").exists());
+
+        let temp_dir = tempdir().unwrap();
+        let wrong = "0".repeat(64);
+        let result = resource_manager
+            .download_update_resources_verified(&url, Some(temp_dir.path()), &wrong)
+            .await;
+        match result {
+            Err(ResourceError::ChecksumMismatch { expected, actual }) => {
+                assert_eq!(expected, wrong);
+                assert_eq!(actual, checksum);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(!temp_dir.path().join("sample.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_download_with_retry_fails_fast_on_not_found() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/missing.zip")
+            .with_status(404)
+            .expect(1)
+            .create();
+
+        let resource_manager = UnixResourceManager::default();
+        let temp_dir = tempdir().unwrap();
+        let url = server.url() + "/missing.zip";
+        let result = resource_manager
+            .download_update_resources_with_retry(
+                &url,
+                Some(temp_dir.path()),
+                3,
+                Duration::from_millis(10),
+            )
+            .await;
+
+        assert!(matches!(result, Err(ResourceError::DownloadFailed(_))));
+        mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_download_with_retry_retries_connection_errors() {
+        // NOTE: Bind and release a port so that connecting to it is refused.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let url = format!("http://127.0.0.1:{}/test.zip", port);
+
+        let resource_manager = UnixResourceManager::default();
+        let temp_dir = tempdir().unwrap();
+        let started = std::time::Instant::now();
+        let result = resource_manager
+            .download_update_resources_with_retry(
+                &url,
+                Some(temp_dir.path()),
+                2,
+                Duration::from_millis(20),
+            )
+            .await;
+
+        match result {
+            Err(ResourceError::DownloadFailed(message)) => {
+                assert!(message.starts_with(&url), "{}", message);
+                assert!(
+                    message.len() > url.len(),
+                    "Expected the last error in {}",
+                    message
+                );
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+        // 20ms + 40ms of backoff between the three attempts.
+        assert!(started.elapsed() >= Duration::from_millis(60));
+    }
+
+    #[tokio::test]
+    async fn test_download_with_retry_retries_truncated_body() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let sample_zip = create_sample_zip();
+        let zip_data = fs::read(sample_zip.path()).unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/test.zip", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            // NOTE: The first response announces the full length but the connection is
+            // closed halfway through the body.
+            for body in [&zip_data[..zip_data.len() / 2], &zip_data[..]] {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await.unwrap();
+                let header = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    zip_data.len()
+                );
+                stream.write_all(header.as_bytes()).await.unwrap();
+                stream.write_all(body).await.unwrap();
+                stream.shutdown().await.unwrap();
+            }
+        });
+
+        let resource_manager = UnixResourceManager::default();
+        let temp_dir = tempdir().unwrap();
+        resource_manager
+            .download_update_resources_with_retry(
+                &url,
+                Some(temp_dir.path()),
+                2,
+                Duration::from_millis(10),
+            )
+            .await
+            .unwrap();
+
+        server.await.unwrap();
+        let content = fs::read_to_string(temp_dir.path().join("sample.txt")).unwrap();
+        assert_eq!(content, "This is a test file.");
+    }
+
+    #[tokio::test]
+    async fn test_download_file_verified_times_out() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/helper", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (_stream, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        let temp_dir = tempdir().unwrap();
+        let dest = temp_dir.path().join("helper");
+        let started = std::time::Instant::now();
+        let result =
+            download_file_verified_with_client(&client, &url, &dest, &"0".repeat(64)).await;
+
+        assert!(
+            matches!(result, Err(ResourceError::DownloadFailed(_))),
+            "{:?}",
+            result
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+        server.abort();
+    }
+
     #[test]
     fn test_collect_downloaded_bundles() {
         let temp_dir = tempdir().unwrap();
@@ -633,6 +1676,136 @@ mod tests {
         assert_eq!(backups.len(), 1, "Expected exactly one backup file");
     }
 
+    #[test]
+    fn test_backup_skipped_when_unchanged() {
+        let temp_dir = tempdir().unwrap();
+        let agent_dir = tempdir().unwrap();
+        let agent_path = agent_dir.path().join("nodex-agent");
+        fs::write(&agent_path, b"v1").unwrap();
+        let resource_manager = UnixResourceManager {
+            tmp_path: temp_dir.path().to_path_buf(),
+            agent_path: agent_path.clone(),
+        };
+
+        let first = resource_manager.backup().unwrap();
+        let BackupOutcome::Created(first_path) = first else {
+            panic!("Expected the first backup to be created");
+        };
+
+        let second = resource_manager.backup().unwrap();
+        assert_eq!(second, BackupOutcome::Unchanged(first_path.clone()));
+
+        // NOTE: Backup file names have second resolution.
+        std::thread::sleep(Duration::from_millis(1100));
+        fs::write(&agent_path, b"v2").unwrap();
+        let third = resource_manager.backup().unwrap();
+        match third {
+            BackupOutcome::Created(path) => assert_ne!(path, first_path),
+            other => panic!("Expected a new backup, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_prune_backups() {
+        let temp_dir = tempdir().unwrap();
+        let resource_manager = UnixResourceManager {
+            tmp_path: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        for timestamp in [100, 300, 200, 500, 400] {
+            let name = format!("nodex_backup_{}.tar.gz", timestamp);
+            File::create(temp_dir.path().join(name)).unwrap();
+        }
+        let unrelated = [
+            "nodex_backup_latest.tar.gz",
+            "nodex_backup_100.zip",
+            "other.tar.gz",
+        ];
+        for name in unrelated {
+            File::create(temp_dir.path().join(name)).unwrap();
+        }
+
+        let mut removed = resource_manager.prune_backups(2).unwrap();
+        removed.sort();
+        let expected: Vec<PathBuf> = [100, 200, 300]
+            .iter()
+            .map(|t| temp_dir.path().join(format!("nodex_backup_{}.tar.gz", t)))
+            .collect();
+        assert_eq!(removed, expected);
+
+        assert!(temp_dir.path().join("nodex_backup_400.tar.gz").exists());
+        assert!(temp_dir.path().join("nodex_backup_500.tar.gz").exists());
+        for name in unrelated {
+            assert!(temp_dir.path().join(name).exists(), "{} was removed", name);
+        }
+    }
+
+    fn create_backup_with_metadata(dir: &Path, metadata: &[(PathBuf, PathBuf)]) -> PathBuf {
+        let backup_path = dir.join("nodex_backup_0.tar.gz");
+        let encoder = GzEncoder::new(File::create(&backup_path).unwrap(), Compression::default());
+        let mut builder = Builder::new(encoder);
+
+        let json = serde_json::to_string(metadata).unwrap();
+        let mut header = Header::new_gnu();
+        header.set_size(json.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "backup_metadata.json", json.as_bytes())
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+        backup_path
+    }
+
+    #[test]
+    fn test_rollback_rejects_path_traversal() {
+        let temp_dir = tempdir().unwrap();
+        let resource_manager = UnixResourceManager {
+            tmp_path: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let target_dir = tempdir().unwrap();
+
+        let cases = [
+            PathBuf::from("../../etc/passwd"),
+            PathBuf::from("nodex/../../etc/passwd"),
+            PathBuf::from("/etc/passwd"),
+        ];
+        for relative_path in cases {
+            let original_path = target_dir.path().join("passwd");
+            let backup =
+                create_backup_with_metadata(temp_dir.path(), &[(original_path, relative_path)]);
+
+            let result = resource_manager.rollback(&backup);
+            assert!(
+                matches!(result, Err(ResourceError::RollbackFailed(_))),
+                "Expected rollback to be rejected, got {:?}",
+                result
+            );
+        }
+        assert!(!target_dir.path().join("passwd").exists());
+    }
+
+    #[test]
+    fn test_move_files_rejects_symlink_escape() {
+        let temp_dir = tempdir().unwrap();
+        let outside_dir = tempdir().unwrap();
+        fs::write(outside_dir.path().join("secret"), b"secret").unwrap();
+        std::os::unix::fs::symlink(outside_dir.path(), temp_dir.path().join("link")).unwrap();
+
+        let resource_manager = UnixResourceManager::default();
+        let target_dir = tempdir().unwrap();
+        let metadata = vec![(
+            target_dir.path().join("secret"),
+            PathBuf::from("link/secret"),
+        )];
+
+        let result = resource_manager.move_files_to_original_paths(temp_dir.path(), &metadata);
+        assert!(matches!(result, Err(ResourceError::RollbackFailed(_))));
+        assert!(!target_dir.path().join("secret").exists());
+    }
+
     #[test]
     fn test_rollback() {
         let temp_dir = tempdir().unwrap();
@@ -646,12 +1819,93 @@ mod tests {
 
         assert!(latest_backup.is_some(), "Expected a backup to exist");
         if let Some(backup) = latest_backup {
-            let result: Result<(), ResourceError> = resource_manager.rollback(&backup);
+            let result: Result<Vec<PathBuf>, ResourceError> = resource_manager.rollback(&backup);
             println!("Result: {:?}", result);
             assert!(result.is_ok(), "Expected rollback to succeed");
         }
     }
 
+    #[test]
+    fn test_rollback_verifies_restored_agent() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempdir().unwrap();
+        let agent_dir = tempdir().unwrap();
+        let agent_path = agent_dir.path().join("nodex-agent");
+        fs::write(&agent_path, b"v1").unwrap();
+        fs::set_permissions(&agent_path, fs::Permissions::from_mode(0o755)).unwrap();
+        let resource_manager = UnixResourceManager::new(&agent_path)
+            .unwrap()
+            .with_tmp_path(temp_dir.path().to_path_buf())
+            .unwrap();
+        let metadata = resource_manager
+            .generate_metadata(&[agent_path.clone()])
+            .unwrap();
+
+        // A matching manifest restores cleanly.
+        let manifest = resource_manager.compute_manifest(&metadata).unwrap();
+        let backup = resource_manager
+            .create_tar_gz_with_metadata(&metadata, &manifest)
+            .unwrap();
+        fs::write(&agent_path, b"v2").unwrap();
+        resource_manager.rollback(&backup).unwrap();
+        assert_eq!(fs::read(&agent_path).unwrap(), b"v1");
+        fs::remove_file(&backup).unwrap();
+
+        // A manifest that disagrees with the archived binary means the backup is corrupt.
+        let mut corrupt = manifest.clone();
+        corrupt
+            .values_mut()
+            .for_each(|hash| *hash = "00".repeat(32));
+        let backup = resource_manager
+            .create_tar_gz_with_metadata(&metadata, &corrupt)
+            .unwrap();
+        let result = resource_manager.rollback(&backup);
+        assert!(
+            matches!(result, Err(ResourceError::RollbackFailed(_))),
+            "{:?}",
+            result
+        );
+        fs::remove_file(&backup).unwrap();
+
+        // A binary restored without its executable bit is rejected too.
+        fs::set_permissions(&agent_path, fs::Permissions::from_mode(0o644)).unwrap();
+        let backup = resource_manager
+            .create_tar_gz_with_metadata(&metadata, &manifest)
+            .unwrap();
+        let result = resource_manager.rollback(&backup);
+        assert!(
+            matches!(result, Err(ResourceError::RollbackFailed(_))),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_extract_tar_to_temp_uses_tmp_path() {
+        let temp_dir = tempdir().unwrap();
+        let agent_dir = tempdir().unwrap();
+        let agent_path = agent_dir.path().join("nodex-agent");
+        fs::write(&agent_path, b"v1").unwrap();
+        let resource_manager = UnixResourceManager::new(&agent_path)
+            .unwrap()
+            .with_tmp_path(temp_dir.path().to_path_buf())
+            .unwrap();
+
+        let BackupOutcome::Created(backup) = resource_manager.backup().unwrap() else {
+            panic!("Expected a backup to be created");
+        };
+
+        let first = resource_manager.extract_tar_to_temp(&backup).unwrap();
+        let second = resource_manager.extract_tar_to_temp(&backup).unwrap();
+        assert!(first.starts_with(temp_dir.path()));
+        assert!(second.starts_with(temp_dir.path()));
+        assert_ne!(
+            first, second,
+            "Expected each restore directory to be unique"
+        );
+    }
+
     #[test]
     fn test_remove() {
         let temp_dir = tempdir().unwrap();
@@ -3,8 +3,11 @@ use chrono::{DateTime, FixedOffset, Utc};
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tokio::sync::watch;
 
+const DEFAULT_TERMINATE_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct RuntimeInfo {
     pub state: State,
@@ -19,12 +22,36 @@ pub enum State {
     Rollback,
 }
 
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("invalid state transition: {from:?} -> {to:?}")]
+pub struct InvalidTransition {
+    pub from: State,
+    pub to: State,
+}
+
+// Update -> Rollback happens when an update fails; every state may fall back to Idle.
+// Anything else (e.g. Rollback -> Update) means we resumed from an inconsistent file.
+pub fn transition(from: State, to: State) -> Result<(), InvalidTransition> {
+    match (from, to) {
+        (from, to) if from == to => Ok(()),
+        (_, State::Idle) | (State::Idle, State::Update) | (State::Update, State::Rollback) => {
+            Ok(())
+        }
+        (from, to) => Err(InvalidTransition { from, to }),
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct ProcessInfo {
     pub process_id: u32,
     pub executed_at: DateTime<FixedOffset>,
     pub version: Version,
     pub feat_type: FeatType,
+    // Empty for entries written before these fields existed.
+    #[serde(default)]
+    pub exe_path: PathBuf,
+    #[serde(default)]
+    pub started_cmdline: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -38,10 +65,34 @@ pub enum NodexSignal {
     SendFd,
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum TerminateError {
+    #[error("process {0} had already exited")]
+    AlreadyGone(u32),
+    #[error("process {0} did not exit after SIGTERM and was killed")]
+    ForceKilled(u32),
+    #[error("failed to signal process {0}: {1}")]
+    Signal(u32, #[source] std::io::Error),
+}
+
 pub trait ProcessManager: Clone {
     fn is_running(&self, process_id: u32) -> bool;
+    /// Like `is_running`, but also checks that the PID still belongs to `exe_path`
+    /// where the platform allows it, guarding against PID reuse.
+    fn is_running_as(&self, process_id: u32, _exe_path: &Path) -> bool {
+        self.is_running(process_id)
+    }
     fn spawn_process(&self, cmd: impl AsRef<Path>, args: &[&str]) -> Result<u32, std::io::Error>;
     fn kill_process(&self, process_id: u32, signal: NodexSignal) -> Result<(), std::io::Error>;
+    /// Asks the process to terminate and, where supported, waits up to `timeout`
+    /// before killing it outright.
+    fn terminate(&self, process_id: u32, _timeout: Duration) -> Result<(), TerminateError> {
+        if !self.is_running(process_id) {
+            return Err(TerminateError::AlreadyGone(process_id));
+        }
+        self.kill_process(process_id, NodexSignal::Terminate)
+            .map_err(|e| TerminateError::Signal(process_id, e))
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -76,6 +127,10 @@ pub enum RuntimeError {
     AlreadyExistController,
     #[error(transparent)]
     SemVer(#[from] semver::Error),
+    #[error(transparent)]
+    InvalidTransition(#[from] InvalidTransition),
+    #[error(transparent)]
+    Terminate(#[from] TerminateError),
     #[cfg(unix)]
     #[error("Failed to bind UDS: {0}")]
     BindUdsError(#[source] std::io::Error),
@@ -122,6 +177,8 @@ pub trait RuntimeManagerWithoutAsync {
     fn kill_process(&mut self, process_info: &ProcessInfo) -> Result<(), RuntimeError>;
 
     fn kill_other_agents(&mut self, target: u32) -> Result<(), RuntimeError>;
+
+    fn prune_dead_processes(&mut self) -> Result<Vec<ProcessInfo>, RuntimeError>;
 }
 
 #[trait_variant::make(Send)]
@@ -141,6 +198,9 @@ where
     uds_path: PathBuf,
     meta_uds_path: PathBuf,
     state_sender: watch::Sender<State>,
+    terminate_timeout: Duration,
+    #[cfg(unix)]
+    request_timeout: Duration,
 }
 
 impl<H, P> RuntimeManager for RuntimeManagerImpl<H, P>
@@ -150,8 +210,12 @@ where
 {
     async fn get_version(&self) -> Result<Version, RuntimeError> {
         #[cfg(unix)]
-        let version_response: VersionResponse =
-            crate::unix_utils::get_request(&self.uds_path, "/internal/version/get").await?;
+        let version_response: VersionResponse = crate::unix_utils::get_request_with_timeout(
+            &self.uds_path,
+            "/internal/version/get",
+            self.request_timeout,
+        )
+        .await?;
         #[cfg(windows)]
         let version_response = VersionResponse {
             version: "9.9.9".to_string(),
@@ -180,10 +244,10 @@ where
                 let _ = std::fs::remove_file(&self.meta_uds_path);
             }
         }
-        let current_exe = &self.get_runtime_info()?.exec_path;
+        let current_exe = self.get_runtime_info()?.exec_path;
         let child = self
             .process_manager
-            .spawn_process(current_exe, &["controlled"])
+            .spawn_process(&current_exe, &["controlled"])
             .map_err(RuntimeError::Fork)?;
 
         #[cfg(unix)]
@@ -210,7 +274,12 @@ where
             crate::unix_utils::send_fd(stream, listener)
                 .map_err(|e| RuntimeError::BindUdsError(e.into()))?;
         }
-        let process_info = ProcessInfo::new(child, FeatType::Agent);
+        let cmdline = vec![
+            current_exe.to_string_lossy().into_owned(),
+            "controlled".to_string(),
+        ];
+        let process_info =
+            ProcessInfo::new(child, FeatType::Agent).with_command(current_exe, cmdline);
         self.add_process_info(process_info.clone())?;
         Ok(process_info)
     }
@@ -225,6 +294,17 @@ where
         } else {
             NodexSignal::Terminate
         };
+        if !self
+            .process_manager
+            .is_running_as(process_info.process_id, &process_info.exe_path)
+        {
+            log::warn!(
+                "PID {} is no longer {:?}, not signaling it",
+                process_info.process_id,
+                process_info.exe_path
+            );
+            return self.remove_process_info(process_info.process_id);
+        }
         self.process_manager
             .kill_process(process_info.process_id, signal)
             .map_err(RuntimeError::Kill)?;
@@ -234,6 +314,7 @@ where
 
     fn update_state_without_send(&mut self, state: State) -> Result<(), RuntimeError> {
         self.file_handler.apply_with_lock(|runtime_info| {
+            transition(runtime_info.state, state)?;
             runtime_info.state = state;
             Ok(())
         })
@@ -249,6 +330,24 @@ where
         self.kill_others(target, Some(FeatType::Agent))
     }
 
+    fn prune_dead_processes(&mut self) -> Result<Vec<ProcessInfo>, RuntimeError> {
+        let process_manager = &self.process_manager;
+        let mut removed = vec![];
+        self.file_handler.apply_with_lock(|runtime_info| {
+            removed = runtime_info
+                .prune_dead_processes(|p| process_manager.is_running_as(p.process_id, &p.exe_path));
+            Ok(())
+        })?;
+        for process_info in &removed {
+            log::warn!(
+                "Removed stale {:?} process {} from runtime info",
+                process_info.feat_type,
+                process_info.process_id
+            );
+        }
+        Ok(removed)
+    }
+
     fn launch_controller(
         &mut self,
         new_controller_path: impl AsRef<Path>,
@@ -290,9 +389,12 @@ where
             process_manager,
             uds_path: uds_path.as_ref().into(),
             meta_uds_path,
+            terminate_timeout: DEFAULT_TERMINATE_TIMEOUT,
+            #[cfg(unix)]
+            request_timeout: crate::unix_utils::DEFAULT_REQUEST_TIMEOUT,
         };
         // We assume that caller is controller.
-        runtime_manager.cleanup_process_info()?;
+        runtime_manager.prune_dead_processes()?;
         let runtime_info = runtime_manager.file_handler.read()?;
         let controller_processes: Vec<_> = runtime_info
             .filter_by_feat(FeatType::Controller)
@@ -301,7 +403,9 @@ where
         if !controller_processes.is_empty() {
             return Err(RuntimeError::AlreadyExistController);
         }
-        let self_info = ProcessInfo::new(self_pid, FeatType::Controller);
+        let self_exe = std::env::current_exe().map_err(RuntimeError::FailedCurrentExe)?;
+        let self_info = ProcessInfo::new(self_pid, FeatType::Controller)
+            .with_command(self_exe, std::env::args().collect());
         runtime_manager.add_process_info(self_info)?;
         Ok((runtime_manager, state_receiver))
     }
@@ -317,9 +421,25 @@ where
             process_manager,
             uds_path: "".into(),
             meta_uds_path: "".into(),
+            terminate_timeout: DEFAULT_TERMINATE_TIMEOUT,
+            #[cfg(unix)]
+            request_timeout: crate::unix_utils::DEFAULT_REQUEST_TIMEOUT,
         }
     }
 
+    /// How long `cleanup_all` waits for a process to exit after SIGTERM before killing it.
+    pub fn with_terminate_timeout(mut self, timeout: Duration) -> Self {
+        self.terminate_timeout = timeout;
+        self
+    }
+
+    /// How long to wait for the agent to answer over the UDS before treating it as failed.
+    #[cfg(unix)]
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
     fn add_process_info(&mut self, process_info: ProcessInfo) -> Result<(), RuntimeError> {
         self.file_handler
             .apply_with_lock(|runtime_info| runtime_info.add_process_info(process_info))
@@ -331,52 +451,70 @@ where
     }
 
     // Kill all related processes
-    pub fn cleanup_all(&mut self) -> Result<(), RuntimeError> {
+    pub async fn cleanup_all(&mut self) -> Result<(), RuntimeError>
+    where
+        P: Send + 'static,
+    {
         #[cfg(unix)]
         {
             crate::unix_utils::remove_file_if_exists(&self.uds_path);
             crate::unix_utils::remove_file_if_exists(&self.meta_uds_path);
         }
+        // NOTE: Only the bookkeeping is done under the file lock. Waiting for the
+        // processes to exit can take up to twice the timeout each, so it happens after.
         let process_manager = &self.process_manager;
-        self.file_handler.apply_with_lock(move |runtime_info| {
-            let mut errs = vec![];
+        let mut targets = vec![];
+        self.file_handler.apply_with_lock(|runtime_info| {
             for info in runtime_info.process_infos.iter_mut() {
-                if let Some(info) = info {
-                    if let Err(err) =
-                        process_manager.kill_process(info.process_id, NodexSignal::Terminate)
-                    {
-                        errs.push(RuntimeError::Kill(err));
-                    }
+                if let Some(info) = info
+                    .take()
+                    .filter(|info| process_manager.is_running_as(info.process_id, &info.exe_path))
+                {
+                    targets.push(info);
                 }
-                *info = None;
             }
             runtime_info.state = State::Idle;
-            if errs.is_empty() {
-                Ok(())
-            } else {
-                Err(RuntimeError::Kills(errs))
+            Ok(())
+        })?;
+
+        let mut errs = vec![];
+        let mut handles = vec![];
+        for info in targets {
+            // We handle our own SIGTERM, so waiting on ourselves would only end in SIGKILL.
+            if info.process_id == self.self_pid {
+                if let Err(err) = self
+                    .process_manager
+                    .kill_process(info.process_id, NodexSignal::Terminate)
+                {
+                    errs.push(RuntimeError::Kill(err));
+                }
+                continue;
             }
-        })
+            let process_manager = self.process_manager.clone();
+            let timeout = self.terminate_timeout;
+            handles.push(tokio::task::spawn_blocking(move || {
+                process_manager.terminate(info.process_id, timeout)
+            }));
+        }
+        for handle in handles {
+            match handle.await {
+                Ok(Ok(())) | Ok(Err(TerminateError::AlreadyGone(_))) => {}
+                Ok(Err(err @ TerminateError::ForceKilled(_))) => log::warn!("{}", err),
+                Ok(Err(err)) => errs.push(RuntimeError::Terminate(err)),
+                Err(err) => errs.push(RuntimeError::Kill(err.into())),
+            }
+        }
+        if errs.is_empty() {
+            Ok(())
+        } else {
+            Err(RuntimeError::Kills(errs))
+        }
     }
 
     pub fn cleanup(&mut self) -> Result<(), RuntimeError> {
         self.remove_process_info(self.self_pid)
     }
 
-    fn cleanup_process_info(&mut self) -> Result<(), RuntimeError> {
-        let process_manager = &self.process_manager;
-        self.file_handler.apply_with_lock(|runtime_info| {
-            for process_info in runtime_info.process_infos.iter_mut() {
-                if let Some(ref p) = process_info {
-                    if !process_manager.is_running(p.process_id) {
-                        *process_info = None;
-                    }
-                }
-            }
-            Ok(())
-        })
-    }
-
     fn kill_others(
         &mut self,
         target: u32,
@@ -416,8 +554,16 @@ impl ProcessInfo {
             executed_at: now,
             version,
             feat_type,
+            exe_path: PathBuf::new(),
+            started_cmdline: vec![],
         }
     }
+
+    pub fn with_command(mut self, exe_path: impl Into<PathBuf>, cmdline: Vec<String>) -> Self {
+        self.exe_path = exe_path.into();
+        self.started_cmdline = cmdline;
+        self
+    }
 }
 
 impl RuntimeInfo {
@@ -457,6 +603,22 @@ impl RuntimeInfo {
         }
     }
 
+    /// Drops entries whose PID is no longer alive and returns them.
+    pub fn prune_dead_processes(
+        &mut self,
+        is_running: impl Fn(&ProcessInfo) -> bool,
+    ) -> Vec<ProcessInfo> {
+        let (alive, dead): (Vec<_>, Vec<_>) = self
+            .process_infos
+            .iter_mut()
+            .filter_map(Option::take)
+            .partition(|p| is_running(p));
+        for (slot, info) in self.process_infos.iter_mut().zip(alive) {
+            *slot = Some(info);
+        }
+        dead
+    }
+
     pub fn find_process_info(&self, process_id: u32) -> Option<&ProcessInfo> {
         self.process_infos
             .iter()
@@ -528,6 +690,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_prune_dead_processes() {
+        let alive = ProcessInfo::new(12345, FeatType::Controller);
+        let dead = ProcessInfo::new(67890, FeatType::Agent);
+        let mut runtime_info = RuntimeInfo {
+            state: State::Idle,
+            process_infos: [Some(dead.clone()), None, Some(alive.clone()), None],
+            exec_path: std::env::current_exe().unwrap(),
+        };
+
+        let removed = runtime_info.prune_dead_processes(|p| p.process_id == 12345);
+
+        assert_eq!(removed, vec![dead]);
+        assert_eq!(runtime_info.process_infos, [Some(alive), None, None, None]);
+        assert!(!runtime_info.is_agent_running());
+    }
+
     #[test]
     fn test_filter_process_infos() {
         let mut runtime_info = RuntimeInfo {
@@ -555,6 +734,49 @@ mod tests {
         assert_eq!(controllers[0].process_id, 67890);
     }
 
+    #[test]
+    fn test_state_transition() {
+        assert!(transition(State::Idle, State::Update).is_ok());
+        assert!(transition(State::Update, State::Rollback).is_ok());
+        assert!(transition(State::Update, State::Idle).is_ok());
+        assert!(transition(State::Rollback, State::Idle).is_ok());
+        assert!(transition(State::Update, State::Update).is_ok());
+
+        assert_eq!(
+            transition(State::Rollback, State::Update),
+            Err(InvalidTransition {
+                from: State::Rollback,
+                to: State::Update,
+            })
+        );
+        assert!(transition(State::Idle, State::Rollback).is_err());
+    }
+
+    #[test]
+    fn test_process_info_without_command_fields() {
+        let json = r#"{
+            "process_id": 12345,
+            "executed_at": "2024-01-01T00:00:00+09:00",
+            "version": "1.0.0",
+            "feat_type": "Agent"
+        }"#;
+        let process_info: ProcessInfo = serde_json::from_str(json).unwrap();
+        assert_eq!(process_info.exe_path, PathBuf::new());
+        assert!(process_info.started_cmdline.is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_is_running_as_checks_exe() {
+        use crate::managers::unix_process_manager::UnixProcessManager;
+
+        let pid = std::process::id();
+        let exe = std::env::current_exe().unwrap();
+        assert!(UnixProcessManager.is_running_as(pid, &exe));
+        assert!(UnixProcessManager.is_running_as(pid, Path::new("")));
+        assert!(!UnixProcessManager.is_running_as(pid, Path::new("/usr/bin/not-nodex")));
+    }
+
     #[test]
     fn test_version_format() {
         assert!(Version::parse(env!("CARGO_PKG_VERSION")).is_ok());
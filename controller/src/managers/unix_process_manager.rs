@@ -1,14 +1,44 @@
-use super::runtime::{NodexSignal, ProcessManager};
+use super::runtime::{NodexSignal, ProcessManager, TerminateError};
 use nix::{
-    sys::signal::{self, Signal},
+    errno::Errno,
+    sys::{
+        signal::{self, Signal},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
     unistd::{execvp, fork, setsid, ForkResult, Pid},
 };
 use std::ffi::CString;
 use std::path::Path;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
 
 #[derive(Clone)]
 pub struct UnixProcessManager;
 
+// Agents are our children, so they linger as zombies until reaped; try that first.
+fn has_exited(pid: Pid) -> bool {
+    match waitpid(pid, Some(WaitPidFlag::WNOHANG)) {
+        Ok(WaitStatus::StillAlive) => false,
+        Ok(_) => true,
+        Err(Errno::ECHILD) => signal::kill(pid, None).is_err(),
+        Err(_) => false,
+    }
+}
+
+fn wait_for_exit(pid: Pid, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if has_exited(pid) {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
 #[inline]
 fn nule_to_ioe(e: std::ffi::NulError) -> std::io::Error {
     std::io::Error::new(std::io::ErrorKind::InvalidInput, e)
@@ -22,6 +52,23 @@ impl ProcessManager for UnixProcessManager {
             Err(_) => false,
         }
     }
+    fn is_running_as(&self, process_id: u32, exe_path: &Path) -> bool {
+        if !self.is_running(process_id) {
+            return false;
+        }
+        if exe_path.as_os_str().is_empty() {
+            return true;
+        }
+        // Without procfs (e.g. macOS) we can only go by the PID.
+        let Ok(actual) = std::fs::read_link(format!("/proc/{}/exe", process_id)) else {
+            return true;
+        };
+        // The binary may have been replaced by an update since the process started.
+        let actual = actual.to_string_lossy();
+        let actual = Path::new(actual.trim_end_matches(" (deleted)"));
+        let expected = std::fs::canonicalize(exe_path).unwrap_or_else(|_| exe_path.into());
+        actual == expected
+    }
     fn spawn_process(&self, cmd: impl AsRef<Path>, args: &[&str]) -> Result<u32, std::io::Error> {
         let cmd = CString::new(cmd.as_ref().to_string_lossy().as_ref()).map_err(nule_to_ioe)?;
         let args: Result<Vec<_>, _> = args
@@ -50,4 +97,67 @@ impl ProcessManager for UnixProcessManager {
         signal::kill(Pid::from_raw(process_id as i32), signal)
             .map_err(|e| std::io::Error::from_raw_os_error(e as _))
     }
+    fn terminate(&self, process_id: u32, timeout: Duration) -> Result<(), TerminateError> {
+        let pid = Pid::from_raw(process_id as i32);
+        match signal::kill(pid, Signal::SIGTERM) {
+            Ok(()) => {}
+            Err(Errno::ESRCH) => return Err(TerminateError::AlreadyGone(process_id)),
+            Err(e) => return Err(TerminateError::Signal(process_id, e.into())),
+        }
+        if wait_for_exit(pid, timeout) {
+            return Ok(());
+        }
+        log::warn!(
+            "Process {} did not exit within {:?}, sending SIGKILL",
+            process_id,
+            timeout
+        );
+        match signal::kill(pid, Signal::SIGKILL) {
+            Ok(()) | Err(Errno::ESRCH) => {}
+            Err(e) => return Err(TerminateError::Signal(process_id, e.into())),
+        }
+        // SIGKILL cannot be ignored; this only reaps the zombie.
+        let _ = wait_for_exit(pid, timeout);
+        Err(TerminateError::ForceKilled(process_id))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_terminate_graceful() {
+        let child = Command::new("sleep").arg("30").spawn().unwrap();
+        let result = UnixProcessManager.terminate(child.id(), Duration::from_secs(5));
+        assert!(result.is_ok(), "{:?}", result);
+        assert!(!UnixProcessManager.is_running(child.id()));
+    }
+
+    #[test]
+    fn test_terminate_escalates_to_sigkill() {
+        let child = Command::new("sh")
+            .args(["-c", "trap '' TERM; while :; do sleep 1; done"])
+            .spawn()
+            .unwrap();
+        // Give the shell a moment to install the trap.
+        std::thread::sleep(Duration::from_millis(300));
+
+        let result = UnixProcessManager.terminate(child.id(), Duration::from_millis(300));
+        assert!(
+            matches!(result, Err(TerminateError::ForceKilled(pid)) if pid == child.id()),
+            "{:?}",
+            result
+        );
+        assert!(!UnixProcessManager.is_running(child.id()));
+    }
+
+    #[test]
+    fn test_terminate_already_gone() {
+        let mut child = Command::new("true").spawn().unwrap();
+        child.wait().unwrap();
+        let result = UnixProcessManager.terminate(child.id(), Duration::from_millis(100));
+        assert!(matches!(result, Err(TerminateError::AlreadyGone(_))));
+    }
 }
@@ -1,3 +1,4 @@
+use crate::managers::resource::ResourceError;
 use crate::managers::runtime::{RuntimeError, RuntimeManager, State};
 use crate::state::{idle, rollback, update};
 
@@ -17,6 +18,8 @@ pub enum StateHandlerError {
     Idle(#[from] idle::IdleError),
     #[error("failed to get runtime info: {0}")]
     RuntimeInfo(#[from] RuntimeError),
+    #[error("failed to prepare resource manager: {0}")]
+    Resource(#[from] ResourceError),
 }
 
 pub async fn handle_state<R: RuntimeManager>(
@@ -25,9 +28,9 @@ pub async fn handle_state<R: RuntimeManager>(
 ) -> Result<(), StateHandlerError> {
     let agent_path = runtime_manager.get_runtime_info()?.exec_path;
     #[cfg(unix)]
-    let resource_manager = UnixResourceManager::new(agent_path);
+    let resource_manager = UnixResourceManager::new(agent_path)?;
     #[cfg(windows)]
-    let resource_manager = WindowsResourceManager::new();
+    let resource_manager = WindowsResourceManager::new(agent_path)?;
 
     match state {
         State::Update => {
@@ -36,7 +39,12 @@ pub async fn handle_state<R: RuntimeManager>(
             // runtime_manager.update_state(crate::managers::runtime::State::Rollback)?;
         }
         State::Rollback => {
-            rollback::execute(&resource_manager, runtime_manager).await?;
+            let summary = rollback::execute(&resource_manager, runtime_manager).await?;
+            log::info!(
+                "Restored {} path(s) from {:?}",
+                summary.restored_paths.len(),
+                summary.backup_file
+            );
         }
         State::Idle => {
             idle::execute(runtime_manager).await?;
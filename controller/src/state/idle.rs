@@ -7,6 +7,8 @@ pub enum IdleError {
 }
 
 pub async fn execute<T: RuntimeManager>(runtime_manager: &mut T) -> Result<(), IdleError> {
+    // A crashed agent would otherwise keep looking "already running" forever.
+    runtime_manager.prune_dead_processes()?;
     if !runtime_manager.get_runtime_info()?.is_agent_running() {
         let _process_info = runtime_manager.launch_agent(true)?;
     } else {
@@ -62,4 +64,33 @@ mod tests {
         let result = execute(&mut runtime_manager).await;
         assert!(result.is_ok(), "Expected Ok result, got {:?}", result);
     }
+
+    #[tokio::test]
+    async fn test_execute_relaunches_after_agent_crash() {
+        let runtime_info = RuntimeInfo {
+            state: State::Idle,
+            process_infos: [
+                Some(ProcessInfo::new(12345, FeatType::Agent)),
+                None,
+                None,
+                None,
+            ],
+            exec_path: std::env::current_exe().unwrap(),
+        };
+        let mut runtime_manager = MockRuntimeManager::new(runtime_info);
+        runtime_manager.dead_pids = vec![12345];
+
+        let result = execute(&mut runtime_manager).await;
+        assert!(result.is_ok(), "Expected Ok result, got {:?}", result);
+
+        let process_infos: Vec<_> = runtime_manager
+            .runtime_info
+            .process_infos
+            .iter()
+            .flatten()
+            .collect();
+        assert_eq!(process_infos.len(), 1);
+        assert_eq!(process_infos[0].feat_type, FeatType::Agent);
+        assert_eq!(process_infos[0].process_id, 1);
+    }
 }
@@ -6,7 +6,7 @@ pub mod update;
 #[cfg(all(test, unix))]
 mod tests {
     use crate::managers::{
-        resource::{ResourceError, ResourceManagerTrait},
+        resource::{BackupOutcome, ResourceError, ResourceManagerTrait},
         runtime::{
             FeatType, ProcessInfo, RuntimeError, RuntimeInfo, RuntimeManager,
             RuntimeManagerWithoutAsync, State,
@@ -20,6 +20,8 @@ mod tests {
     pub struct MockRuntimeManager {
         pub response_version: Version,
         pub runtime_info: RuntimeInfo,
+        // Pids that `prune_dead_processes` treats as no longer running.
+        pub dead_pids: Vec<u32>,
     }
 
     impl MockRuntimeManager {
@@ -28,6 +30,7 @@ mod tests {
             Self {
                 response_version: current_version,
                 runtime_info,
+                dead_pids: vec![],
             }
         }
     }
@@ -40,6 +43,8 @@ mod tests {
                 feat_type: FeatType::Agent,
                 version: self.response_version.clone(),
                 executed_at: now,
+                exe_path: "".into(),
+                started_cmdline: vec![],
             };
             let _ = self.runtime_info.add_process_info(process_info.clone());
             Ok(process_info)
@@ -81,6 +86,13 @@ mod tests {
             }
             Ok(())
         }
+
+        fn prune_dead_processes(&mut self) -> Result<Vec<ProcessInfo>, RuntimeError> {
+            let dead_pids = &self.dead_pids;
+            Ok(self
+                .runtime_info
+                .prune_dead_processes(|p| !dead_pids.contains(&p.process_id)))
+        }
     }
 
     impl RuntimeManager for MockRuntimeManager {
@@ -91,6 +103,7 @@ mod tests {
 
     pub struct MockResourceManager {
         bundles: Vec<PathBuf>,
+        pub restored_paths: Vec<PathBuf>,
         pub rollback_called: StdMutex<bool>,
         pub remove_called: StdMutex<bool>,
     }
@@ -99,6 +112,7 @@ mod tests {
         pub fn new(bundles: Vec<PathBuf>) -> Self {
             Self {
                 bundles,
+                restored_paths: vec![],
                 remove_called: StdMutex::new(false),
                 rollback_called: StdMutex::new(false),
             }
@@ -106,14 +120,14 @@ mod tests {
     }
 
     impl ResourceManagerTrait for MockResourceManager {
-        fn backup(&self) -> Result<(), ResourceError> {
+        fn backup(&self) -> Result<BackupOutcome, ResourceError> {
             unimplemented!()
         }
 
-        fn rollback(&self, _backup_file: &std::path::Path) -> Result<(), ResourceError> {
+        fn rollback(&self, _backup_file: &std::path::Path) -> Result<Vec<PathBuf>, ResourceError> {
             let mut called = self.rollback_called.lock().unwrap();
             *called = true;
-            Ok(())
+            Ok(self.restored_paths.clone())
         }
 
         fn agent_path(&self) -> &PathBuf {
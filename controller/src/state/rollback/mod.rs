@@ -2,6 +2,8 @@ use crate::managers::{
     resource::{ResourceError, ResourceManagerTrait},
     runtime::{RuntimeError, RuntimeManager},
 };
+use crate::validator::process::is_manage_by_systemd;
+use std::path::PathBuf;
 
 #[cfg(unix)]
 pub use nix::{
@@ -23,10 +25,18 @@ pub enum RollbackError {
     CurrentExecutablePathError(#[source] std::io::Error),
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RollbackSummary {
+    pub backup_file: PathBuf,
+    pub restored_paths: Vec<PathBuf>,
+    // NOTE: false when systemd is responsible for restarting the controller.
+    pub controller_relaunched: bool,
+}
+
 pub async fn execute<'a, R, T>(
     resource_manager: &'a R,
     runtime_manager: &'a mut T,
-) -> Result<(), RollbackError>
+) -> Result<RollbackSummary, RollbackError>
 where
     R: ResourceManagerTrait,
     T: RuntimeManager,
@@ -38,13 +48,18 @@ where
         Some(backup_file) => {
             let agent_path = runtime_manager.get_runtime_info()?.exec_path;
             log::info!("Found backup: {}", backup_file.display());
-            resource_manager.rollback(&backup_file)?;
+            let restored_paths = resource_manager.rollback(&backup_file)?;
             if let Err(err) = resource_manager.remove() {
                 log::error!("Failed to remove files {}", err);
             }
             runtime_manager.update_state_without_send(crate::managers::runtime::State::Idle)?;
             runtime_manager.launch_controller(agent_path)?;
-            log::info!("Rollback completed");
+            let summary = RollbackSummary {
+                backup_file,
+                restored_paths,
+                controller_relaunched: !is_manage_by_systemd(),
+            };
+            log::info!("Rollback completed: {:?}", summary);
 
             #[cfg(not(test))] // failed test by kill own process
             {
@@ -55,7 +70,7 @@ where
                 )?;
                 runtime_manager.kill_process(self_info)?;
             }
-            Ok(())
+            Ok(summary)
         }
         None => Err(RollbackError::BackupNotFound),
     }
@@ -66,6 +81,7 @@ mod tests {
     use super::super::tests::{MockResourceManager, MockRuntimeManager};
     use super::*;
     use crate::managers::runtime::{RuntimeInfo, RuntimeManagerWithoutAsync, State};
+    use serial_test::serial;
     use tempfile::tempdir;
 
     #[tokio::test]
@@ -98,6 +114,40 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn test_execute_returns_summary() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let backup_file = temp_dir.path().join("backup.tar.gz");
+        let mut resource = MockResourceManager::new(vec![backup_file.clone()]);
+        resource.restored_paths = vec![
+            PathBuf::from("/usr/bin/nodex-agent"),
+            PathBuf::from("/home/nodex/.config/nodex"),
+        ];
+        let runtime_info = RuntimeInfo {
+            state: State::Rollback,
+            process_infos: [None, None, None, None],
+            exec_path: "".into(),
+        };
+        let mut runtime = MockRuntimeManager::new(runtime_info);
+
+        std::env::remove_var("INVOCATION_ID");
+        let summary = execute(&resource, &mut runtime).await.unwrap();
+        assert_eq!(
+            summary,
+            RollbackSummary {
+                backup_file: backup_file.clone(),
+                restored_paths: resource.restored_paths.clone(),
+                controller_relaunched: true,
+            }
+        );
+
+        std::env::set_var("INVOCATION_ID", "dummy_id");
+        let summary = execute(&resource, &mut runtime).await.unwrap();
+        std::env::remove_var("INVOCATION_ID");
+        assert!(!summary.controller_relaunched);
+    }
+
     #[tokio::test]
     async fn test_execute_without_backup() {
         let resource = MockResourceManager::new(vec![]);
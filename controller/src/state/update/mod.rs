@@ -148,8 +148,12 @@ where
             &current_version,
             &current_running_agent.version,
         )?;
+        let current_agent_version = current_running_agent.version.to_string();
+        for action in &pending_update_actions {
+            action.validate(&current_agent_version)?;
+        }
         for action in pending_update_actions {
-            action.handle()?;
+            action.handle().await?;
         }
         // launch new version agent
         let latest = runtime_manager.launch_agent(false)?;
@@ -200,6 +204,8 @@ mod tests {
                     version: current_version.clone(),
                     executed_at: Utc::now()
                         .with_timezone(&FixedOffset::east_opt(9 * 3600).unwrap()),
+                    exe_path: "".into(),
+                    started_cmdline: vec![],
                 }),
                 Some(ProcessInfo {
                     process_id: 3,
@@ -207,6 +213,8 @@ mod tests {
                     version: Version::parse("0.0.1").unwrap(),
                     executed_at: Utc::now()
                         .with_timezone(&FixedOffset::east_opt(9 * 3600).unwrap()),
+                    exe_path: "".into(),
+                    started_cmdline: vec![],
                 }),
                 None,
                 None,
@@ -216,6 +224,7 @@ mod tests {
         let mut runtime = MockRuntimeManager {
             response_version: current_version.clone(),
             runtime_info,
+            dead_pids: vec![],
         };
         let resource = MockResourceManager::new(vec![]);
 
@@ -255,13 +264,18 @@ mod tests {
                     version: current_version.clone(),
                     executed_at: Utc::now()
                         .with_timezone(&FixedOffset::east_opt(9 * 3600).unwrap()),
+                    exe_path: "".into(),
+                    started_cmdline: vec![],
                 }),
                 Some(ProcessInfo {
                     process_id: 3,
                     feat_type: FeatType::Agent,
-                    version: Version::parse("0.0.1").unwrap(),
+                    // Same major as the controller; crossing majors requires `force`.
+                    version: Version::new(current_version.major, 0, 0),
                     executed_at: Utc::now()
                         .with_timezone(&FixedOffset::east_opt(9 * 3600).unwrap()),
+                    exe_path: "".into(),
+                    started_cmdline: vec![],
                 }),
                 None,
                 None,
@@ -272,6 +286,7 @@ mod tests {
         let mut runtime = MockRuntimeManager {
             response_version: current_version.clone(),
             runtime_info,
+            dead_pids: vec![],
         };
 
         // setup bundles
@@ -289,6 +304,7 @@ mod tests {
             version: current_version.to_string(),
             description: "Test move tasks".to_string(),
             tasks,
+            force: false,
         };
 
         let _temp_dir = tempdir().expect("Failed to create temporary directory");
@@ -313,6 +329,7 @@ mod tests {
         let mut runtime = MockRuntimeManager {
             response_version: current_version,
             runtime_info,
+            dead_pids: vec![],
         };
         let resource = MockResourceManager::new(vec![]);
 
@@ -343,6 +360,7 @@ mod tests {
                 version,
                 description: "Test move tasks".to_string(),
                 tasks,
+                force: false,
             };
 
             let yaml_str =
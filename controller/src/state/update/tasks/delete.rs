@@ -0,0 +1,128 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DeleteError {
+    #[error("Path '{0}' is outside the directories an update may delete from")]
+    OutsideAllowedRoot(PathBuf),
+    #[error("Path '{0}' is a directory but recursive is not set")]
+    IsDirectory(PathBuf),
+    #[error("Failed to delete '{0}': {1}")]
+    RemoveError(PathBuf, #[source] std::io::Error),
+}
+
+// NOTE: The target is only moved aside here and its new location returned, so the
+// caller can restore it if a later task fails and purge it once the action succeeds.
+pub fn run(
+    path: &String,
+    recursive: bool,
+    allowed_roots: &[PathBuf],
+) -> Result<Option<PathBuf>, DeleteError> {
+    let target = Path::new(path).to_path_buf();
+    if !super::is_allowed(&target, allowed_roots) {
+        return Err(DeleteError::OutsideAllowedRoot(target));
+    }
+
+    let metadata = match fs::symlink_metadata(&target) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            log::info!("Nothing to delete at {}", path);
+            return Ok(None);
+        }
+        Err(e) => return Err(DeleteError::RemoveError(target, e)),
+    };
+
+    if metadata.is_dir() && !recursive {
+        return Err(DeleteError::IsDirectory(target));
+    }
+    log::info!("Deleting {}", path);
+    super::set_aside(&target).map_err(|e| DeleteError::RemoveError(target, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_delete_file() {
+        let root = tempdir().unwrap();
+        let file_path = root.path().join("old-binary");
+        fs::write(&file_path, b"old").unwrap();
+
+        let result = run(
+            &file_path.to_string_lossy().to_string(),
+            false,
+            &[root.path().to_path_buf()],
+        );
+
+        assert!(result.is_ok(), "Expected run to succeed, got {:?}", result);
+        assert!(!file_path.exists());
+        let stash = result.unwrap().expect("Expected the file to be set aside");
+        assert_eq!(fs::read(stash).unwrap(), b"old");
+    }
+
+    #[test]
+    fn test_delete_directory() {
+        let root = tempdir().unwrap();
+        let dir_path = root.path().join("plugins");
+        fs::create_dir_all(dir_path.join("nested")).unwrap();
+        fs::write(dir_path.join("nested").join("file"), b"data").unwrap();
+        let path = dir_path.to_string_lossy().to_string();
+        let roots = [root.path().to_path_buf()];
+
+        let result = run(&path, false, &roots);
+        assert!(
+            matches!(result, Err(DeleteError::IsDirectory(_))),
+            "Expected IsDirectory, got {:?}",
+            result
+        );
+        assert!(dir_path.exists());
+
+        let result = run(&path, true, &roots);
+        assert!(result.is_ok(), "Expected run to succeed, got {:?}", result);
+        assert!(!dir_path.exists());
+
+        let result = run(&path, true, &roots);
+        assert!(
+            matches!(result, Ok(None)),
+            "Expected nothing left to delete, got {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_delete_refuses_traversal() {
+        let root = tempdir().unwrap();
+        let outside = tempdir().unwrap();
+        let victim = outside.path().join("victim");
+        fs::write(&victim, b"keep").unwrap();
+        let roots = [root.path().to_path_buf()];
+
+        let traversal = root
+            .path()
+            .join("..")
+            .join(outside.path().file_name().unwrap())
+            .join("victim");
+        #[allow(unused_mut)]
+        let mut paths = vec![traversal, victim.clone(), root.path().to_path_buf()];
+        #[cfg(unix)]
+        {
+            let symlink = root.path().join("link");
+            std::os::unix::fs::symlink(outside.path(), &symlink).unwrap();
+            paths.push(symlink.join("victim"));
+        }
+
+        for path in paths {
+            let result = run(&path.to_string_lossy().to_string(), true, &roots);
+            assert!(
+                matches!(result, Err(DeleteError::OutsideAllowedRoot(_))),
+                "Expected OutsideAllowedRoot for {:?}, got {:?}",
+                path,
+                result
+            );
+        }
+        assert!(victim.exists());
+        assert!(root.path().exists());
+    }
+}
@@ -1,14 +1,27 @@
+mod delete;
 mod move_resource;
 mod update_json;
 
-use crate::state::update::tasks::{move_resource::MoveResourceError, update_json::UpdateJsonError};
+use crate::config::get_config;
+use crate::managers::resource::{download_file_verified, ResourceError};
+use crate::state::update::tasks::{
+    delete::DeleteError, move_resource::MoveResourceError, update_json::UpdateJsonError,
+};
+use semver::Version;
 use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct UpdateAction {
     pub version: String,
     pub description: String,
     pub tasks: Vec<Task>,
+    // NOTE: Allows applying the action as a downgrade or across major versions.
+    #[serde(default)]
+    pub force: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -23,7 +36,20 @@ pub enum Task {
         description: String,
         file: String,
         field: String,
-        value: String,
+        // NOTE: Keeps the YAML scalar type, so `8080` is written as a number and `"8080"` as a string.
+        value: serde_json::Value,
+    },
+    Download {
+        description: String,
+        url: String,
+        dest: String,
+        sha256: String,
+    },
+    Delete {
+        description: String,
+        path: String,
+        #[serde(default)]
+        recursive: bool,
     },
 }
 
@@ -33,20 +59,243 @@ pub enum UpdateActionError {
     Move(#[from] MoveResourceError),
     #[error("Update JSON operation failed: {0}")]
     UpdateJson(#[from] UpdateJsonError),
+    #[error("Download task failed: {0}")]
+    Download(#[from] ResourceError),
+    #[error("Path '{0}' is outside the directories an update may download to")]
+    DownloadOutsideAllowedRoot(PathBuf),
+    #[error("Delete task failed: {0}")]
+    Delete(#[from] DeleteError),
+    #[error("Failed to set aside '{0}' before replacing it: {1}")]
+    SetAside(PathBuf, #[source] std::io::Error),
+    #[error("Invalid version '{0}': {1}")]
+    InvalidVersion(String, #[source] semver::Error),
+    #[error("Refusing to downgrade from {current} to {target}")]
+    Downgrade { current: Version, target: Version },
+    #[error("Refusing to update across major versions from {current} to {target}")]
+    MajorVersionMismatch { current: Version, target: Version },
+}
+
+// NOTE: How to revert one completed task when a later task in the same action fails.
+#[derive(Debug)]
+enum Undo {
+    // Put a moved file back where it came from.
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    // Restore the bytes a file had before the task rewrote it.
+    WriteBack {
+        path: PathBuf,
+        contents: Vec<u8>,
+    },
+    // Drop whatever the task left at `path` and put the set-aside original back.
+    Unstash {
+        path: PathBuf,
+        stash: Option<PathBuf>,
+    },
+}
+
+impl Undo {
+    fn revert(&self) -> io::Result<()> {
+        match self {
+            Undo::Rename { from, to } => fs::rename(from, to),
+            Undo::WriteBack { path, contents } => fs::write(path, contents),
+            Undo::Unstash { path, stash } => {
+                remove_path(path)?;
+                match stash {
+                    Some(stash) => fs::rename(stash, path),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    fn commit(self) {
+        if let Undo::Unstash {
+            stash: Some(stash), ..
+        } = self
+        {
+            if let Err(e) = remove_path(&stash) {
+                log::warn!("Failed to remove {}: {}", stash.display(), e);
+            }
+        }
+    }
+}
+
+fn remove_path(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+fn is_within(path: &Path, roots: &[PathBuf]) -> bool {
+    roots
+        .iter()
+        .any(|root| path.starts_with(root) && path != root.as_path())
+}
+
+// NOTE: Checked both lexically and after resolving symlinks in the parent, so neither
+// `..` nor a symlinked directory can point a task outside `allowed_roots`.
+fn is_allowed(path: &Path, allowed_roots: &[PathBuf]) -> bool {
+    if !path.is_absolute()
+        || path.components().any(|c| c == Component::ParentDir)
+        || !is_within(path, allowed_roots)
+    {
+        return false;
+    }
+    let (Some(parent), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return false;
+    };
+    if let Ok(parent) = parent.canonicalize() {
+        let roots: Vec<_> = allowed_roots
+            .iter()
+            .filter_map(|root| root.canonicalize().ok())
+            .collect();
+        if !is_within(&parent.join(file_name), &roots) {
+            return false;
+        }
+    }
+    true
+}
+
+// NOTE: Renames an existing `path` to a hidden sibling so it can be restored on failure;
+// the sibling is only removed once the whole action has succeeded.
+fn set_aside(path: &Path) -> io::Result<Option<PathBuf>> {
+    if fs::symlink_metadata(path).is_err() {
+        return Ok(None);
+    }
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let stash = path.with_file_name(format!(
+        ".{}.nodex-undo-{}-{}",
+        file_name.to_string_lossy(),
+        std::process::id(),
+        nanos
+    ));
+    fs::rename(path, &stash)?;
+    Ok(Some(stash))
 }
 
 impl UpdateAction {
-    pub fn handle(&self) -> Result<(), UpdateActionError> {
+    pub fn validate(&self, current_version: &str) -> Result<(), UpdateActionError> {
+        let parse = |version: &str| {
+            Version::parse(version)
+                .map_err(|e| UpdateActionError::InvalidVersion(version.to_string(), e))
+        };
+        let target = parse(&self.version)?;
+        let current = parse(current_version)?;
+        if self.force {
+            return Ok(());
+        }
+        if target < current {
+            return Err(UpdateActionError::Downgrade { current, target });
+        }
+        if target.major != current.major {
+            return Err(UpdateActionError::MajorVersionMismatch { current, target });
+        }
+        Ok(())
+    }
+
+    pub async fn handle(&self) -> Result<(), UpdateActionError> {
+        // NOTE: Updates may only write to and clean up NodeX's own directories.
+        let allowed_roots = {
+            let config = get_config().lock().unwrap();
+            vec![config.config_dir.clone(), config.nodex_dir.clone()]
+        };
+        self.handle_within(&allowed_roots).await
+    }
+
+    async fn handle_within(&self, allowed_roots: &[PathBuf]) -> Result<(), UpdateActionError> {
+        let mut undo_log = Vec::new();
+        match self.run_tasks(&mut undo_log, allowed_roots).await {
+            Ok(()) => {
+                undo_log.into_iter().for_each(Undo::commit);
+                Ok(())
+            }
+            Err(e) => {
+                log::error!(
+                    "Update action {} failed, reverting {} step(s): {}",
+                    self.version,
+                    undo_log.len(),
+                    e
+                );
+                for undo in undo_log.iter().rev() {
+                    if let Err(err) = undo.revert() {
+                        log::error!("Failed to revert {:?}: {}", undo, err);
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn run_tasks(
+        &self,
+        undo_log: &mut Vec<Undo>,
+        allowed_roots: &[PathBuf],
+    ) -> Result<(), UpdateActionError> {
         for task in &self.tasks {
             match task {
                 Task::Move { src, dest, .. } => {
-                    move_resource::run(src, dest)?;
+                    for moved in move_resource::run(src, dest)? {
+                        // Reverted in reverse, so the file is moved back before the
+                        // original destination is restored.
+                        undo_log.push(Undo::Unstash {
+                            path: moved.dest.clone(),
+                            stash: moved.stash,
+                        });
+                        undo_log.push(Undo::Rename {
+                            from: moved.dest,
+                            to: moved.src,
+                        });
+                    }
                 }
                 Task::UpdateJson {
                     file, field, value, ..
                 } => {
+                    if let Ok(contents) = fs::read(file) {
+                        undo_log.push(Undo::WriteBack {
+                            path: PathBuf::from(file),
+                            contents,
+                        });
+                    }
                     update_json::run(file, field, value)?;
                 }
+                Task::Download {
+                    url, dest, sha256, ..
+                } => {
+                    let dest = Path::new(dest);
+                    if !is_allowed(dest, allowed_roots) {
+                        return Err(UpdateActionError::DownloadOutsideAllowedRoot(
+                            dest.to_path_buf(),
+                        ));
+                    }
+                    let stash = set_aside(dest)
+                        .map_err(|e| UpdateActionError::SetAside(dest.to_path_buf(), e))?;
+                    undo_log.push(Undo::Unstash {
+                        path: dest.to_path_buf(),
+                        stash,
+                    });
+                    download_file_verified(url, dest, sha256).await?;
+                }
+                Task::Delete {
+                    path, recursive, ..
+                } => {
+                    if let Some(stash) = delete::run(path, *recursive, allowed_roots)? {
+                        undo_log.push(Undo::Unstash {
+                            path: PathBuf::from(path),
+                            stash: Some(stash),
+                        });
+                    }
+                }
             };
         }
         Ok(())
@@ -75,7 +324,11 @@ mod tests {
     mod mock_update_json {
         use super::*;
         #[allow(dead_code)]
-        pub fn run(file: &str, _field: &str, _value: &str) -> Result<(), UpdateJsonError> {
+        pub fn run(
+            file: &str,
+            _field: &str,
+            _value: &serde_json::Value,
+        ) -> Result<(), UpdateJsonError> {
             if file == "error.json" {
                 Err(UpdateJsonError::InvalidFieldPath(
                     "invalid_field".to_string(),
@@ -96,8 +349,8 @@ mod tests {
         let _ = fs::remove_file(path);
     }
 
-    #[test]
-    fn test_handle_successful_move_tasks() {
+    #[tokio::test]
+    async fn test_handle_successful_move_tasks() {
         let source1_path = "/tmp/source1.txt";
         let source2_path = "/tmp/source2.txt";
 
@@ -124,9 +377,10 @@ mod tests {
             version: "1.0.0".to_string(),
             description: "Test move tasks".to_string(),
             tasks,
+            force: false,
         };
 
-        let result = action.handle();
+        let result = action.handle().await;
 
         assert!(
             result.is_ok(),
@@ -155,8 +409,8 @@ mod tests {
         cleanup_test_file(dest2_path);
     }
 
-    #[test]
-    fn test_handle_successful_update_json_tasks() {
+    #[tokio::test]
+    async fn test_handle_successful_update_json_tasks() {
         let source1_path = "/tmp/test1.json";
         let source2_path = "/tmp/test2.json";
 
@@ -176,13 +430,13 @@ mod tests {
                 description: "Update field 1".to_string(),
                 file: source1_path.to_string(),
                 field: "key1".to_string(),
-                value: "value1".to_string(),
+                value: "value1".into(),
             },
             Task::UpdateJson {
                 description: "Update field 2".to_string(),
                 file: source2_path.to_string(),
                 field: "key2".to_string(),
-                value: "value2".to_string(),
+                value: "value2".into(),
             },
         ];
 
@@ -190,9 +444,10 @@ mod tests {
             version: "1.0.0".to_string(),
             description: "Test update JSON tasks".to_string(),
             tasks,
+            force: false,
         };
 
-        let result = action.handle();
+        let result = action.handle().await;
         assert!(
             result.is_ok(),
             "Expected successful execution, but got: {:?}",
@@ -219,8 +474,8 @@ mod tests {
         cleanup_test_file(source2_path);
     }
 
-    #[test]
-    fn test_handle_move_task_error() {
+    #[tokio::test]
+    async fn test_handle_move_task_error() {
         let tasks = vec![
             Task::Move {
                 description: "Move valid file".to_string(),
@@ -238,9 +493,10 @@ mod tests {
             version: "1.0.0".to_string(),
             description: "Test move task error".to_string(),
             tasks,
+            force: false,
         };
 
-        let result = action.handle();
+        let result = action.handle().await;
         assert!(
             matches!(result, Err(UpdateActionError::Move(_))),
             "Expected Move error, but got: {:?}",
@@ -248,20 +504,20 @@ mod tests {
         );
     }
 
-    #[test]
-    fn test_handle_update_json_task_error() {
+    #[tokio::test]
+    async fn test_handle_update_json_task_error() {
         let tasks = vec![
             Task::UpdateJson {
                 description: "Update valid JSON".to_string(),
                 file: "/tmp/test1.json".to_string(),
                 field: "key1".to_string(),
-                value: "value1".to_string(),
+                value: "value1".into(),
             },
             Task::UpdateJson {
                 description: "Update invalid JSON".to_string(),
                 file: "error.json".to_string(),
                 field: "key2".to_string(),
-                value: "value2".to_string(),
+                value: "value2".into(),
             },
         ];
 
@@ -269,13 +525,289 @@ mod tests {
             version: "1.0.0".to_string(),
             description: "Test update JSON task error".to_string(),
             tasks,
+            force: false,
+        };
+
+        let result = action.handle().await;
+        assert!(
+            matches!(result, Err(UpdateActionError::UpdateJson(_))),
+            "Expected UpdateJson error, but got: {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_parse_download_task() {
+        let yaml = r#"
+version: "1.0.0"
+description: "Fetch helper"
+tasks:
+  - action: Download
+    description: "Download helper binary"
+    url: "https://example.com/helper"
+    dest: "/tmp/helper"
+    sha256: "abcdef"
+"#;
+        let action: UpdateAction = serde_yaml::from_str(yaml).unwrap();
+        match &action.tasks[..] {
+            [Task::Download {
+                url, dest, sha256, ..
+            }] => {
+                assert_eq!(url, "https://example.com/helper");
+                assert_eq!(dest, "/tmp/helper");
+                assert_eq!(sha256, "abcdef");
+            }
+            tasks => panic!("Expected a single Download task, got {:?}", tasks),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_download_task() {
+        use sha2::{Digest, Sha256};
+
+        let body = b"helper contents";
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/helper")
+            .with_status(200)
+            .with_body(body)
+            .create_async()
+            .await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dest = temp_dir.path().join("bin").join("helper");
+        let download = |sha256: String| UpdateAction {
+            version: "1.0.0".to_string(),
+            description: "Test download task".to_string(),
+            tasks: vec![Task::Download {
+                description: "Download helper".to_string(),
+                url: format!("{}/helper", server.url()),
+                dest: dest.to_string_lossy().to_string(),
+                sha256,
+            }],
+            force: false,
+        };
+
+        let allowed_roots = [temp_dir.path().to_path_buf()];
+        let result = download("00".repeat(32))
+            .handle_within(&allowed_roots)
+            .await;
+        assert!(
+            matches!(
+                result,
+                Err(UpdateActionError::Download(
+                    ResourceError::ChecksumMismatch { .. }
+                ))
+            ),
+            "Expected ChecksumMismatch, but got: {:?}",
+            result
+        );
+        assert!(!dest.exists(), "Mismatched download must not be kept");
+
+        let result = download(hex::encode(Sha256::digest(body)))
+            .handle_within(&allowed_roots)
+            .await;
+        assert!(result.is_ok(), "Expected success, but got: {:?}", result);
+        assert_eq!(fs::read(&dest).unwrap(), body);
+
+        let outside = tempfile::tempdir().unwrap();
+        let result = download(hex::encode(Sha256::digest(body)))
+            .handle_within(&[outside.path().to_path_buf()])
+            .await;
+        assert!(
+            matches!(
+                result,
+                Err(UpdateActionError::DownloadOutsideAllowedRoot(_))
+            ),
+            "Expected DownloadOutsideAllowedRoot, but got: {:?}",
+            result
+        );
+        assert_eq!(fs::read(&dest).unwrap(), body);
+    }
+
+    #[tokio::test]
+    async fn test_handle_reverts_completed_tasks_on_failure() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("first.txt");
+        let dest = temp_dir.path().join("dest");
+        let untouched = temp_dir.path().join("third.txt");
+        create_test_file(src.to_str().unwrap(), "first").unwrap();
+        create_test_file(untouched.to_str().unwrap(), "third").unwrap();
+
+        let action = UpdateAction {
+            version: "1.0.0".to_string(),
+            description: "Second task fails".to_string(),
+            tasks: vec![
+                Task::Move {
+                    description: "Move first file".to_string(),
+                    src: src.to_string_lossy().to_string(),
+                    dest: dest.to_string_lossy().to_string(),
+                },
+                Task::UpdateJson {
+                    description: "Update missing JSON".to_string(),
+                    file: temp_dir
+                        .path()
+                        .join("missing.json")
+                        .to_string_lossy()
+                        .to_string(),
+                    field: "key".to_string(),
+                    value: "value".into(),
+                },
+                Task::Move {
+                    description: "Move third file".to_string(),
+                    src: untouched.to_string_lossy().to_string(),
+                    dest: dest.to_string_lossy().to_string(),
+                },
+            ],
+            force: false,
+        };
+
+        let result = action.handle().await;
+        assert!(
+            matches!(result, Err(UpdateActionError::UpdateJson(_))),
+            "Expected UpdateJson error, but got: {:?}",
+            result
+        );
+        assert_eq!(fs::read_to_string(&src).unwrap(), "first");
+        assert!(!dest.join("first.txt").exists());
+        assert_eq!(fs::read_to_string(&untouched).unwrap(), "third");
+    }
+
+    #[tokio::test]
+    async fn test_handle_restores_move_destination_on_failure() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src = temp_dir.path().join("config.txt");
+        let dest = temp_dir.path().join("dest");
+        fs::create_dir_all(&dest).unwrap();
+        create_test_file(src.to_str().unwrap(), "new").unwrap();
+        create_test_file(dest.join("config.txt").to_str().unwrap(), "old").unwrap();
+
+        let action = UpdateAction {
+            version: "1.0.0".to_string(),
+            description: "Move over an existing file, then fail".to_string(),
+            tasks: vec![
+                Task::Move {
+                    description: "Replace config".to_string(),
+                    src: src.to_string_lossy().to_string(),
+                    dest: dest.to_string_lossy().to_string(),
+                },
+                Task::UpdateJson {
+                    description: "Update missing JSON".to_string(),
+                    file: temp_dir
+                        .path()
+                        .join("missing.json")
+                        .to_string_lossy()
+                        .to_string(),
+                    field: "key".to_string(),
+                    value: "value".into(),
+                },
+            ],
+            force: false,
         };
 
-        let result = action.handle();
+        let result = action.handle().await;
         assert!(
             matches!(result, Err(UpdateActionError::UpdateJson(_))),
             "Expected UpdateJson error, but got: {:?}",
             result
         );
+        assert_eq!(fs::read_to_string(&src).unwrap(), "new");
+        assert_eq!(fs::read_to_string(dest.join("config.txt")).unwrap(), "old");
+        assert_eq!(fs::read_dir(&dest).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_handle_restores_overwritten_files_on_failure() {
+        use sha2::{Digest, Sha256};
+
+        let body = b"new helper";
+        let mut server = mockito::Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/helper")
+            .with_status(200)
+            .with_body(body)
+            .create_async()
+            .await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        let helper = temp_dir.path().join("helper");
+        let config = temp_dir.path().join("config.json");
+        fs::write(&helper, b"old helper").unwrap();
+        fs::write(&config, r#"{"key": "old"}"#).unwrap();
+
+        let tasks = |fail: bool| {
+            let mut tasks = vec![
+                Task::Download {
+                    description: "Replace helper".to_string(),
+                    url: format!("{}/helper", server.url()),
+                    dest: helper.to_string_lossy().to_string(),
+                    sha256: hex::encode(Sha256::digest(body)),
+                },
+                Task::UpdateJson {
+                    description: "Update config".to_string(),
+                    file: config.to_string_lossy().to_string(),
+                    field: "key".to_string(),
+                    value: "new".into(),
+                },
+            ];
+            if fail {
+                tasks.push(Task::Move {
+                    description: "Move missing file".to_string(),
+                    src: temp_dir
+                        .path()
+                        .join("missing")
+                        .to_string_lossy()
+                        .to_string(),
+                    dest: temp_dir.path().join("dest").to_string_lossy().to_string(),
+                });
+            }
+            UpdateAction {
+                version: "1.0.0".to_string(),
+                description: "Replace helper".to_string(),
+                tasks,
+                force: false,
+            }
+        };
+
+        let allowed_roots = [temp_dir.path().to_path_buf()];
+        let result = tasks(true).handle_within(&allowed_roots).await;
+        assert!(matches!(result, Err(UpdateActionError::Move(_))));
+        assert_eq!(fs::read(&helper).unwrap(), b"old helper");
+        assert_eq!(fs::read_to_string(&config).unwrap(), r#"{"key": "old"}"#);
+
+        tasks(false).handle_within(&allowed_roots).await.unwrap();
+        assert_eq!(fs::read(&helper).unwrap(), body);
+        let leftovers: Vec<_> = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_string_lossy().contains("nodex-undo"))
+            .collect();
+        assert!(leftovers.is_empty(), "Stashed files left: {:?}", leftovers);
+    }
+
+    #[test]
+    fn test_validate_version() {
+        let action = |version: &str, force: bool| UpdateAction {
+            version: version.to_string(),
+            description: "Test validate".to_string(),
+            tasks: vec![],
+            force,
+        };
+
+        assert!(action("1.2.0", false).validate("1.1.0").is_ok());
+        assert!(action("1.1.0", false).validate("1.1.0").is_ok());
+        assert!(matches!(
+            action("1.0.0", false).validate("1.1.0"),
+            Err(UpdateActionError::Downgrade { .. })
+        ));
+        assert!(matches!(
+            action("2.0.0", false).validate("1.1.0"),
+            Err(UpdateActionError::MajorVersionMismatch { .. })
+        ));
+        assert!(matches!(
+            action("latest", false).validate("1.1.0"),
+            Err(UpdateActionError::InvalidVersion(_, _))
+        ));
+
+        assert!(action("1.0.0", true).validate("1.1.0").is_ok());
+        assert!(action("2.0.0", true).validate("1.1.0").is_ok());
     }
 }
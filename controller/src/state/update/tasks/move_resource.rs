@@ -3,8 +3,12 @@ use std::path::{Path, PathBuf};
 
 #[derive(Debug, thiserror::Error)]
 pub enum MoveResourceError {
-    #[error("Source file '{0}' not found or is not a file")]
+    #[error("Source '{0}' not found")]
     SourceNotFoundError(PathBuf),
+    #[error("Invalid source pattern '{0}': {1}")]
+    InvalidSourcePattern(String, #[source] glob::PatternError),
+    #[error("Failed to read source '{0}': {1}")]
+    SourceReadError(PathBuf, #[source] std::io::Error),
     #[error("Destination directory '{0}' does not exist and could not be created: {1}")]
     DestinationCreationError(PathBuf, #[source] std::io::Error),
     #[error("Destination path '{0}' is not a directory")]
@@ -13,15 +17,62 @@ pub enum MoveResourceError {
     InvalidSourceFileName(PathBuf),
     #[error("Failed to move file from '{0}' to '{1}': {2}")]
     FileMoveError(PathBuf, PathBuf, #[source] std::io::Error),
+    #[error("Failed to set aside '{0}' before replacing it: {1}")]
+    SetAside(PathBuf, #[source] std::io::Error),
 }
 
-pub fn run(src: &String, dest: &String) -> Result<(), MoveResourceError> {
-    let src_path = Path::new(src).to_path_buf();
-    if !src_path.exists() {
-        return Err(MoveResourceError::SourceNotFoundError(src_path));
-    } else if src_path.is_dir() {
-        return Err(MoveResourceError::InvalidSourceFileName(src_path));
+#[derive(Debug, PartialEq)]
+pub struct MovedResource {
+    pub src: PathBuf,
+    pub dest: PathBuf,
+    // Where an entry that already existed at `dest` was set aside.
+    pub stash: Option<PathBuf>,
+}
+
+impl MovedResource {
+    fn move_back(&self) -> std::io::Result<()> {
+        fs::rename(&self.dest, &self.src)?;
+        match &self.stash {
+            Some(stash) => fs::rename(stash, &self.dest),
+            None => Ok(()),
+        }
     }
+}
+
+fn is_glob(src: &str) -> bool {
+    src.contains(['*', '?', '['])
+}
+
+fn resolve_sources(src: &str) -> Result<Vec<PathBuf>, MoveResourceError> {
+    if !is_glob(src) {
+        let src_path = PathBuf::from(src);
+        if !src_path.exists() {
+            return Err(MoveResourceError::SourceNotFoundError(src_path));
+        }
+        return Ok(vec![src_path]);
+    }
+    let sources = glob::glob(src)
+        .map_err(|e| MoveResourceError::InvalidSourcePattern(src.to_string(), e))?
+        .map(|entry| {
+            entry.map_err(|e| {
+                let path = e.path().to_path_buf();
+                MoveResourceError::SourceReadError(path, e.into_error())
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if sources.is_empty() {
+        return Err(MoveResourceError::SourceNotFoundError(PathBuf::from(src)));
+    }
+    Ok(sources)
+}
+
+// NOTE: `src` is either a single file or directory, or a glob pattern; every match is moved
+// (directories with their whole tree) into the `dest` directory. Returns the
+// source and destination of each moved entry. An entry already at the destination is set
+// aside rather than replaced, so it can be restored. If one of several matches fails to
+// move, the ones already moved are put back before the error is returned.
+pub fn run(src: &String, dest: &String) -> Result<Vec<MovedResource>, MoveResourceError> {
+    let sources = resolve_sources(src)?;
 
     let dest_path = Path::new(dest).to_path_buf();
     if !dest_path.exists() {
@@ -35,17 +86,71 @@ pub fn run(src: &String, dest: &String) -> Result<(), MoveResourceError> {
         return Err(MoveResourceError::DestinationNotDirectoryError(dest_path));
     }
 
+    let mut moved = Vec::with_capacity(sources.len());
+    for src_path in sources {
+        match move_into(&src_path, &dest_path) {
+            Ok(resource) => moved.push(resource),
+            Err(e) => {
+                for resource in moved.iter().rev() {
+                    if let Err(err) = resource.move_back() {
+                        log::error!(
+                            "Failed to move {} back to {}: {}",
+                            resource.dest.display(),
+                            resource.src.display(),
+                            err
+                        );
+                    }
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(moved)
+}
+
+fn move_into(src_path: &Path, dest_path: &Path) -> Result<MovedResource, MoveResourceError> {
     let file_name = src_path
         .file_name()
-        .ok_or_else(|| MoveResourceError::InvalidSourceFileName(src_path.clone()))?;
+        .ok_or_else(|| MoveResourceError::InvalidSourceFileName(src_path.to_path_buf()))?;
     let dest_file_path = dest_path.join(file_name);
 
-    log::info!("Moving file from {} to {}", src, dest_file_path.display());
+    log::info!(
+        "Moving {} from {} to {}",
+        if src_path.is_dir() {
+            "directory"
+        } else {
+            "file"
+        },
+        src_path.display(),
+        dest_file_path.display()
+    );
 
-    fs::rename(&src_path, &dest_file_path)
-        .map_err(|e| MoveResourceError::FileMoveError(src_path, dest_file_path, e))?;
+    let stash = super::set_aside(&dest_file_path)
+        .map_err(|e| MoveResourceError::SetAside(dest_file_path.clone(), e))?;
+    if let Err(e) = fs::rename(src_path, &dest_file_path) {
+        if let Some(stash) = &stash {
+            if let Err(err) = fs::rename(stash, &dest_file_path) {
+                log::error!(
+                    "Failed to restore {} from {}: {}",
+                    dest_file_path.display(),
+                    stash.display(),
+                    err
+                );
+            }
+        }
+        return Err(MoveResourceError::FileMoveError(
+            src_path.to_path_buf(),
+            dest_file_path,
+            e,
+        ));
+    }
 
-    Ok(())
+    Ok(MovedResource {
+        src: src_path.to_path_buf(),
+        dest: dest_file_path,
+        stash,
+    })
 }
 
 #[cfg(test)]
@@ -158,7 +263,7 @@ mod tests {
         let dest_dir_path = temp_dir.path().join("destination");
 
         let result = run(
-            &temp_dir.path().to_string_lossy().to_string(),
+            &temp_dir.path().join("..").to_string_lossy().to_string(),
             &dest_dir_path.to_string_lossy().to_string(),
         );
 
@@ -168,4 +273,115 @@ mod tests {
             result
         );
     }
+
+    #[test]
+    fn test_moves_directory_tree() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let src_dir_path = temp_dir.path().join("bundle");
+        let dest_dir_path = temp_dir.path().join("destination");
+        fs::create_dir_all(src_dir_path.join("nested")).unwrap();
+        fs::write(src_dir_path.join("top.txt"), "top").unwrap();
+        fs::write(src_dir_path.join("nested").join("inner.txt"), "inner").unwrap();
+
+        let moved = run(
+            &src_dir_path.to_string_lossy().to_string(),
+            &dest_dir_path.to_string_lossy().to_string(),
+        )
+        .expect("Failed to move directory");
+
+        let moved_dir_path = dest_dir_path.join("bundle");
+        assert_eq!(
+            moved,
+            vec![MovedResource {
+                src: src_dir_path.clone(),
+                dest: moved_dir_path.clone(),
+                stash: None,
+            }]
+        );
+        assert!(!src_dir_path.exists());
+        assert_eq!(
+            fs::read_to_string(moved_dir_path.join("top.txt")).unwrap(),
+            "top"
+        );
+        assert_eq!(
+            fs::read_to_string(moved_dir_path.join("nested").join("inner.txt")).unwrap(),
+            "inner"
+        );
+    }
+
+    #[test]
+    fn test_moves_glob_matches() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let src_dir_path = temp_dir.path().join("configs");
+        let dest_dir_path = temp_dir.path().join("destination");
+        fs::create_dir_all(&src_dir_path).unwrap();
+        for name in ["a.yml", "b.yml", "keep.json"] {
+            File::create(src_dir_path.join(name)).unwrap();
+        }
+
+        let moved = run(
+            &src_dir_path.join("*.yml").to_string_lossy().to_string(),
+            &dest_dir_path.to_string_lossy().to_string(),
+        )
+        .expect("Failed to move glob matches");
+
+        assert_eq!(
+            moved,
+            ["a.yml", "b.yml"]
+                .into_iter()
+                .map(|name| MovedResource {
+                    src: src_dir_path.join(name),
+                    dest: dest_dir_path.join(name),
+                    stash: None,
+                })
+                .collect::<Vec<_>>()
+        );
+        assert!(!src_dir_path.join("a.yml").exists());
+        assert!(!src_dir_path.join("b.yml").exists());
+        assert!(src_dir_path.join("keep.json").exists());
+        assert!(!dest_dir_path.join("keep.json").exists());
+    }
+
+    #[test]
+    fn test_glob_without_matches() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let dest_dir_path = temp_dir.path().join("destination");
+
+        let result = run(
+            &temp_dir.path().join("*.yml").to_string_lossy().to_string(),
+            &dest_dir_path.to_string_lossy().to_string(),
+        );
+
+        assert!(
+            matches!(result, Err(MoveResourceError::SourceNotFoundError(_))),
+            "Expected SourceNotFoundError, but got: {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_glob_requires_directory_destination() {
+        let temp_dir = tempdir().expect("Failed to create temporary directory");
+        let dest_file_path = temp_dir.path().join("not_a_directory.txt");
+        File::create(&dest_file_path).unwrap();
+        for name in ["a.yml", "b.yml"] {
+            File::create(temp_dir.path().join(name)).unwrap();
+        }
+
+        let result = run(
+            &temp_dir.path().join("*.yml").to_string_lossy().to_string(),
+            &dest_file_path.to_string_lossy().to_string(),
+        );
+
+        assert!(
+            matches!(
+                result,
+                Err(MoveResourceError::DestinationNotDirectoryError(_))
+            ),
+            "Expected DestinationNotDirectoryError, but got: {:?}",
+            result
+        );
+        assert!(temp_dir.path().join("a.yml").exists());
+        assert!(temp_dir.path().join("b.yml").exists());
+    }
 }
@@ -1,4 +1,4 @@
-use serde_json::{error::Error as SerdeError, Value};
+use serde_json::{error::Error as SerdeError, Map, Value};
 use std::fs;
 
 #[derive(Debug, thiserror::Error)]
@@ -13,9 +13,25 @@ pub enum UpdateJsonError {
     FileWriteError(String, #[source] std::io::Error),
 }
 
-pub fn run(file: &String, field: &String, value: &String) -> Result<(), UpdateJsonError> {
-    // Array updates are not supported.
-    // It's unclear whether the operation is an addition or a completely new write.
+// NOTE: Objects along the path are created when missing; numeric segments index into
+// existing arrays. Anything else on the way (e.g. a string) cannot be traversed.
+fn child_mut<'a>(
+    node: &'a mut Value,
+    key: &str,
+    field: &str,
+) -> Result<&'a mut Value, UpdateJsonError> {
+    match node {
+        Value::Object(map) => Ok(map.entry(key).or_insert_with(|| Value::Object(Map::new()))),
+        Value::Array(items) => key
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| items.get_mut(i))
+            .ok_or_else(|| UpdateJsonError::InvalidFieldPath(field.to_string())),
+        _ => Err(UpdateJsonError::InvalidFieldPath(field.to_string())),
+    }
+}
+
+pub fn run(file: &String, field: &String, value: &Value) -> Result<(), UpdateJsonError> {
     log::info!(
         "Updating JSON file '{}' field '{}' with value '{}'",
         file,
@@ -30,14 +46,21 @@ pub fn run(file: &String, field: &String, value: &String) -> Result<(), UpdateJs
         .map_err(|e| UpdateJsonError::JsonParseError(file.to_string(), e))?;
 
     let parts: Vec<&str> = field.split('.').collect();
+    if parts.iter().any(|part| part.is_empty()) {
+        return Err(UpdateJsonError::InvalidFieldPath(field.to_string()));
+    }
+    let (last, parents) = parts.split_last().unwrap();
     let mut current = &mut json_data;
-    for part in &parts[..parts.len() - 1] {
-        current = current
-            .get_mut(part)
-            .ok_or_else(|| UpdateJsonError::InvalidFieldPath(field.to_string()))?;
+    for part in parents {
+        current = child_mut(current, part, field)?;
+    }
+    match current {
+        Value::Object(map) => {
+            map.insert(last.to_string(), value.clone());
+        }
+        Value::Array(_) => *child_mut(current, last, field)? = value.clone(),
+        _ => return Err(UpdateJsonError::InvalidFieldPath(field.to_string())),
     }
-
-    current[parts.last().unwrap()] = Value::String(value.to_string());
 
     fs::write(
         file,
@@ -63,7 +86,7 @@ mod tests {
         fs::write(&file_path, r#"{"key1": {"key2": "value"}}"#).unwrap();
 
         let field = "key1.key2".to_string();
-        let value = "new_value".to_string();
+        let value = Value::from("new_value");
         let file_path_str = file_path.to_str().unwrap().to_string();
 
         let result = run(&file_path_str, &field, &value);
@@ -93,7 +116,7 @@ mod tests {
         fs::write(&file_path, r#"{"key1": {"key2": ["item1", "item2"]}}"#).unwrap();
 
         let field = "key1.key2".to_string();
-        let value = "new_item".to_string();
+        let value = Value::from("new_item");
         let file_path_str = file_path.to_str().unwrap().to_string();
 
         let result = run(&file_path_str, &field, &value);
@@ -123,7 +146,7 @@ mod tests {
         fs::write(&file_path, r#"{"key1": {"other_key1": "value1"}}"#).unwrap();
 
         let field = "key1.other_key2".to_string();
-        let value = "new_value".to_string();
+        let value = Value::from("new_value");
         let file_path_str = file_path.to_str().unwrap().to_string();
 
         let result = run(&file_path_str, &field, &value);
@@ -157,7 +180,7 @@ mod tests {
         fs::set_permissions(&file_path, permissions).unwrap();
 
         let field = "key1".to_string();
-        let value = "new_value".to_string();
+        let value = Value::from("new_value");
         let file_path_str = file_path.to_str().unwrap().to_string();
 
         let result = run(&file_path_str, &field, &value);
@@ -177,7 +200,7 @@ mod tests {
         fs::write(&file_path, "not a json").unwrap();
 
         let field = "key1".to_string();
-        let value = "new_value".to_string();
+        let value = Value::from("new_value");
         let file_path_str = file_path.to_str().unwrap().to_string();
 
         let result = run(&file_path_str, &field, &value);
@@ -195,7 +218,7 @@ mod tests {
         fs::write(&file_path, "").unwrap();
 
         let field = "key1".to_string();
-        let value = "new_value".to_string();
+        let value = Value::from("new_value");
         let file_path_str = file_path.to_str().unwrap().to_string();
 
         let result = run(&file_path_str, &field, &value);
@@ -213,7 +236,7 @@ mod tests {
         fs::write(&file_path, r#"{"key1": {"key2": "old_value"}}"#).unwrap();
 
         let field = "key1.key2".to_string();
-        let value = "new_value".to_string();
+        let value = Value::from("new_value");
         let file_path_str = file_path.to_str().unwrap().to_string();
 
         let result = run(&file_path_str, &field, &value);
@@ -235,4 +258,99 @@ mod tests {
             "File content mismatch"
         );
     }
+
+    #[test]
+    fn test_creates_missing_intermediate_objects() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test.json");
+        fs::write(&file_path, r#"{"other": true}"#).unwrap();
+        let file_path_str = file_path.to_str().unwrap().to_string();
+
+        run(
+            &file_path_str,
+            &"server.http.port".to_string(),
+            &Value::from(8080),
+        )
+        .unwrap();
+
+        let updated: Value =
+            serde_json::from_str(&fs::read_to_string(&file_path).unwrap()).unwrap();
+        assert_eq!(
+            updated,
+            serde_json::json!({"other": true, "server": {"http": {"port": 8080}}})
+        );
+    }
+
+    #[test]
+    fn test_updates_array_element() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test.json");
+        fs::write(&file_path, r#"{"servers": [{"port": 80}, {"port": 81}]}"#).unwrap();
+        let file_path_str = file_path.to_str().unwrap().to_string();
+
+        run(
+            &file_path_str,
+            &"servers.1.port".to_string(),
+            &Value::from(443),
+        )
+        .unwrap();
+        run(&file_path_str, &"servers.0".to_string(), &Value::Null).unwrap();
+
+        let updated: Value =
+            serde_json::from_str(&fs::read_to_string(&file_path).unwrap()).unwrap();
+        assert_eq!(
+            updated,
+            serde_json::json!({"servers": [null, {"port": 443}]})
+        );
+
+        for field in [
+            "servers.2.port",
+            "servers.x",
+            "servers..port",
+            "servers.1.port.x",
+        ] {
+            let result = run(&file_path_str, &field.to_string(), &Value::Bool(true));
+            assert!(
+                matches!(result, Err(UpdateJsonError::InvalidFieldPath(_))),
+                "Expected InvalidFieldPath for {}, but got: {:?}",
+                field,
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn test_typed_values_from_yaml() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test.json");
+        fs::write(&file_path, "{}").unwrap();
+        let file_path_str = file_path.to_str().unwrap().to_string();
+
+        let values: Vec<(String, Value)> = serde_yaml::from_str(
+            r#"
+- [port, 8080]
+- [ratio, 0.5]
+- [enabled, true]
+- [proxy, null]
+- [version, "8080"]
+"#,
+        )
+        .unwrap();
+        for (field, value) in &values {
+            run(&file_path_str, field, value).unwrap();
+        }
+
+        let updated: Value =
+            serde_json::from_str(&fs::read_to_string(&file_path).unwrap()).unwrap();
+        assert_eq!(
+            updated,
+            serde_json::json!({
+                "port": 8080,
+                "ratio": 0.5,
+                "enabled": true,
+                "proxy": null,
+                "version": "8080"
+            })
+        );
+    }
 }
@@ -1,18 +1,21 @@
 use bytes::Bytes;
 use http_body_util::{BodyExt, Full};
-use hyper::{body::Incoming, Response};
+use hyper::{body::Incoming, header::CONTENT_TYPE, Method, Request, Response};
 use hyper_util::client::legacy::{Client, Error as LegacyClientError};
 use hyperlocal::{UnixClientExt, UnixConnector, Uri};
 use nix::sys::socket::{recvmsg, sendmsg, ControlMessage, ControlMessageOwned, MsgFlags};
 use notify::event::{AccessKind, AccessMode, CreateKind, MetadataKind, ModifyKind};
 use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Serialize};
 use std::env;
 use std::fs::set_permissions;
 use std::io::{IoSlice, IoSliceMut};
 use std::os::unix::fs::PermissionsExt;
 use std::os::unix::io::RawFd;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub fn convention_of_meta_uds_path(uds: impl AsRef<Path>) -> std::io::Result<PathBuf> {
     let parent = uds.as_ref().parent().ok_or(std::io::Error::new(
@@ -122,6 +125,10 @@ pub enum GetRequestError {
     Json(#[from] serde_json::Error),
     #[error("Request failed: {0}")]
     RequestFailed(#[from] LegacyClientError),
+    #[error("Failed to build request: {0}")]
+    Build(#[from] hyper::http::Error),
+    #[error("Request timed out after {0:?}")]
+    RequestTimeout(Duration),
 }
 
 async fn parse_response_body<T>(response: Response<Incoming>) -> Result<T, GetRequestError>
@@ -138,13 +145,97 @@ pub async fn get_request<T>(
     uds_path: impl AsRef<Path>,
     endpoint: &str,
 ) -> Result<T, GetRequestError>
+where
+    T: serde::de::DeserializeOwned + Send,
+{
+    get_request_with_timeout(uds_path, endpoint, DEFAULT_REQUEST_TIMEOUT).await
+}
+
+/// Like `get_request`, but gives up once `timeout` has elapsed so a wedged agent
+/// holding the socket cannot block the controller.
+pub async fn get_request_with_timeout<T>(
+    uds_path: impl AsRef<Path>,
+    endpoint: &str,
+    timeout: Duration,
+) -> Result<T, GetRequestError>
 where
     T: serde::de::DeserializeOwned + Send,
 {
     let client: Client<UnixConnector, Full<Bytes>> = Client::unix();
     let uri = Uri::new(uds_path, endpoint).into();
-    let response: Response<Incoming> = client.get(uri).await?;
-    parse_response_body(response).await
+    tokio::time::timeout(timeout, async {
+        let response: Response<Incoming> = client.get(uri).await?;
+        parse_response_body(response).await
+    })
+    .await
+    .map_err(|_| GetRequestError::RequestTimeout(timeout))?
+}
+
+// NOTE: Like `get_request_with_timeout`, the whole exchange including reading the
+// response body is bounded by `timeout`.
+async fn send_json_request<T, B>(
+    uds_path: impl AsRef<Path>,
+    method: Method,
+    endpoint: &str,
+    body: &B,
+    timeout: Duration,
+) -> Result<T, GetRequestError>
+where
+    T: DeserializeOwned + Send,
+    B: Serialize + ?Sized,
+{
+    let client: Client<UnixConnector, Full<Bytes>> = Client::unix();
+    let uri: hyper::Uri = Uri::new(uds_path, endpoint).into();
+    let body = Full::new(Bytes::from(serde_json::to_vec(body)?));
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body)?;
+    tokio::time::timeout(timeout, async {
+        let response: Response<Incoming> = client.request(request).await?;
+        parse_response_body(response).await
+    })
+    .await
+    .map_err(|_| GetRequestError::RequestTimeout(timeout))?
+}
+
+pub async fn post_request<T, B>(
+    uds_path: impl AsRef<Path>,
+    endpoint: &str,
+    body: &B,
+) -> Result<T, GetRequestError>
+where
+    T: DeserializeOwned + Send,
+    B: Serialize + ?Sized,
+{
+    send_json_request(
+        uds_path,
+        Method::POST,
+        endpoint,
+        body,
+        DEFAULT_REQUEST_TIMEOUT,
+    )
+    .await
+}
+
+pub async fn put_request<T, B>(
+    uds_path: impl AsRef<Path>,
+    endpoint: &str,
+    body: &B,
+) -> Result<T, GetRequestError>
+where
+    T: DeserializeOwned + Send,
+    B: Serialize + ?Sized,
+{
+    send_json_request(
+        uds_path,
+        Method::PUT,
+        endpoint,
+        body,
+        DEFAULT_REQUEST_TIMEOUT,
+    )
+    .await
 }
 
 pub fn change_to_executable(path: &Path) -> std::io::Result<()> {
@@ -155,7 +246,10 @@ pub fn change_to_executable(path: &Path) -> std::io::Result<()> {
 
 // NOTE: the LISTEN_FDS is assigned from 3.
 // ref: https://manpages.debian.org/testing/libsystemd-dev/sd_listen_fds.3.en.html
-static DEFAULT_FD: RawFd = 3;
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+// Matches `FileDescriptorName=` in the packaged nodex socket unit.
+pub const NODEX_FD_NAME: &str = "nodex";
 
 #[derive(Debug, thiserror::Error)]
 pub enum GetFdError {
@@ -167,9 +261,17 @@ pub enum GetFdError {
     ListenPidMismatch { listen_pid: i32, current_pid: i32 },
     #[error("No file descriptors passed by systemd.")]
     NoFileDescriptors,
+    #[error("No file descriptor named {0:?} passed by systemd.")]
+    NamedFdNotFound(String),
 }
 
-pub fn get_fd_from_systemd() -> Result<RawFd, GetFdError> {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenFd {
+    pub fd: RawFd,
+    pub name: Option<String>,
+}
+
+pub fn get_fds_from_systemd() -> Result<Vec<ListenFd>, GetFdError> {
     let listen_fds = env::var("LISTEN_FDS")
         .ok()
         .and_then(|x| x.parse::<i32>().ok())
@@ -189,26 +291,200 @@ pub fn get_fd_from_systemd() -> Result<RawFd, GetFdError> {
     } else if listen_fds <= 0 {
         return Err(GetFdError::NoFileDescriptors);
     }
-    Ok(DEFAULT_FD)
+
+    // LISTEN_FDNAMES is colon separated; ignore it if it doesn't line up with LISTEN_FDS.
+    let names: Option<Vec<String>> = env::var("LISTEN_FDNAMES")
+        .ok()
+        .map(|names| names.split(':').map(str::to_string).collect::<Vec<_>>())
+        .filter(|names| names.len() == listen_fds as usize);
+
+    Ok((0..listen_fds)
+        .map(|i| ListenFd {
+            fd: SD_LISTEN_FDS_START + i,
+            name: names.as_ref().map(|names| names[i as usize].clone()),
+        })
+        .collect())
+}
+
+/// Picks the fd named `name`, or the first one when systemd didn't pass names
+/// (LISTEN_FDNAMES needs systemd >= 227).
+pub fn select_listen_fd(fds: &[ListenFd], name: &str) -> Result<RawFd, GetFdError> {
+    let first = fds.first().ok_or(GetFdError::NoFileDescriptors)?;
+    if fds.iter().all(|fd| fd.name.is_none()) {
+        return Ok(first.fd);
+    }
+    fds.iter()
+        .find(|fd| fd.name.as_deref() == Some(name))
+        .map(|fd| fd.fd)
+        .ok_or_else(|| GetFdError::NamedFdNotFound(name.to_string()))
+}
+
+pub fn get_fd_from_systemd() -> Result<RawFd, GetFdError> {
+    select_listen_fd(&get_fds_from_systemd()?, NODEX_FD_NAME)
 }
 
 #[cfg(all(test, unix))]
 mod tests {
     use super::*;
+    use serial_test::serial;
     use std::env;
 
+    // Minimal HTTP/1.1 server that answers every request with its own body.
+    async fn spawn_echo_server(uds_path: &Path) -> tokio::task::JoinHandle<()> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::UnixListener::bind(uds_path).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buf = Vec::new();
+            let mut chunk = [0u8; 1024];
+            let header_end = loop {
+                let n = stream.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+                if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break pos + 4;
+                }
+            };
+            let headers = String::from_utf8_lossy(&buf[..header_end]).to_lowercase();
+            let content_length: usize = headers
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length:"))
+                .map(|v| v.trim().parse().unwrap())
+                .unwrap_or(0);
+            while buf.len() < header_end + content_length {
+                let n = stream.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+            }
+            let body = &buf[header_end..header_end + content_length];
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream.write_all(body).await.unwrap();
+        })
+    }
+
+    #[tokio::test]
+    async fn test_post_and_put_request_echo_body() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let body = serde_json::json!({ "version": "1.2.3" });
+
+        let uds_path = temp_dir.path().join("post.sock");
+        let server = spawn_echo_server(&uds_path).await;
+        let echoed: serde_json::Value = post_request(&uds_path, "/internal/version/update", &body)
+            .await
+            .unwrap();
+        assert_eq!(echoed, body);
+        server.await.unwrap();
+
+        let uds_path = temp_dir.path().join("put.sock");
+        let server = spawn_echo_server(&uds_path).await;
+        let echoed: serde_json::Value = put_request(&uds_path, "/internal/version/update", &body)
+            .await
+            .unwrap();
+        assert_eq!(echoed, body);
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_get_request_times_out() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let uds_path = temp_dir.path().join("slow.sock");
+        let listener = tokio::net::UnixListener::bind(&uds_path).unwrap();
+        let server = tokio::spawn(async move {
+            let (_stream, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        let timeout = Duration::from_millis(200);
+        let result: Result<serde_json::Value, _> =
+            get_request_with_timeout(&uds_path, "/internal/version/get", timeout).await;
+        assert!(
+            matches!(result, Err(GetRequestError::RequestTimeout(t)) if t == timeout),
+            "{:?}",
+            result
+        );
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_post_request_times_out() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let uds_path = temp_dir.path().join("slow.sock");
+        let listener = tokio::net::UnixListener::bind(&uds_path).unwrap();
+        let server = tokio::spawn(async move {
+            let (_stream, _) = listener.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+
+        let timeout = Duration::from_millis(200);
+        let body = serde_json::json!({ "version": "1.2.3" });
+        let result: Result<serde_json::Value, _> = send_json_request(
+            &uds_path,
+            Method::POST,
+            "/internal/version/update",
+            &body,
+            timeout,
+        )
+        .await;
+        assert!(
+            matches!(result, Err(GetRequestError::RequestTimeout(t)) if t == timeout),
+            "{:?}",
+            result
+        );
+        server.abort();
+    }
+
+    fn set_listen_env(fds: &str, names: Option<&str>) {
+        env::set_var("LISTEN_FDS", fds);
+        env::set_var("LISTEN_PID", std::process::id().to_string());
+        match names {
+            Some(names) => env::set_var("LISTEN_FDNAMES", names),
+            None => env::remove_var("LISTEN_FDNAMES"),
+        }
+    }
+
     #[test]
+    #[serial]
     fn test_setup_listener_with_systemd_activation() {
-        env::set_var("LISTEN_FDS", "1");
-        env::set_var("LISTEN_PID", std::process::id().to_string());
+        set_listen_env("1", None);
 
         let result = get_fd_from_systemd();
         assert!(result.is_ok(), "Systemd socket activation should succeed");
         let listener_fd = result.unwrap();
 
         assert_eq!(
-            listener_fd, DEFAULT_FD,
-            "Listener FD should match DEFAULT_FD"
+            listener_fd, SD_LISTEN_FDS_START,
+            "Listener FD should match SD_LISTEN_FDS_START"
         );
     }
+
+    #[test]
+    #[serial]
+    fn test_get_fds_from_systemd_multiple() {
+        set_listen_env("3", Some("metrics:nodex:other"));
+
+        let fds = get_fds_from_systemd().unwrap();
+        assert_eq!(
+            fds.iter().map(|fd| fd.fd).collect::<Vec<_>>(),
+            vec![3, 4, 5]
+        );
+        assert_eq!(fds[1].name.as_deref(), Some("nodex"));
+        assert_eq!(get_fd_from_systemd().unwrap(), 4);
+        assert!(matches!(
+            select_listen_fd(&fds, "missing"),
+            Err(GetFdError::NamedFdNotFound(_))
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn test_get_fds_from_systemd_mismatched_names() {
+        set_listen_env("2", Some("only-one"));
+
+        let fds = get_fds_from_systemd().unwrap();
+        assert!(fds.iter().all(|fd| fd.name.is_none()));
+        assert_eq!(get_fd_from_systemd().unwrap(), SD_LISTEN_FDS_START);
+    }
 }
@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::{did_repository::DidRepository, sidetree::payload::DidResolutionResponse};
+use crate::keyring::keypair::KeyPairing;
+
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+pub const DEFAULT_CACHE_CAPACITY: usize = 256;
+
+struct CacheEntry {
+    response: DidResolutionResponse,
+    resolved_at: Instant,
+    last_used: Instant,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+#[derive(Default)]
+struct CacheCounters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+// NOTE: Memoizes successful `find_identifier` resolutions for `ttl`. Misses (`None`) and
+// errors are passed through uncached so a newly published DID is seen immediately.
+// When full, the least recently used entry is evicted. Clones share the same cache.
+#[derive(Clone)]
+pub struct CachingDidRepository<R: DidRepository> {
+    inner: R,
+    ttl: Duration,
+    capacity: usize,
+    entries: Arc<Mutex<HashMap<String, CacheEntry>>>,
+    counters: Arc<CacheCounters>,
+}
+
+impl<R: DidRepository> CachingDidRepository<R> {
+    pub fn new(inner: R) -> Self {
+        Self::with_config(inner, DEFAULT_CACHE_TTL, DEFAULT_CACHE_CAPACITY)
+    }
+
+    pub fn with_config(inner: R, ttl: Duration, capacity: usize) -> Self {
+        Self {
+            inner,
+            ttl,
+            capacity,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            counters: Arc::new(CacheCounters::default()),
+        }
+    }
+
+    // NOTE: `evictions` counts entries dropped to make room, not entries that expired.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            evictions: self.counters.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    fn get_cached(&self, did: &str) -> Option<DidResolutionResponse> {
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        match entries.get_mut(did) {
+            Some(entry) if now.duration_since(entry.resolved_at) < self.ttl => {
+                entry.last_used = now;
+                Some(entry.response.clone())
+            }
+            Some(_) => {
+                entries.remove(did);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, did: &str, response: &DidResolutionResponse) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        entries.retain(|_, entry| now.duration_since(entry.resolved_at) < self.ttl);
+        if entries.len() >= self.capacity && !entries.contains_key(did) {
+            let lru = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(did, _)| did.clone());
+            if let Some(lru) = lru {
+                entries.remove(&lru);
+                self.counters.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        entries.insert(
+            did.to_string(),
+            CacheEntry {
+                response: response.clone(),
+                resolved_at: now,
+                last_used: now,
+            },
+        );
+    }
+}
+
+impl<R: DidRepository> DidRepository for CachingDidRepository<R> {
+    type CreateIdentifierError = R::CreateIdentifierError;
+    type FindIdentifierError = R::FindIdentifierError;
+
+    async fn create_identifier(
+        &self,
+        keyring: KeyPairing,
+    ) -> Result<DidResolutionResponse, Self::CreateIdentifierError> {
+        self.inner.create_identifier(keyring).await
+    }
+
+    async fn find_identifier(
+        &self,
+        did: &str,
+    ) -> Result<Option<DidResolutionResponse>, Self::FindIdentifierError> {
+        if let Some(response) = self.get_cached(did) {
+            self.counters.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(Some(response));
+        }
+        self.counters.misses.fetch_add(1, Ordering::Relaxed);
+        let response = self.inner.find_identifier(did).await?;
+        if let Some(response) = &response {
+            self.insert(did, response);
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::did::did_repository::{
+        mocks::{DummyError, MockDidRepository},
+        CreateIdentifierError, FindIdentifierError,
+    };
+
+    const ERROR_DID: &str = "did:nodex:test:error";
+
+    #[derive(Clone)]
+    struct CountingDidRepository {
+        inner: MockDidRepository,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl CountingDidRepository {
+        fn new(dids: &[&str]) -> Self {
+            let map = dids
+                .iter()
+                .map(|did| (did.to_string(), KeyPairing::create_keyring(OsRng)))
+                .collect::<BTreeMap<_, _>>();
+            Self {
+                inner: MockDidRepository::from_single(map),
+                calls: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    impl DidRepository for CountingDidRepository {
+        type CreateIdentifierError = CreateIdentifierError<DummyError>;
+        type FindIdentifierError = FindIdentifierError<DummyError>;
+        async fn create_identifier(
+            &self,
+            _keyring: KeyPairing,
+        ) -> Result<DidResolutionResponse, Self::CreateIdentifierError> {
+            unimplemented!()
+        }
+        async fn find_identifier(
+            &self,
+            did: &str,
+        ) -> Result<Option<DidResolutionResponse>, Self::FindIdentifierError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if did == ERROR_DID {
+                return Err(FindIdentifierError::SidetreeRequestFailed(
+                    "unavailable".to_string(),
+                ));
+            }
+            self.inner.find_identifier(did).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_second_lookup_within_ttl_is_cached() {
+        let inner = CountingDidRepository::new(&["did:nodex:test:a"]);
+        let repository = CachingDidRepository::new(inner.clone());
+
+        let first = repository
+            .find_identifier("did:nodex:test:a")
+            .await
+            .unwrap();
+        let second = repository
+            .find_identifier("did:nodex:test:a")
+            .await
+            .unwrap();
+
+        assert_eq!(inner.calls(), 1);
+        assert_eq!(
+            first.unwrap().did_document.id,
+            second.unwrap().did_document.id
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_entry_is_resolved_again() {
+        let inner = CountingDidRepository::new(&["did:nodex:test:a"]);
+        let repository = CachingDidRepository::with_config(inner.clone(), Duration::ZERO, 8);
+
+        repository
+            .find_identifier("did:nodex:test:a")
+            .await
+            .unwrap();
+        repository
+            .find_identifier("did:nodex:test:a")
+            .await
+            .unwrap();
+
+        assert_eq!(inner.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_misses_and_errors_are_not_cached() {
+        let inner = CountingDidRepository::new(&[]);
+        let repository = CachingDidRepository::new(inner.clone());
+
+        assert!(repository
+            .find_identifier("did:nodex:test:missing")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(repository
+            .find_identifier("did:nodex:test:missing")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(repository.find_identifier(ERROR_DID).await.is_err());
+        assert!(repository.find_identifier(ERROR_DID).await.is_err());
+
+        assert_eq!(inner.calls(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_least_recently_used_entry_is_evicted() {
+        let inner = CountingDidRepository::new(&["did:a", "did:b", "did:c"]);
+        let repository = CachingDidRepository::with_config(inner.clone(), DEFAULT_CACHE_TTL, 2);
+
+        repository.find_identifier("did:a").await.unwrap();
+        repository.find_identifier("did:b").await.unwrap();
+        // Touch `did:a` so that `did:b` is the least recently used.
+        repository.find_identifier("did:a").await.unwrap();
+        repository.find_identifier("did:c").await.unwrap();
+        assert_eq!(inner.calls(), 3);
+
+        repository.find_identifier("did:a").await.unwrap();
+        assert_eq!(inner.calls(), 3);
+        repository.find_identifier("did:b").await.unwrap();
+        assert_eq!(inner.calls(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_stats_count_hits_misses_and_evictions() {
+        let inner = CountingDidRepository::new(&["did:a", "did:b", "did:c"]);
+        let repository = CachingDidRepository::with_config(inner.clone(), DEFAULT_CACHE_TTL, 2);
+        assert_eq!(repository.stats(), CacheStats::default());
+
+        repository.find_identifier("did:a").await.unwrap();
+        repository.find_identifier("did:a").await.unwrap();
+        repository.find_identifier("did:b").await.unwrap();
+        repository.find_identifier("did:c").await.unwrap();
+        repository.find_identifier("did:missing").await.unwrap();
+
+        // Clones share the counters as well as the entries.
+        assert_eq!(
+            repository.clone().stats(),
+            CacheStats {
+                hits: 1,
+                misses: 4,
+                evictions: 1,
+            }
+        );
+    }
+}
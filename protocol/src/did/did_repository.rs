@@ -58,10 +58,27 @@ fn get_key(key_type: &str, did_document: &DidDocument) -> Result<Jwk, GetPublicK
 }
 
 pub fn get_sign_key(did_document: &DidDocument) -> Result<k256::PublicKey, GetPublicKeyError> {
-    let public_key = get_key("#signingKey", did_document)?;
+    get_sign_key_by_id(did_document, "#signingKey")
+}
+
+pub fn get_sign_key_by_id(
+    did_document: &DidDocument,
+    key_id: &str,
+) -> Result<k256::PublicKey, GetPublicKeyError> {
+    let public_key = get_key(key_id, did_document)?;
     Ok(public_key.try_into()?)
 }
 
+// NOTE: Every secp256k1 key in the document; keys of other types are skipped.
+pub fn get_sign_keys(did_document: &DidDocument) -> Vec<k256::PublicKey> {
+    did_document
+        .public_key
+        .iter()
+        .flatten()
+        .filter_map(|pk| pk.public_key_jwk.clone().try_into().ok())
+        .collect()
+}
+
 pub fn get_encrypt_key(
     did_document: &DidDocument,
 ) -> Result<x25519_dalek::PublicKey, GetPublicKeyError> {
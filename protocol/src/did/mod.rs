@@ -1,3 +1,4 @@
+pub mod caching_did_repository;
 pub mod did_repository;
 pub mod sidetree;
 
@@ -1,6 +1,7 @@
 use cuid;
 pub use didcomm_rs;
 use didcomm_rs::{crypto::CryptoAlgorithm, AttachmentBuilder, AttachmentDataBuilder, Message};
+use futures::future::try_join_all;
 pub use serde_json;
 use serde_json::Value;
 use thiserror::Error;
@@ -30,6 +31,14 @@ pub trait DidCommEncryptedService: Sync {
         to_did: &str,
         metadata: Option<&Value>,
     ) -> Result<DidCommMessage, Self::GenerateError>;
+    // Returns one message per recipient, in the order of `to_dids`.
+    async fn generate_for_recipients(
+        &self,
+        model: VerifiableCredentials,
+        from_keyring: &KeyPairing,
+        to_dids: &[String],
+        metadata: Option<&Value>,
+    ) -> Result<Vec<DidCommMessage>, Self::GenerateError>;
     async fn verify(
         &self,
         my_keyring: &KeyPairing,
@@ -37,6 +46,9 @@ pub trait DidCommEncryptedService: Sync {
     ) -> Result<VerifiedContainer, Self::VerifyError>;
 }
 
+// Upper bound on recipient DID documents resolved at the same time.
+pub const MAX_CONCURRENT_RESOLUTIONS: usize = 8;
+
 fn didcomm_generate<R: DidRepository, V: DidVcService>(
     body: &VerifiableCredentials,
     from_keyring: &KeyPairing,
@@ -101,16 +113,63 @@ async fn generate<R: DidRepository, V: DidVcService>(
     let body = vc_service
         .generate(model, from_keyring)
         .map_err(DidCommEncryptedServiceGenerateError::VcService)?;
-    let to_doc = did_repository
+    let to_doc = find_recipient::<R, V>(did_repository, to_did).await?;
+
+    didcomm_generate::<R, V>(&body, from_keyring, &to_doc, metadata, attachment_link)
+}
+
+async fn find_recipient<R: DidRepository, V: DidVcService>(
+    did_repository: &R,
+    to_did: &str,
+) -> Result<
+    DidDocument,
+    DidCommEncryptedServiceGenerateError<R::FindIdentifierError, V::GenerateError>,
+> {
+    Ok(did_repository
         .find_identifier(to_did)
         .await
         .map_err(DidCommEncryptedServiceGenerateError::SidetreeFindRequestFailed)?
         .ok_or(DidCommEncryptedServiceGenerateError::DidDocNotFound(
             to_did.to_string(),
         ))?
-        .did_document;
+        .did_document)
+}
 
-    didcomm_generate::<R, V>(&body, from_keyring, &to_doc, metadata, attachment_link)
+async fn generate_for_recipients<R: DidRepository, V: DidVcService>(
+    did_repository: &R,
+    vc_service: &V,
+    model: VerifiableCredentials,
+    from_keyring: &KeyPairing,
+    to_dids: &[String],
+    metadata: Option<&Value>,
+    attachment_link: Option<&str>,
+) -> Result<
+    Vec<DidCommMessage>,
+    DidCommEncryptedServiceGenerateError<R::FindIdentifierError, V::GenerateError>,
+> {
+    let body = vc_service
+        .generate(model, from_keyring)
+        .map_err(DidCommEncryptedServiceGenerateError::VcService)?;
+
+    // NOTE: Resolve recipients concurrently, at most `MAX_CONCURRENT_RESOLUTIONS` at a time,
+    // so that a large group does not flood the DID resolver. `try_join_all` keeps the order.
+    let mut to_docs = Vec::with_capacity(to_dids.len());
+    for chunk in to_dids.chunks(MAX_CONCURRENT_RESOLUTIONS) {
+        let docs = try_join_all(
+            chunk
+                .iter()
+                .map(|to_did| find_recipient::<R, V>(did_repository, to_did)),
+        )
+        .await?;
+        to_docs.extend(docs);
+    }
+
+    to_docs
+        .iter()
+        .map(|to_doc| {
+            didcomm_generate::<R, V>(&body, from_keyring, to_doc, metadata, attachment_link)
+        })
+        .collect()
 }
 
 fn didcomm_verify<R: DidRepository>(
@@ -235,6 +294,17 @@ where
         generate::<R, R>(self, self, model, from_keyring, to_did, metadata, None).await
     }
 
+    async fn generate_for_recipients(
+        &self,
+        model: VerifiableCredentials,
+        from_keyring: &KeyPairing,
+        to_dids: &[String],
+        metadata: Option<&Value>,
+    ) -> Result<Vec<DidCommMessage>, Self::GenerateError> {
+        generate_for_recipients::<R, R>(self, self, model, from_keyring, to_dids, metadata, None)
+            .await
+    }
+
     async fn verify(
         &self,
         my_keyring: &KeyPairing,
@@ -290,6 +360,25 @@ where
         .await
     }
 
+    async fn generate_for_recipients(
+        &self,
+        model: VerifiableCredentials,
+        from_keyring: &KeyPairing,
+        to_dids: &[String],
+        metadata: Option<&Value>,
+    ) -> Result<Vec<DidCommMessage>, Self::GenerateError> {
+        generate_for_recipients::<R, R>(
+            &self.vc_service,
+            &self.vc_service,
+            model,
+            from_keyring,
+            to_dids,
+            metadata,
+            Some(&self.attachment_link),
+        )
+        .await
+    }
+
     async fn verify(
         &self,
         my_keyring: &KeyPairing,
@@ -349,6 +438,128 @@ mod tests {
         assert_eq!(verified.credential_subject.container, message);
     }
 
+    mod generate_for_recipients {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        use super::*;
+        use crate::{
+            did::{
+                did_repository::{
+                    mocks::DummyError, CreateIdentifierError, DidRepository, FindIdentifierError,
+                },
+                sidetree::payload::DidResolutionResponse,
+            },
+            didcomm::encrypted::MAX_CONCURRENT_RESOLUTIONS,
+        };
+
+        #[derive(Clone)]
+        struct ConcurrencyRecordingDidRepository {
+            inner: MockDidRepository,
+            in_flight: Arc<AtomicUsize>,
+            max_in_flight: Arc<AtomicUsize>,
+        }
+
+        impl ConcurrencyRecordingDidRepository {
+            fn new(inner: MockDidRepository) -> Self {
+                Self {
+                    inner,
+                    in_flight: Arc::new(AtomicUsize::new(0)),
+                    max_in_flight: Arc::new(AtomicUsize::new(0)),
+                }
+            }
+        }
+
+        impl DidRepository for ConcurrencyRecordingDidRepository {
+            type CreateIdentifierError = CreateIdentifierError<DummyError>;
+            type FindIdentifierError = FindIdentifierError<DummyError>;
+            async fn create_identifier(
+                &self,
+                _keyring: KeyPairing,
+            ) -> Result<DidResolutionResponse, Self::CreateIdentifierError> {
+                unimplemented!()
+            }
+            async fn find_identifier(
+                &self,
+                did: &str,
+            ) -> Result<Option<DidResolutionResponse>, Self::FindIdentifierError> {
+                let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                let res = self.inner.find_identifier(did).await;
+                self.in_flight.fetch_sub(1, Ordering::SeqCst);
+                res
+            }
+        }
+
+        #[tokio::test]
+        async fn test_recipients_are_resolved_concurrently_in_order() {
+            let from_did = create_random_did();
+            let from_keyring = KeyPairing::create_keyring(OsRng);
+
+            let recipients = (0..MAX_CONCURRENT_RESOLUTIONS + 2)
+                .map(|_| (create_random_did(), KeyPairing::create_keyring(OsRng)))
+                .collect::<Vec<_>>();
+            let to_dids = recipients
+                .iter()
+                .map(|(did, _)| did.clone())
+                .collect::<Vec<_>>();
+
+            let mut keyrings = BTreeMap::from_iter(recipients.iter().cloned());
+            keyrings.insert(from_did.clone(), from_keyring.clone());
+            let repo =
+                ConcurrencyRecordingDidRepository::new(MockDidRepository::from_single(keyrings));
+
+            let message = json!({"test": "0123456789abcdef"});
+            let model = VerifiableCredentials::new(from_did.clone(), message.clone(), Utc::now());
+            let res = repo
+                .generate_for_recipients(model, &from_keyring, &to_dids, None)
+                .await
+                .unwrap();
+
+            let max_in_flight = repo.max_in_flight.load(Ordering::SeqCst);
+            assert!(max_in_flight > 1, "resolved serially");
+            assert!(max_in_flight <= MAX_CONCURRENT_RESOLUTIONS);
+
+            assert_eq!(res.len(), recipients.len());
+            for (message_for, (_, to_keyring)) in res.iter().zip(recipients.iter()) {
+                let verified = repo.verify(to_keyring, message_for).await.unwrap();
+                assert_eq!(verified.message.issuer.id, from_did);
+                assert_eq!(verified.message.credential_subject.container, message);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_unknown_recipient_fails_all() {
+            let from_did = create_random_did();
+            let to_did = create_random_did();
+            let unknown_did = create_random_did();
+
+            let from_keyring = KeyPairing::create_keyring(OsRng);
+            let to_keyring = KeyPairing::create_keyring(OsRng);
+
+            let repo = MockDidRepository::from_single(BTreeMap::from_iter([
+                (from_did.clone(), from_keyring.clone()),
+                (to_did.clone(), to_keyring),
+            ]));
+
+            let message = json!({"test": "0123456789abcdef"});
+            let model = VerifiableCredentials::new(from_did, message, Utc::now());
+            let res = repo
+                .generate_for_recipients(model, &from_keyring, &[to_did, unknown_did.clone()], None)
+                .await
+                .unwrap_err();
+
+            if let DidCommEncryptedServiceGenerateError::DidDocNotFound(did) = res {
+                assert_eq!(did, unknown_did);
+            } else {
+                panic!("unexpected result: {:?}", res);
+            }
+        }
+    }
+
     mod generate_failed {
         use super::*;
         use crate::did::did_repository::mocks::NoPublicKeyDidRepository;
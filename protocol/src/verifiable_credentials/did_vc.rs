@@ -1,7 +1,7 @@
 use thiserror::Error;
 
 use crate::{
-    did::did_repository::{get_sign_key, DidRepository, GetPublicKeyError},
+    did::did_repository::{get_sign_key_by_id, get_sign_keys, DidRepository, GetPublicKeyError},
     keyring::keypair,
     verifiable_credentials::{
         credential_signer::{
@@ -37,6 +37,8 @@ pub enum DidVcServiceVerifyError<FindIdentifierError: std::error::Error> {
     FindIdentifier(FindIdentifierError),
     #[error("credential signer error")]
     VerifyFailed(#[from] CredentialSignerVerifyError),
+    #[error("no key in the DID document of {0} verifies the credential")]
+    NoVerifyingKey(String),
 }
 
 impl<R: DidRepository> DidVcService for R {
@@ -71,8 +73,30 @@ impl<R: DidRepository> DidVcService for R {
                 model.issuer.id.clone(),
             ))?
             .did_document;
-        let public_key = get_sign_key(&did_document)?;
-        Ok(CredentialSigner::verify(model, &public_key)?)
+        // NOTE: The proof names its key as `<did>#<key id>`, and the document lists it as `#<key id>`.
+        let key_id = model
+            .proof
+            .as_ref()
+            .ok_or(CredentialSignerVerifyError::ProofNotFound)?
+            .verification_method
+            .split_once('#')
+            .map(|(_, key_id)| format!("#{}", key_id));
+        match key_id {
+            Some(key_id) => {
+                let public_key = get_sign_key_by_id(&did_document, &key_id)?;
+                Ok(CredentialSigner::verify(model, &public_key)?)
+            }
+            None => {
+                let public_keys = get_sign_keys(&did_document);
+                if public_keys.is_empty() {
+                    return Err(GetPublicKeyError::PublicKeyNotFound(did_document.id).into());
+                }
+                public_keys
+                    .iter()
+                    .find_map(|public_key| CredentialSigner::verify(model.clone(), public_key).ok())
+                    .ok_or(DidVcServiceVerifyError::NoVerifyingKey(did_document.id))
+            }
+        }
     }
 }
 
@@ -115,6 +139,154 @@ mod tests {
         assert_eq!(verified.credential_subject.container, message);
     }
 
+    #[test]
+    fn test_generate_with_proof_details() {
+        let from_did = create_random_did();
+        let from_keyring = KeyPairing::create_keyring(OsRng);
+        let service = MockDidRepository::from_single(BTreeMap::new());
+
+        let model = VerifiableCredentials::new(from_did.clone(), json!({}), Utc::now());
+        let vc = service.generate(model, &from_keyring).unwrap();
+        let proof = vc.proof.clone().unwrap();
+
+        let plain = serde_json::to_value(&vc).unwrap();
+        assert!(plain.get("proofDetails").is_none());
+
+        let expanded = vc.to_value_with_proof_details().unwrap();
+        assert_eq!(
+            expanded["proofDetails"],
+            json!({
+                "algorithm": "ES256K",
+                "created": proof.created,
+                "keyId": "signingKey",
+                "verificationMethod": format!("{}#signingKey", from_did),
+                "jws": proof.jws,
+            })
+        );
+        assert_eq!(expanded["proof"], plain["proof"]);
+
+        let roundtrip: VerifiableCredentials = serde_json::from_value(expanded).unwrap();
+        assert_eq!(roundtrip, vc);
+    }
+
+    mod two_keys {
+        use super::*;
+        use crate::did::did_repository::DidRepository;
+        use crate::did::{
+            did_repository::{mocks::DummyError, CreateIdentifierError, FindIdentifierError},
+            sidetree::payload::{DidDocument, DidPublicKey, DidResolutionResponse, MethodMetadata},
+        };
+        use crate::keyring::keypair::KeyPair;
+        use crate::verifiable_credentials::credential_signer::{
+            CredentialSigner, CredentialSignerSuite,
+        };
+
+        // A document holding a rotated-out key first and the current signing key second.
+        struct TwoKeyDidRepository {
+            old: KeyPairing,
+            current: KeyPairing,
+        }
+
+        impl DidRepository for TwoKeyDidRepository {
+            type CreateIdentifierError = CreateIdentifierError<DummyError>;
+            type FindIdentifierError = FindIdentifierError<DummyError>;
+            async fn create_identifier(
+                &self,
+                _keyring: KeyPairing,
+            ) -> Result<DidResolutionResponse, Self::CreateIdentifierError> {
+                unimplemented!()
+            }
+            async fn find_identifier(
+                &self,
+                did: &str,
+            ) -> Result<Option<DidResolutionResponse>, Self::FindIdentifierError> {
+                let key = |id: &str, keyring: &KeyPairing| DidPublicKey {
+                    id: id.to_string(),
+                    controller: String::new(),
+                    r#type: "EcdsaSecp256k1VerificationKey2019".to_string(),
+                    public_key_jwk: keyring.sign.get_public_key().try_into().unwrap(),
+                };
+                Ok(Some(DidResolutionResponse {
+                    context: "https://www.w3.org/ns/did-resolution/v1".to_string(),
+                    did_document: DidDocument {
+                        id: did.to_string(),
+                        public_key: Some(vec![
+                            key("#oldKey", &self.old),
+                            key("#currentKey", &self.current),
+                        ]),
+                        service: None,
+                        authentication: None,
+                    },
+                    method_metadata: MethodMetadata {
+                        published: true,
+                        recovery_commitment: None,
+                        update_commitment: None,
+                    },
+                }))
+            }
+        }
+
+        fn sign(did: &str, key_id: &str, keyring: &KeyPairing) -> VerifiableCredentials {
+            let model = VerifiableCredentials::new(did.to_string(), json!({}), Utc::now());
+            CredentialSigner::sign(
+                model,
+                CredentialSignerSuite {
+                    did,
+                    key_id,
+                    context: &keyring.sign,
+                },
+            )
+            .unwrap()
+        }
+
+        fn without_key_id(mut vc: VerifiableCredentials) -> VerifiableCredentials {
+            let proof = vc.proof.as_mut().unwrap();
+            proof.verification_method = vc.issuer.id.clone();
+            vc
+        }
+
+        #[tokio::test]
+        async fn test_verify_with_named_key() {
+            let did = create_random_did();
+            let service = TwoKeyDidRepository {
+                old: KeyPairing::create_keyring(OsRng),
+                current: KeyPairing::create_keyring(OsRng),
+            };
+
+            let vc = sign(&did, "currentKey", &service.current);
+            assert!(service.verify(vc).await.is_ok());
+
+            // Naming the other key of the document must not fall back to trying the rest.
+            let vc = sign(&did, "oldKey", &service.current);
+            let res = service.verify(vc).await.unwrap_err();
+            assert!(
+                matches!(res, DidVcServiceVerifyError::VerifyFailed(_)),
+                "unexpected error: {:?}",
+                res
+            );
+        }
+
+        #[tokio::test]
+        async fn test_verify_tries_each_key_when_unnamed() {
+            let did = create_random_did();
+            let service = TwoKeyDidRepository {
+                old: KeyPairing::create_keyring(OsRng),
+                current: KeyPairing::create_keyring(OsRng),
+            };
+
+            let vc = without_key_id(sign(&did, "currentKey", &service.current));
+            assert!(service.verify(vc).await.is_ok());
+
+            let vc = without_key_id(sign(&did, "currentKey", &KeyPairing::create_keyring(OsRng)));
+            let res = service.verify(vc).await.unwrap_err();
+            assert!(
+                matches!(res, DidVcServiceVerifyError::NoVerifyingKey(_)),
+                "unexpected error: {:?}",
+                res
+            );
+        }
+    }
+
     mod generate_failed {}
 
     mod verify_failed {
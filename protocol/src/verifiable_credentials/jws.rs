@@ -73,6 +73,17 @@ pub fn sign(object: &Value, secret_key: &k256::SecretKey) -> Result<String, JwsE
     Ok([header, "".to_string(), signature].join("."))
 }
 
+pub fn algorithm(jws: &str) -> Result<String, JwsDecodeError> {
+    let header = jws
+        .split('.')
+        .next()
+        .ok_or_else(|| JwsDecodeError::InvalidJws(jws.to_string()))?;
+    let decoded = BASE64URL_NOPAD.decode(header.as_bytes())?;
+    let decoded = String::from_utf8(decoded)?;
+    let header = serde_json::from_str::<JwsHeader>(&decoded)?;
+    Ok(header.alg)
+}
+
 pub fn verify(
     object: &Value,
     jws: &str,
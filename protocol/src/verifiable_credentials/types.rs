@@ -2,6 +2,8 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use super::jws::{self, JwsDecodeError};
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct VerifiedContainer {
     pub message: VerifiableCredentials,
@@ -51,6 +53,41 @@ pub struct Proof {
     pub domain: Option<String>,
 }
 
+// NOTE: Expanded view of a proof for debugging interop. It is never part of
+// the signed payload.
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub struct ProofDetails {
+    #[serde(rename = "algorithm")]
+    pub algorithm: String,
+
+    #[serde(rename = "created")]
+    pub created: DateTime<Utc>,
+
+    #[serde(rename = "keyId")]
+    pub key_id: Option<String>,
+
+    #[serde(rename = "verificationMethod")]
+    pub verification_method: String,
+
+    #[serde(rename = "jws")]
+    pub jws: String,
+}
+
+impl Proof {
+    pub fn details(&self) -> Result<ProofDetails, JwsDecodeError> {
+        Ok(ProofDetails {
+            algorithm: jws::algorithm(&self.jws)?,
+            created: self.created,
+            key_id: self
+                .verification_method
+                .split_once('#')
+                .map(|(_, key_id)| key_id.to_string()),
+            verification_method: self.verification_method.clone(),
+            jws: self.jws.clone(),
+        })
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
 pub struct VerifiableCredentials {
     // NOTE: 'id' property is optional.
@@ -98,4 +135,17 @@ impl VerifiableCredentials {
             proof: None,
         }
     }
+
+    // NOTE: Serializes the credential with an extra `proofDetails` field next
+    // to `proof`. The signed fields are left untouched.
+    pub fn to_value_with_proof_details(&self) -> Result<Value, JwsDecodeError> {
+        let mut value = serde_json::to_value(self)?;
+        if let (Some(proof), Some(object)) = (&self.proof, value.as_object_mut()) {
+            object.insert(
+                "proofDetails".to_string(),
+                serde_json::to_value(proof.details()?)?,
+            );
+        }
+        Ok(value)
+    }
 }